@@ -1,15 +1,27 @@
 use crate::{
-    devices::{Render, RenderSpec},
-    dlna,
-    error::Result,
+    control::{RelTime, RenderController},
+    devices::{Render, RenderDescription, RenderSpec},
+    dlna::{self, PlaybackOptions, SlideshowOptions},
+    embedded::SubtitleTrackSelector,
+    error::{Error, Result},
+    images::{prepare_images, ImageOptions, ImageSize},
+    inspect::{ActionSpec, DeviceTree, StateVariableSpec},
+    interactive,
+    media::{MediaKind, MediaProfile},
+    playlist::{expand_image_paths, expand_media_paths, RepeatMode},
+    quirks::QuirksDatabase,
+    status::RenderStatus,
     streaming::{
-        get_local_ip, infer_subtitle_from_video, MediaStreamingServer, STREAMING_PORT_DEFAULT,
+        get_local_ip, infer_subtitles_from_video, MediaStreamingServer, STREAMING_PORT_DEFAULT,
     },
+    subtitles::{SubtitleCandidate, SubtitleEncoding, SubtitleFormat, SubtitleOptions},
+    transcode::{TranscodeMode, TranscodeProfile, Transcoder, FFMPEG_DEFAULT},
 };
 use clap::{Args, Parser, Subcommand};
-use log::info;
+use log::{info, warn};
 use pretty_env_logger;
-use std::env;
+use rand::seq::SliceRandom;
+use std::{env, fmt, str::FromStr, time::Duration};
 
 /// A minimal UPnP/DLNA media streamer
 #[derive(Parser)]
@@ -27,6 +39,10 @@ struct Cli {
     #[clap(short = 'b', long)]
     debug: bool,
 
+    /// The quirks file overriding the behaviour of the devices (by default, crab-dlna/quirks.toml in the configuration directory, when it exists)
+    #[clap(long, parse(from_os_str), value_name = "FILE")]
+    quirks: Option<std::path::PathBuf>,
+
     #[clap(subcommand)]
     command: Commands,
 }
@@ -36,8 +52,35 @@ enum Commands {
     /// Scan and list devices in the network capable of playing media
     List(List),
 
-    /// Play a video file
+    /// Show the device tree of a device, with the actions and state variables of all its services
+    Inspect(Inspect),
+
+    /// Play video or audio files, directories or playlists
     Play(Play),
+
+    /// Show images, or a slideshow of them
+    Show(Show),
+
+    /// Pause the media being played in a device
+    Pause(Pause),
+
+    /// Resume the media paused in a device
+    Resume(Resume),
+
+    /// Stop the media being played in a device
+    Stop(Stop),
+
+    /// Seek to a position of the media being played in a device
+    Seek(Seek),
+
+    /// Show the playback status of a device
+    Status(Status),
+
+    /// Show or change the volume of a device
+    Volume(Volume),
+
+    /// Mute or unmute a device
+    Mute(Mute),
 }
 
 impl Commands {
@@ -45,7 +88,16 @@ impl Commands {
         self.setup_log(cli);
         match self {
             Self::List(list) => list.run(cli).await?,
+            Self::Inspect(inspect) => inspect.run(cli).await?,
             Self::Play(play) => play.run(cli).await?,
+            Self::Show(show) => show.run(cli).await?,
+            Self::Pause(pause) => pause.run(cli).await?,
+            Self::Resume(resume) => resume.run(cli).await?,
+            Self::Stop(stop) => stop.run(cli).await?,
+            Self::Seek(seek) => seek.run(cli).await?,
+            Self::Status(status) => status.run(cli).await?,
+            Self::Volume(volume) => volume.run(cli).await?,
+            Self::Mute(mute) => mute.run(cli).await?,
         }
         Ok(())
     }
@@ -56,7 +108,7 @@ impl Commands {
             crabldna_log.as_str()
         } else if cli.debug {
             "debug"
-        } else if cli.quiet {
+        } else if cli.quiet || self.is_interactive() {
             "warn"
         } else {
             "info"
@@ -64,19 +116,378 @@ impl Commands {
         env::set_var("RUST_LOG", log_level);
         pretty_env_logger::init();
     }
+
+    fn is_interactive(&self) -> bool {
+        matches!(self, Self::Play(play) if play.interactive)
+    }
+}
+
+/// The output format of the list of devices
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ListFormat {
+    Json,
+    Yaml,
+    Table,
+    Tsv,
+}
+
+impl FromStr for ListFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "table" => Ok(Self::Table),
+            "tsv" => Ok(Self::Tsv),
+            _ => Err(Error::DevicesListFormatParseError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for ListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+            Self::Table => "table",
+            Self::Tsv => "tsv",
+        };
+        write!(f, "{}", format)
+    }
 }
 
 #[derive(Args)]
-struct List;
+struct List {
+    /// Print the formats each device declares it can play
+    #[clap(short, long)]
+    verbose: bool,
+
+    /// Print the devices as structured records, with their services and actions (json, yaml, table or tsv)
+    #[clap(long, value_name = "FORMAT", conflicts_with = "verbose")]
+    format: Option<ListFormat>,
+}
 
 impl List {
     async fn run(&self, cli: &Cli) -> Result<()> {
         info!("List devices");
-        for render in Render::discover(cli.timeout).await? {
-            println!("{}", render);
+        let mut renders = Render::discover(cli.timeout).await?;
+        if self.verbose {
+            futures::future::join_all(renders.iter_mut().map(Render::fetch_sink_protocol_info))
+                .await;
+        }
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                for render in &renders {
+                    self.print_human(render);
+                }
+                return Ok(());
+            }
+        };
+
+        let mut descriptions = Vec::new();
+        for render in &renders {
+            descriptions.push(render.describe().await);
+        }
+        match format {
+            ListFormat::Json => println!("{}", Self::to_json(&descriptions)),
+            ListFormat::Yaml => print!("{}", to_yaml(&Self::to_json(&descriptions), 0)),
+            ListFormat::Table => Self::print_table(&descriptions),
+            ListFormat::Tsv => Self::print_tsv(&descriptions),
+        }
+        Ok(())
+    }
+
+    fn print_human(&self, render: &Render) {
+        println!("{}", render);
+        if self.verbose {
+            match render.sink_protocol_info.is_empty() {
+                true => println!("    (no supported format declared)"),
+                false => {
+                    for protocol_info in &render.sink_protocol_info {
+                        println!("    {}", protocol_info);
+                    }
+                }
+            }
+        }
+    }
+
+    fn to_json(descriptions: &[RenderDescription]) -> serde_json::Value {
+        serde_json::to_value(descriptions).expect("the descriptions of renders are serializable")
+    }
+
+    fn print_table(descriptions: &[RenderDescription]) {
+        let header = ["NAME", "MANUFACTURER", "MODEL", "IP", "LOCATION"];
+        let rows: Vec<[&str; 5]> = descriptions
+            .iter()
+            .map(|description| {
+                [
+                    description.friendly_name.as_str(),
+                    description.manufacturer.as_str(),
+                    description.model_name.as_str(),
+                    description.ip.as_str(),
+                    description.location.as_str(),
+                ]
+            })
+            .collect();
+        let mut widths = header.map(|column| column.chars().count());
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<_>>()
+                .join("  ");
+            println!("{}", line.trim_end());
+        }
+    }
+
+    fn print_tsv(descriptions: &[RenderDescription]) {
+        println!("udn\tfriendly_name\tmanufacturer\tmodel_name\tmodel_number\tdevice_type\tlocation\tip\tservices\tactions");
+        for description in descriptions {
+            let services = description
+                .services
+                .iter()
+                .map(|service| service.service_type.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+            let mut actions: Vec<&str> = Vec::new();
+            for action in description
+                .services
+                .iter()
+                .flat_map(|service| &service.actions)
+            {
+                if !actions.contains(&action.as_str()) {
+                    actions.push(action);
+                }
+            }
+            let fields = [
+                description.udn.as_str(),
+                &description.friendly_name,
+                &description.manufacturer,
+                &description.model_name,
+                &description.model_number,
+                &description.device_type,
+                &description.location,
+                &description.ip,
+                &services,
+                &actions.join(","),
+            ];
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|field| field.replace(['\t', '\n', '\r'], " "))
+                .collect();
+            println!("{}", fields.join("\t"));
+        }
+    }
+}
+
+/// Writes a JSON value as YAML, with the strings quoted as in JSON (which YAML accepts)
+fn to_yaml(value: &serde_json::Value, indent: usize) -> String {
+    use serde_json::Value;
+
+    let is_scalar = |value: &Value| match value {
+        Value::Array(items) => items.is_empty(),
+        Value::Object(fields) => fields.is_empty(),
+        _ => true,
+    };
+    let scalar = |value: &Value| match value {
+        Value::Array(_) => "[]".to_string(),
+        Value::Object(_) => "{}".to_string(),
+        _ => value.to_string(),
+    };
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Array(items) if !items.is_empty() => items
+            .iter()
+            .map(|item| match is_scalar(item) {
+                true => format!("{}- {}\n", pad, scalar(item)),
+                false => format!("{}- {}", pad, &to_yaml(item, indent + 2)[indent + 2..]),
+            })
+            .collect(),
+        Value::Object(fields) if !fields.is_empty() => fields
+            .iter()
+            .map(|(key, field)| match is_scalar(field) {
+                true => format!("{}{}: {}\n", pad, key, scalar(field)),
+                false => format!("{}{}:\n{}", pad, key, to_yaml(field, indent + 2)),
+            })
+            .collect(),
+        _ => format!("{}{}\n", pad, scalar(value)),
+    }
+}
+
+#[derive(Args)]
+struct Inspect {
+    /// The device to inspect, through its location URL or a query (the first device found if not provided)
+    #[clap(value_name = "DEVICE", conflicts_with_all = &["device-query", "device-url"])]
+    device: Option<String>,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+impl Inspect {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let render = match &self.device {
+            Some(device) if device.starts_with("http://") || device.starts_with("https://") => {
+                RenderArgs {
+                    device_query: None,
+                    device_url: Some(device.to_owned()),
+                }
+            }
+            Some(device) => RenderArgs {
+                device_query: Some(device.to_owned()),
+                device_url: None,
+            },
+            None => RenderArgs {
+                device_query: self.render.device_query.clone(),
+                device_url: self.render.device_url.clone(),
+            },
+        }
+        .select_render(cli)
+        .await?;
+        info!("Inspecting device");
+        let tree = DeviceTree::inspect(&render.device).await;
+        println!("{}", render);
+        for (field, value) in [
+            ("Manufacturer", render.manufacturer.as_str()),
+            ("Model name", render.model_name.as_str()),
+            ("Model number", render.model_number.as_str()),
+            ("UDN", render.udn.as_str()),
+        ] {
+            if !value.is_empty() {
+                println!("{}: {}", field, value);
+            }
+        }
+        if !render.quirks.names.is_empty() {
+            println!("Quirks: {}", render.quirks.names.join(", "));
         }
+        Self::print_device(&tree, 0);
         Ok(())
     }
+
+    fn print_device(device: &DeviceTree, indent: usize) {
+        let pad = " ".repeat(indent);
+        println!();
+        println!(
+            "{}Device {} [{}]",
+            pad, device.friendly_name, device.device_type
+        );
+        for service in &device.services {
+            println!(
+                "{}  Service {} [{}]",
+                pad, service.service_id, service.service_type
+            );
+            if let Some(error) = &service.error {
+                println!("{}    (description not available: {})", pad, error);
+                continue;
+            }
+            println!("{}    Actions:", pad);
+            for action in &service.actions {
+                println!("{}      {}", pad, Self::format_action(action));
+            }
+            println!("{}    State variables:", pad);
+            for state_variable in &service.state_variables {
+                println!(
+                    "{}      {}",
+                    pad,
+                    Self::format_state_variable(state_variable)
+                );
+            }
+        }
+        for embedded_device in &device.devices {
+            Self::print_device(embedded_device, indent + 2);
+        }
+    }
+
+    fn format_action(action: &ActionSpec) -> String {
+        let arguments = action
+            .input_arguments
+            .iter()
+            .map(|argument| ("in", argument))
+            .chain(
+                action
+                    .output_arguments
+                    .iter()
+                    .map(|argument| ("out", argument)),
+            )
+            .map(|(direction, argument)| {
+                format!(
+                    "{} {}: {}",
+                    direction, argument.name, argument.state_variable
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}({})", action.name, arguments)
+    }
+
+    fn format_state_variable(state_variable: &StateVariableSpec) -> String {
+        let kind = match (&state_variable.data_type, &state_variable.allowed_range) {
+            (Some(data_type), _) => data_type.clone(),
+            (None, Some(allowed_range)) => format!("range {}", allowed_range),
+            (None, None) => format!("one of [{}]", state_variable.allowed_values.join(", ")),
+        };
+        let mut attributes = Vec::new();
+        if let Some(default) = &state_variable.default {
+            attributes.push(format!("default {}", default));
+        }
+        if state_variable.sends_events {
+            attributes.push("evented".to_string());
+        }
+        match attributes.is_empty() {
+            true => format!("{}: {}", state_variable.name, kind),
+            false => format!(
+                "{}: {} ({})",
+                state_variable.name,
+                kind,
+                attributes.join(", ")
+            ),
+        }
+    }
+}
+
+#[derive(Args)]
+struct RenderArgs {
+    /// Specify the device through a query (scan devices before selecting it)
+    #[clap(short = 'q', long = "query-device")]
+    device_query: Option<String>,
+
+    /// Specify the device through its exact location (no scan, faster)
+    #[clap(short, long = "device")]
+    device_url: Option<String>,
+}
+
+impl RenderArgs {
+    async fn select_render(&self, cli: &Cli) -> Result<Render> {
+        info!("Selecting render");
+        let mut render = Render::new(if let Some(device_url) = &self.device_url {
+            RenderSpec::Location(device_url.to_owned())
+        } else if let Some(device_query) = &self.device_query {
+            RenderSpec::Query(cli.timeout, device_query.to_owned())
+        } else {
+            RenderSpec::First(cli.timeout)
+        })
+        .await?;
+        if let Some(quirks_path) = cli.quirks.clone().or_else(QuirksDatabase::user_path) {
+            info!("Reading quirks file: {}", quirks_path.display());
+            render.apply_quirks(&QuirksDatabase::load(&quirks_path)?);
+        }
+        if !render.quirks.names.is_empty() {
+            info!("Device quirks: {}", render.quirks.names.join(", "));
+        }
+        Ok(render)
+    }
+
+    async fn select_controller(&self, cli: &Cli) -> Result<RenderController> {
+        Ok(RenderController::new(self.select_render(cli).await?))
+    }
 }
 
 #[derive(Args)]
@@ -89,61 +500,490 @@ struct Play {
     #[clap(short = 'P', long = "port", default_value_t=STREAMING_PORT_DEFAULT)]
     port: u32,
 
-    /// Specify the device where to play through a query (scan devices before playing)
-    #[clap(short = 'q', long = "query-device")]
-    device_query: Option<String>,
+    #[clap(flatten)]
+    render: RenderArgs,
 
-    /// Specify the device where to play through its exact location (no scan, faster)
-    #[clap(short, long = "device")]
-    device_url: Option<String>,
+    /// The volume to set in the device before playing, from 0 to 100
+    #[clap(short, long)]
+    volume: Option<u16>,
 
-    /// The file of the subtitle (if not provided, we derive it from <FILE_VIDEO>)
-    #[clap(short, long, parse(from_os_str), value_name = "FILE_SUBTITLE")]
-    subtitle: Option<std::path::PathBuf>,
+    /// Control the playback through the keyboard, showing a status bar
+    #[clap(short, long)]
+    interactive: bool,
+
+    /// The file of the subtitle, when playing a single file, repeated for each language (if not provided, we derive them from <FILES>)
+    #[clap(
+        short,
+        long,
+        parse(from_os_str),
+        value_name = "FILE_SUBTITLE",
+        multiple_occurrences = true
+    )]
+    subtitle: Vec<std::path::PathBuf>,
 
     /// Disable subtitles
     #[clap(short, long)]
     no_subtitle: bool,
 
-    /// The file of the video to be played
-    #[clap(parse(from_os_str))]
-    file_video: std::path::PathBuf,
+    /// The subtitle track embedded in the videos to be used, by index (from 1) or language, instead of subtitle files (by default, embedded subtitles are used when there is no subtitle file)
+    #[clap(long, value_name = "TRACK", conflicts_with = "subtitle")]
+    subtitle_track: Option<SubtitleTrackSelector>,
+
+    /// The preferred languages of the inferred and embedded subtitles, separated by commas (e.g. pt-BR,en)
+    #[clap(long, value_name = "LANGUAGES", value_delimiter = ',')]
+    subtitle_lang: Vec<String>,
+
+    /// The format the subtitles are converted to and served in (srt, vtt or smi)
+    #[clap(long, value_name = "FORMAT", default_value_t = SubtitleFormat::Srt)]
+    subtitle_format: SubtitleFormat,
+
+    /// The character encoding the subtitles are served in (e.g. utf-8, windows-1252, iso-8859-2)
+    #[clap(long, value_name = "ENCODING", default_value_t = SubtitleEncoding::UTF_8)]
+    subtitle_encoding: SubtitleEncoding,
+
+    /// The delay of the subtitles, in milliseconds (negative to show them earlier)
+    #[clap(
+        long,
+        value_name = "MS",
+        default_value_t = 0,
+        allow_hyphen_values = true
+    )]
+    subtitle_delay: i64,
+
+    /// The frame rate of the video the subtitles were made for, to resync them (e.g. 25)
+    #[clap(long, value_name = "FPS", requires = "subtitle-fps-to")]
+    subtitle_fps_from: Option<f64>,
+
+    /// The frame rate of the video being played, to resync the subtitles (e.g. 23.976)
+    #[clap(long, value_name = "FPS", requires = "subtitle-fps-from")]
+    subtitle_fps_to: Option<f64>,
+
+    /// When to transcode the files with ffmpeg while streaming them: auto (only the ones the device declares it can not play, from their container and codecs, remuxing them when only their container is not supported), never or always
+    #[clap(long, value_name = "MODE", default_value_t = TranscodeMode::Auto)]
+    transcode: TranscodeMode,
+
+    /// The profile the files are transcoded with (h264-aac-ts, h264-aac-mp4, mp3, h264-aac-ts-subs, burning the subtitles in, or copy-ts), instead of the one matching what the device plays
+    #[clap(long, value_name = "PROFILE")]
+    transcode_profile: Option<TranscodeProfile>,
+
+    /// Custom ffmpeg arguments added to the ones of the transcoding profile, separated by spaces (e.g. "-crf 20 -preset fast")
+    #[clap(long, value_name = "ARGS", allow_hyphen_values = true)]
+    transcode_args: Option<String>,
+
+    /// The ffmpeg command used to transcode
+    #[clap(long, parse(from_os_str), value_name = "PATH", default_value = FFMPEG_DEFAULT)]
+    ffmpeg: std::path::PathBuf,
+
+    /// Refuse to play the files the device declares it can not play, instead of only warning
+    #[clap(long)]
+    strict: bool,
+
+    /// Play the files in a random order
+    #[clap(long)]
+    shuffle: bool,
+
+    /// Repeat the current file or all the files (one, all or off)
+    #[clap(long, value_name = "MODE", default_value_t = RepeatMode::Off)]
+    repeat: RepeatMode,
+
+    /// The files to be played: videos, audios, directories or playlists (.m3u, .m3u8, .pls)
+    #[clap(parse(from_os_str), required = true)]
+    files: Vec<std::path::PathBuf>,
 }
 
 impl Play {
     async fn run(&self, cli: &Cli) -> Result<()> {
-        let render = self.select_render(cli).await?;
-        let media_streaming_server = self.build_media_streaming_server().await?;
-        dlna::play(render, media_streaming_server).await
+        let mut render = self.render.select_render(cli).await?;
+        // the formats the render plays decide what is transcoded, and what it is warned about
+        render.fetch_sink_protocol_info().await;
+        let media_streaming_server = self.build_media_streaming_server(&render).await?;
+        if let Some(volume) = self.volume {
+            RenderController::new(render.clone())
+                .set_volume(volume)
+                .await?;
+        }
+        let playback_options = PlaybackOptions {
+            repeat: self.repeat,
+            strict: self.strict,
+        };
+        match self.interactive {
+            true => tokio::select! {
+                result = dlna::play_with_options(render.clone(), media_streaming_server, playback_options) => result,
+                result = interactive::run(RenderController::new(render)) => result,
+            },
+            false => {
+                dlna::play_with_options(render, media_streaming_server, playback_options).await
+            }
+        }
     }
 
-    async fn select_render(&self, cli: &Cli) -> Result<Render> {
-        info!("Selecting render");
-        Render::new(if let Some(device_url) = &self.device_url {
-            RenderSpec::Location(device_url.to_owned())
-        } else if let Some(device_query) = &self.device_query {
-            RenderSpec::Query(cli.timeout, device_query.to_owned())
-        } else {
-            RenderSpec::First(cli.timeout)
-        })
-        .await
+    async fn build_media_streaming_server(&self, render: &Render) -> Result<MediaStreamingServer> {
+        info!("Building media streaming server");
+        let local_host_ip = get_local_ip().await?;
+        let host_ip = self.host.as_ref().unwrap_or(&local_host_ip);
+        let host_port = self.port;
+
+        let mut media_paths = expand_media_paths(&self.files)?;
+        if self.shuffle {
+            media_paths.shuffle(&mut rand::thread_rng());
+        }
+        if !self.subtitle.is_empty() && media_paths.len() > 1 {
+            warn!("Ignoring the subtitle files, as there are multiple files to be played");
+        }
+
+        let mut media_streaming_server: Option<MediaStreamingServer> = None;
+        for media_path in &media_paths {
+            let kind = MediaProfile::detect(media_path).kind;
+            if kind == MediaKind::Unknown {
+                warn!(
+                    "Skipping '{}', as its type is not known",
+                    media_path.display()
+                );
+                continue;
+            }
+            let is_video = kind == MediaKind::Video;
+            let subtitles = match self.no_subtitle || !is_video || self.subtitle_track.is_some() {
+                false if media_paths.len() == 1 && !self.subtitle.is_empty() => self
+                    .subtitle
+                    .iter()
+                    .map(|subtitle_path| SubtitleCandidate::from_file(subtitle_path, media_path))
+                    .collect(),
+                false => infer_subtitles_from_video(media_path, &self.subtitle_lang),
+                true => Vec::new(),
+            };
+            match media_streaming_server.as_mut() {
+                Some(media_streaming_server) => {
+                    media_streaming_server.add_media_with_subtitles(media_path, &subtitles)?
+                }
+                None => {
+                    media_streaming_server = Some(MediaStreamingServer::new_with_options(
+                        media_path,
+                        &subtitles,
+                        host_ip,
+                        &host_port,
+                        SubtitleOptions {
+                            format: self.subtitle_format,
+                            encoding: self.subtitle_encoding,
+                            delay: self.subtitle_delay,
+                            framerate: self.subtitle_fps_from.zip(self.subtitle_fps_to),
+                            embedded: !self.no_subtitle,
+                            track: self.subtitle_track.clone(),
+                            languages: self.subtitle_lang.clone(),
+                        },
+                    )?)
+                }
+            }
+        }
+
+        let mut media_streaming_server = media_streaming_server.ok_or(Error::PlaylistEmpty)?;
+        let args: Vec<String> = self
+            .transcode_args
+            .iter()
+            .flat_map(|args| args.split_whitespace())
+            .map(str::to_string)
+            .collect();
+        let ffmpeg_available = Transcoder::new(TranscodeProfile::H264AacTs)
+            .ffmpeg(&self.ffmpeg)
+            .is_available();
+        for media_item in media_streaming_server.media_items_mut() {
+            let profile = match self
+                .transcode
+                .decide(media_item, render, self.transcode_profile)
+            {
+                Some(profile) => profile,
+                None => continue,
+            };
+            if self.transcode == TranscodeMode::Auto && !ffmpeg_available {
+                warn!(
+                    "The device may not play '{}', but {} was not found to transcode it to {}, streaming it as is",
+                    media_item.video_path().display(),
+                    self.ffmpeg.display(),
+                    profile
+                );
+                continue;
+            }
+            info!(
+                "Transcoding '{}' to {}",
+                media_item.video_path().display(),
+                profile
+            );
+            media_item.transcode(
+                Transcoder::new(profile)
+                    .ffmpeg(&self.ffmpeg)
+                    .args(args.clone()),
+            );
+        }
+        Ok(media_streaming_server)
     }
+}
+
+#[derive(Args)]
+struct Show {
+    /// The hostname or IP to be used to host and serve the files (if not provided we derive it from the local network address)
+    #[clap(short = 'H', long = "host")]
+    host: Option<String>,
+
+    /// The port to be used to host and serve the files
+    #[clap(short = 'P', long = "port", default_value_t=STREAMING_PORT_DEFAULT)]
+    port: u32,
+
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Show each image for this number of seconds, as a slideshow (otherwise only the first image is shown)
+    #[clap(short = 'I', long, value_name = "SECONDS")]
+    interval: Option<u64>,
+
+    /// Show the images in a random order
+    #[clap(long)]
+    shuffle: bool,
+
+    /// Repeat the current image or all the images of the slideshow (one, all or off)
+    #[clap(long, value_name = "MODE", default_value_t = RepeatMode::Off)]
+    repeat: RepeatMode,
+
+    /// Rotate the images according to their EXIF orientation
+    #[clap(short, long)]
+    rotate: bool,
+
+    /// Downscale the images to JPEG, to fit a DLNA profile (medium: 1024x768, large: 4096x4096)
+    #[clap(long, value_name = "SIZE")]
+    downscale: Option<ImageSize>,
+
+    /// The images to be shown (JPEG, PNG or GIF), directories or playlists (.m3u, .m3u8, .pls)
+    #[clap(parse(from_os_str), required = true)]
+    files: Vec<std::path::PathBuf>,
+}
+
+impl Show {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let render = self.render.select_render(cli).await?;
+
+        let mut image_paths = expand_image_paths(&self.files)?;
+        if self.shuffle {
+            image_paths.shuffle(&mut rand::thread_rng());
+        }
+        if self.interval.is_none() && image_paths.len() > 1 {
+            warn!("Showing only the first image, use --interval for a slideshow");
+            image_paths.truncate(1);
+        }
+        let image_options = ImageOptions {
+            rotate: self.rotate,
+            downscale: self.downscale,
+        };
+        let prepared_images = prepare_images(&image_paths, &image_options)?;
 
-    async fn build_media_streaming_server(&self) -> Result<MediaStreamingServer> {
         info!("Building media streaming server");
         let local_host_ip = get_local_ip().await?;
         let host_ip = self.host.as_ref().unwrap_or(&local_host_ip);
-        let host_port = self.port;
+        let (first_image, other_images) = prepared_images
+            .paths()
+            .split_first()
+            .ok_or(Error::PlaylistEmpty)?;
+        let mut media_streaming_server =
+            MediaStreamingServer::new(first_image, &None, host_ip, &self.port)?;
+        for image_path in other_images {
+            media_streaming_server.add_media(image_path, &None)?;
+        }
 
-        let subtitle = match &self.no_subtitle {
-            false => self
-                .subtitle
-                .clone()
-                .or_else(|| infer_subtitle_from_video(&self.file_video)),
-            true => None,
+        let slideshow_options = SlideshowOptions {
+            interval: self.interval.map(Duration::from_secs),
+            repeat: self.repeat,
         };
+        dlna::show(render, media_streaming_server, slideshow_options).await
+    }
+}
+
+#[derive(Args)]
+struct Pause {
+    #[clap(flatten)]
+    render: RenderArgs,
+}
 
-        MediaStreamingServer::new(&self.file_video, &subtitle, host_ip, &host_port)
+impl Pause {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        self.render.select_controller(cli).await?.pause().await
+    }
+}
+
+#[derive(Args)]
+struct Resume {
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+impl Resume {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        self.render.select_controller(cli).await?.resume().await
+    }
+}
+
+#[derive(Args)]
+struct Stop {
+    #[clap(flatten)]
+    render: RenderArgs,
+}
+
+impl Stop {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        self.render.select_controller(cli).await?.stop().await
+    }
+}
+
+#[derive(Args)]
+struct Seek {
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Interpret <TARGET> as a track number instead of a time position
+    #[clap(long)]
+    track: bool,
+
+    /// The position to seek to, as [H:]MM:SS (or a track number, with --track)
+    target: String,
+}
+
+impl Seek {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let controller = self.render.select_controller(cli).await?;
+        match self.track {
+            true => {
+                let track = self
+                    .target
+                    .parse()
+                    .map_err(|_| Error::DLNATrackNumberParseError(self.target.to_owned()))?;
+                controller.seek_track(track).await
+            }
+            false => controller.seek(self.target.parse::<RelTime>()?).await,
+        }
+    }
+}
+
+#[derive(Args)]
+struct Status {
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Print the status as JSON
+    #[clap(long)]
+    json: bool,
+}
+
+impl Status {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let status = self.render.select_render(cli).await?.status().await?;
+        match self.json {
+            true => println!("{}", Self::to_json(&status)),
+            false => Self::print_human(&status),
+        }
+        Ok(())
+    }
+
+    fn print_human(status: &RenderStatus) {
+        let format_time = |time: &Option<RelTime>| match time {
+            Some(time) => time.to_string(),
+            None => "-".to_string(),
+        };
+        println!(
+            "State:    {} ({})",
+            status.transport.state, status.transport.status
+        );
+        println!(
+            "Position: {} / {} (track {} of {})",
+            format_time(&status.position.rel_time),
+            format_time(&status.position.track_duration),
+            status.position.track,
+            status.media.tracks
+        );
+        println!("URI:      {}", status.media.current_uri);
+        if !status.media.next_uri.is_empty() {
+            println!("Next URI: {}", status.media.next_uri);
+        }
+    }
+
+    fn to_json(status: &RenderStatus) -> serde_json::Value {
+        let format_time = |time: &Option<RelTime>| time.map(|time| time.to_string());
+        serde_json::json!({
+            "transport": {
+                "state": status.transport.state.to_string(),
+                "status": status.transport.status,
+                "speed": status.transport.speed,
+            },
+            "position": {
+                "track": status.position.track,
+                "track_duration": format_time(&status.position.track_duration),
+                "track_uri": status.position.track_uri,
+                "rel_time": format_time(&status.position.rel_time),
+            },
+            "media": {
+                "tracks": status.media.tracks,
+                "media_duration": format_time(&status.media.media_duration),
+                "current_uri": status.media.current_uri,
+                "next_uri": status.media.next_uri,
+            },
+        })
+    }
+}
+
+#[derive(Args)]
+#[clap(allow_negative_numbers = true)]
+struct Volume {
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// The volume to set, from 0 to 100, or a relative step as +N/-N (if not provided, show the current volume)
+    level: Option<String>,
+}
+
+impl Volume {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let controller = self.render.select_controller(cli).await?;
+        let volume = match &self.level {
+            None => controller.volume().await?,
+            Some(level) if level.starts_with('+') || level.starts_with('-') => {
+                let step = level
+                    .parse()
+                    .map_err(|_| Error::DLNAVolumeParseError(level.to_owned()))?;
+                controller.change_volume(step).await?
+            }
+            Some(level) => {
+                let volume = level
+                    .parse()
+                    .map_err(|_| Error::DLNAVolumeParseError(level.to_owned()))?;
+                controller.set_volume(volume).await?;
+                controller.volume().await?
+            }
+        };
+        println!("{}", volume);
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+struct Mute {
+    #[clap(flatten)]
+    render: RenderArgs,
+
+    /// Mute the device (instead of toggling it)
+    #[clap(long, conflicts_with = "off")]
+    on: bool,
+
+    /// Unmute the device (instead of toggling it)
+    #[clap(long)]
+    off: bool,
+}
+
+impl Mute {
+    async fn run(&self, cli: &Cli) -> Result<()> {
+        let controller = self.render.select_controller(cli).await?;
+        let mute = match (self.on, self.off) {
+            (true, _) => true,
+            (_, true) => false,
+            _ => !controller.is_muted().await?,
+        };
+        controller.set_mute(mute).await?;
+        println!("{}", if mute { "muted" } else { "unmuted" });
+        Ok(())
     }
 }
 
@@ -0,0 +1,401 @@
+use crate::{
+    devices::Render,
+    error::{Error, Result},
+};
+use log::{debug, info};
+use serde::Deserialize;
+use std::{collections::HashMap, fmt, str::FromStr, time::Duration};
+
+const PAYLOAD_PLAY: &str = r#"
+    <InstanceID>0</InstanceID>
+    <Speed>1</Speed>
+"#;
+
+const PAYLOAD_PAUSE: &str = r#"
+    <InstanceID>0</InstanceID>
+"#;
+
+const PAYLOAD_STOP: &str = r#"
+    <InstanceID>0</InstanceID>
+"#;
+
+const PAYLOAD_GET_VOLUME: &str = r#"
+    <InstanceID>0</InstanceID>
+    <Channel>Master</Channel>
+"#;
+
+const PAYLOAD_GET_MUTE: &str = r#"
+    <InstanceID>0</InstanceID>
+    <Channel>Master</Channel>
+"#;
+
+const VOLUME_MAX: u16 = 100;
+
+/// A time position relative to the beginning of a track, as used by AVTransport (`H+:MM:SS`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RelTime(Duration);
+
+impl RelTime {
+    /// Create a time position from a number of seconds.
+    pub fn from_secs(secs: u64) -> Self {
+        Self(Duration::from_secs(secs))
+    }
+
+    /// Returns the time position as a duration.
+    pub fn as_duration(&self) -> Duration {
+        self.0
+    }
+
+    /// Returns the time position in whole seconds.
+    pub fn as_secs(&self) -> u64 {
+        self.0.as_secs()
+    }
+}
+
+impl From<Duration> for RelTime {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl FromStr for RelTime {
+    type Err = Error;
+
+    /// Parses `H+:MM:SS[.F+]`, `MM:SS` or plain seconds.
+    fn from_str(s: &str) -> Result<Self> {
+        let parse_error = || Error::DLNARelTimeParseError(s.to_owned());
+
+        let (whole, fraction) = match s.trim().split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (s.trim(), None),
+        };
+
+        let mut secs: u64 = 0;
+        for part in whole.split(':') {
+            let value: u64 = part.parse().map_err(|_| parse_error())?;
+            secs = secs
+                .checked_mul(60)
+                .and_then(|secs| secs.checked_add(value))
+                .ok_or_else(parse_error)?;
+        }
+
+        let nanos = match fraction {
+            // fractions may also be expressed as F0/F1, which we simply ignore
+            Some(fraction) if fraction.contains('/') => 0,
+            Some(fraction) => {
+                let digits: String = fraction.chars().take(9).collect();
+                let value: u32 = digits.parse().map_err(|_| parse_error())?;
+                value * 10u32.pow(9 - digits.len() as u32)
+            }
+            None => 0,
+        };
+
+        Ok(Self(Duration::new(secs, nanos)))
+    }
+}
+
+impl fmt::Display for RelTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        write!(
+            f,
+            "{}:{:02}:{:02}",
+            secs / 3600,
+            (secs / 60) % 60,
+            secs % 60
+        )
+    }
+}
+
+/// The format of the positions sent to a render to seek
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeekFormat {
+    /// A `REL_TIME` target as `H:MM:SS`
+    #[default]
+    RelTime,
+    /// A `REL_TIME` target with two digit hours, as `HH:MM:SS`
+    RelTimePadded,
+    /// A `REL_TIME` target with milliseconds, as `H:MM:SS.mmm`
+    RelTimeMillis,
+    /// An `ABS_TIME` target as `H:MM:SS`
+    AbsTime,
+}
+
+impl SeekFormat {
+    /// Returns the unit and the target of a seek to a time position
+    fn target(&self, position: RelTime) -> (&'static str, String) {
+        match self {
+            Self::RelTime => ("REL_TIME", position.to_string()),
+            Self::RelTimePadded => ("REL_TIME", format!("{:0>8}", position.to_string())),
+            Self::RelTimeMillis => (
+                "REL_TIME",
+                format!("{}.{:03}", position, position.as_duration().subsec_millis()),
+            ),
+            Self::AbsTime => ("ABS_TIME", position.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SeekFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            Self::RelTime => "rel-time",
+            Self::RelTimePadded => "rel-time-padded",
+            Self::RelTimeMillis => "rel-time-millis",
+            Self::AbsTime => "abs-time",
+        };
+        write!(f, "{}", format)
+    }
+}
+
+/// A controller of the media playback in a render, through AVTransport and RenderingControl actions.
+#[derive(Debug, Clone)]
+pub struct RenderController {
+    render: Render,
+}
+
+impl RenderController {
+    /// Create a new controller for the given render.
+    pub fn new(render: Render) -> Self {
+        Self { render }
+    }
+
+    /// Returns the render being controlled.
+    pub fn render(&self) -> &Render {
+        &self.render
+    }
+
+    /// Starts playing the media currently set in the render.
+    pub async fn play(&self) -> Result<()> {
+        info!("Playing media");
+        self.action("Play", PAYLOAD_PLAY)
+            .await
+            .map_err(Error::DLNAPlayError)
+    }
+
+    /// Pauses the media being played.
+    pub async fn pause(&self) -> Result<()> {
+        info!("Pausing media");
+        self.action("Pause", PAYLOAD_PAUSE)
+            .await
+            .map_err(Error::DLNAPauseError)
+    }
+
+    /// Resumes a paused media.
+    pub async fn resume(&self) -> Result<()> {
+        info!("Resuming media");
+        self.action("Play", PAYLOAD_PLAY)
+            .await
+            .map_err(Error::DLNAPlayError)
+    }
+
+    /// Stops the media being played.
+    pub async fn stop(&self) -> Result<()> {
+        info!("Stopping media");
+        self.action("Stop", PAYLOAD_STOP)
+            .await
+            .map_err(Error::DLNAStopError)
+    }
+
+    /// Seeks to a time position in the current track.
+    pub async fn seek(&self, target: RelTime) -> Result<()> {
+        info!("Seeking to {}", target);
+        let (unit, target) = self.render.quirks.seek_format.target(target);
+        self.action("Seek", &payload_seek(unit, &target))
+            .await
+            .map_err(Error::DLNASeekError)
+    }
+
+    /// Seeks to a track number, starting from 1.
+    pub async fn seek_track(&self, track: u32) -> Result<()> {
+        info!("Seeking to track {}", track);
+        self.action("Seek", &payload_seek("TRACK_NR", &track.to_string()))
+            .await
+            .map_err(Error::DLNASeekError)
+    }
+
+    /// Queries the volume of the render, from 0 to 100.
+    pub async fn volume(&self) -> Result<u16> {
+        let response = self
+            .rendering_control_action("GetVolume", PAYLOAD_GET_VOLUME, Error::DLNAGetVolumeError)
+            .await?;
+        let volume = response.get("CurrentVolume").cloned().unwrap_or_default();
+        volume
+            .parse()
+            .map_err(|_| Error::DLNAVolumeParseError(volume))
+    }
+
+    /// Sets the volume of the render, from 0 to 100.
+    pub async fn set_volume(&self, volume: u16) -> Result<()> {
+        let volume = volume.min(VOLUME_MAX);
+        info!("Setting volume to {}", volume);
+        self.rendering_control_action(
+            "SetVolume",
+            &payload_set_volume(volume),
+            Error::DLNASetVolumeError,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Raises (or lowers, if `step` is negative) the volume of the render, returning the new volume.
+    pub async fn change_volume(&self, step: i32) -> Result<u16> {
+        let current = self.volume().await?;
+        let volume = (current as i32 + step).clamp(0, VOLUME_MAX as i32) as u16;
+        self.set_volume(volume).await?;
+        Ok(volume)
+    }
+
+    /// Queries whether the render is muted.
+    pub async fn is_muted(&self) -> Result<bool> {
+        let response = self
+            .rendering_control_action("GetMute", PAYLOAD_GET_MUTE, Error::DLNAGetMuteError)
+            .await?;
+        Ok(matches!(
+            response.get("CurrentMute").map(String::as_str),
+            Some("1") | Some("true")
+        ))
+    }
+
+    /// Mutes or unmutes the render.
+    pub async fn set_mute(&self, mute: bool) -> Result<()> {
+        info!("{} render", if mute { "Muting" } else { "Unmuting" });
+        self.rendering_control_action("SetMute", &payload_set_mute(mute), Error::DLNASetMuteError)
+            .await
+            .map(|_| ())
+    }
+
+    async fn action(&self, action: &str, payload: &str) -> Result<(), rupnp::Error> {
+        debug!("{} payload: '{}'", action, payload);
+        self.render
+            .service
+            .action(self.render.device.url(), action, payload)
+            .await
+            .map(|_| ())
+    }
+
+    async fn rendering_control_action(
+        &self,
+        action: &str,
+        payload: &str,
+        map_err: fn(rupnp::Error) -> Error,
+    ) -> Result<HashMap<String, String>> {
+        let service = self
+            .render
+            .rendering_control
+            .as_ref()
+            .ok_or_else(|| Error::DLNARenderingControlNotSupported(self.render.to_string()))?;
+        debug!("{} payload: '{}'", action, payload);
+        service
+            .action(self.render.device.url(), action, payload)
+            .await
+            .map_err(map_err)
+    }
+}
+
+fn payload_seek(unit: &str, target: &str) -> String {
+    format!(
+        r#"
+        <InstanceID>0</InstanceID>
+        <Unit>{}</Unit>
+        <Target>{}</Target>
+        "#,
+        unit, target
+    )
+}
+
+fn payload_set_volume(volume: u16) -> String {
+    format!(
+        r#"
+        <InstanceID>0</InstanceID>
+        <Channel>Master</Channel>
+        <DesiredVolume>{}</DesiredVolume>
+        "#,
+        volume
+    )
+}
+
+fn payload_set_mute(mute: bool) -> String {
+    format!(
+        r#"
+        <InstanceID>0</InstanceID>
+        <Channel>Master</Channel>
+        <DesiredMute>{}</DesiredMute>
+        "#,
+        mute as u8
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Option<Duration> {
+        s.parse::<RelTime>()
+            .ok()
+            .map(|position| position.as_duration())
+    }
+
+    #[test]
+    fn parse_rel_time() {
+        assert_eq!(parse("1:02:03"), Some(Duration::from_secs(3723)));
+        assert_eq!(parse(" 02:03 "), Some(Duration::from_secs(123)));
+        assert_eq!(parse("90"), Some(Duration::from_secs(90)));
+        assert_eq!(parse("0:00:01.5"), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            parse("0:00:01.123456789123"),
+            Some(Duration::new(1, 123_456_789))
+        );
+        // fractions as F0/F1 are ignored
+        assert_eq!(parse("0:00:02.1/3"), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn parse_invalid_rel_time() {
+        for invalid in ["", "1:", ":30", "1:xx", "-1:00", "1.", "1.x", "1:00:00.-5"] {
+            assert_eq!(parse(invalid), None, "{}", invalid);
+        }
+        assert!(matches!(
+            "99999999999999999999:00".parse::<RelTime>(),
+            Err(Error::DLNARelTimeParseError(_))
+        ));
+        assert_eq!(parse("307445734561825861:00"), None);
+    }
+
+    #[test]
+    fn format_rel_time() {
+        let position = RelTime::from(Duration::from_millis(3_723_045));
+        assert_eq!(position.to_string(), "1:02:03");
+        assert_eq!(RelTime::from_secs(100 * 3600).to_string(), "100:00:00");
+    }
+
+    #[test]
+    fn seek_targets() {
+        let position = RelTime::from(Duration::from_millis(3_723_045));
+        let target = |seek_format: SeekFormat| seek_format.target(position);
+        assert_eq!(
+            target(SeekFormat::RelTime),
+            ("REL_TIME", "1:02:03".to_owned())
+        );
+        assert_eq!(
+            target(SeekFormat::RelTimePadded),
+            ("REL_TIME", "01:02:03".to_owned())
+        );
+        assert_eq!(
+            target(SeekFormat::RelTimeMillis),
+            ("REL_TIME", "1:02:03.045".to_owned())
+        );
+        assert_eq!(
+            target(SeekFormat::AbsTime),
+            ("ABS_TIME", "1:02:03".to_owned())
+        );
+        // positions of 10 hours or more are not truncated
+        assert_eq!(
+            SeekFormat::RelTimePadded
+                .target(RelTime::from_secs(100 * 3600))
+                .1,
+            "100:00:00"
+        );
+    }
+}
@@ -1,11 +1,21 @@
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    media::MediaProfile,
+    protocol::ProtocolInfo,
+    quirks::{Quirks, QuirksDatabase},
+};
 use futures_util::stream::{Stream, StreamExt, TryStreamExt};
 use http::Uri;
 use log::{debug, info, warn};
 use rupnp::ssdp::{SearchTarget, URN};
+use serde::Serialize;
 use std::time::Duration;
 
 const AV_TRANSPORT: URN = URN::service("schemas-upnp-org", "AVTransport", 1);
+const RENDERING_CONTROL: URN = URN::service("schemas-upnp-org", "RenderingControl", 1);
+const CONNECTION_MANAGER: URN = URN::service("schemas-upnp-org", "ConnectionManager", 1);
+/// Time to wait for a render to answer GetProtocolInfo
+const PROTOCOL_INFO_TIMEOUT: Duration = Duration::from_secs(5);
 
 macro_rules! format_device {
     ($device:expr) => {{
@@ -25,6 +35,57 @@ pub struct Render {
     pub device: rupnp::Device,
     /// The AVTransport service
     pub service: rupnp::Service,
+    /// The RenderingControl service, if the device provides it
+    pub rendering_control: Option<rupnp::Service>,
+    /// The ConnectionManager service, if the device provides it
+    pub connection_manager: Option<rupnp::Service>,
+    /// The protocol info of the media the render declares it can play (empty when unknown or not fetched
+    /// yet, see [`Render::fetch_sink_protocol_info`])
+    pub sink_protocol_info: Vec<ProtocolInfo>,
+    /// The manufacturer of the device, from its description
+    pub manufacturer: String,
+    /// The model name of the device, from its description
+    pub model_name: String,
+    /// The model number of the device, from its description (empty when unknown)
+    pub model_number: String,
+    /// The unique device name (UDN) of the device, from its description
+    pub udn: String,
+    /// The vendor-specific behaviour of the render, from the built-in quirks database by default
+    pub quirks: Quirks,
+}
+
+/// A description of a render, with the services of its device and the actions they support.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderDescription {
+    /// The unique device name (UDN)
+    pub udn: String,
+    /// The friendly name
+    pub friendly_name: String,
+    /// The manufacturer
+    pub manufacturer: String,
+    /// The model name
+    pub model_name: String,
+    /// The model number
+    pub model_number: String,
+    /// The device type (URN)
+    pub device_type: String,
+    /// The location URL of the device description
+    pub location: String,
+    /// The IP address (or host) of the device
+    pub ip: String,
+    /// The services of the device and of its embedded devices
+    pub services: Vec<ServiceDescription>,
+}
+
+/// A description of a service of a render
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceDescription {
+    /// The service type (URN)
+    pub service_type: String,
+    /// The service ID
+    pub service_id: String,
+    /// The names of the actions the service supports (empty when its description can not be read)
+    pub actions: Vec<String>,
 }
 
 /// An specification of a DLNA render device.
@@ -83,7 +144,7 @@ impl Render {
             match result {
                 Ok(device) => {
                     debug!("Found device: {}", format_device!(device));
-                    if let Some(render) = Self::from_device(device).await {
+                    if let Some(render) = Self::from_device(device) {
                         renders.push(render);
                     };
                 }
@@ -101,6 +162,97 @@ impl Render {
         self.device.url().authority().unwrap().host().to_string()
     }
 
+    /// Describes the render, reading the description of each of its services to list their actions.
+    pub async fn describe(&self) -> RenderDescription {
+        let mut services = Vec::new();
+        for service in self.device.services_iter() {
+            let actions = match service.scpd(self.device.url()).await {
+                Ok(scpd) => scpd
+                    .actions()
+                    .iter()
+                    .map(|action| action.name().to_string())
+                    .collect(),
+                Err(err) => {
+                    warn!(
+                        "Failed to get the description of the service {} of {}: {}",
+                        service.service_id(),
+                        self.device.friendly_name(),
+                        err
+                    );
+                    Vec::new()
+                }
+            };
+            services.push(ServiceDescription {
+                service_type: service.service_type().to_string(),
+                service_id: service.service_id().to_string(),
+                actions,
+            });
+        }
+        RenderDescription {
+            udn: self.udn.clone(),
+            friendly_name: self.device.friendly_name().to_string(),
+            manufacturer: self.manufacturer.clone(),
+            model_name: self.model_name.clone(),
+            model_number: self.model_number.clone(),
+            device_type: self.device.device_type().to_string(),
+            location: self.device.url().to_string(),
+            ip: self.host(),
+            services,
+        }
+    }
+
+    /// Sets the quirks of the render from a quirks database, such as one with the rules of a quirks file.
+    pub fn apply_quirks(&mut self, database: &QuirksDatabase) {
+        self.quirks = database.quirks(
+            &self.manufacturer,
+            &self.model_name,
+            self.device.friendly_name(),
+        );
+        debug!(
+            "Quirks of {}: {:?}",
+            self.device.friendly_name(),
+            self.quirks
+        );
+    }
+
+    /// Fetches the protocol info of the media the render can play from its ConnectionManager, leaving it
+    /// empty when the render does not answer in time.
+    pub async fn fetch_sink_protocol_info(&mut self) {
+        let connection_manager = match &self.connection_manager {
+            Some(connection_manager) => connection_manager,
+            None => {
+                debug!(
+                    "No ConnectionManager service found on {}",
+                    self.device.friendly_name()
+                );
+                return;
+            }
+        };
+        self.sink_protocol_info = match tokio::time::timeout(
+            PROTOCOL_INFO_TIMEOUT,
+            sink_protocol_info(&self.device, connection_manager),
+        )
+        .await
+        {
+            Ok(sink_protocol_info) => sink_protocol_info,
+            Err(_) => {
+                warn!(
+                    "{} did not answer its protocol info in time",
+                    self.device.friendly_name()
+                );
+                Vec::new()
+            }
+        };
+    }
+
+    /// Whether the render declares it can play a type of media, in the sink protocol info of its
+    /// ConnectionManager (once fetched).
+    ///
+    /// Renders which do not declare what they can play are assumed to play anything.
+    pub fn supports(&self, profile: &MediaProfile) -> bool {
+        ProtocolInfo::list_matches(&self.sink_protocol_info, profile)
+    }
+
     async fn select_by_url(url: &String) -> Result<Option<Self>> {
         debug!("Selecting device by url: {}", url);
         let uri: Uri = url
@@ -111,7 +263,7 @@ impl Render {
             .await
             .map_err(|err| Error::DevicesCreateError(url.to_owned(), err))?;
 
-        Ok(Self::from_device(device).await)
+        Ok(Self::from_device(device))
     }
 
     async fn select_by_query(duration_secs: u64, query: &String) -> Result<Option<Self>> {
@@ -125,16 +277,35 @@ impl Render {
         Ok(None)
     }
 
-    async fn from_device(device: rupnp::Device) -> Option<Self> {
+    fn from_device(device: rupnp::Device) -> Option<Self> {
         debug!(
             "Retrieving AVTransport service from device '{}'",
             format_device!(device)
         );
         match device.find_service(&AV_TRANSPORT) {
-            Some(service) => Some(Self {
-                device: device.clone(),
-                service: service.clone(),
-            }),
+            Some(service) => {
+                let rendering_control = device.find_service(&RENDERING_CONTROL).cloned();
+                if rendering_control.is_none() {
+                    debug!(
+                        "No RenderingControl service found on {}",
+                        device.friendly_name()
+                    );
+                }
+                let mut render = Self {
+                    device: device.clone(),
+                    service: service.clone(),
+                    rendering_control,
+                    connection_manager: device.find_service(&CONNECTION_MANAGER).cloned(),
+                    sink_protocol_info: Vec::new(),
+                    manufacturer: device.manufacturer().to_string(),
+                    model_name: device.model_name().to_string(),
+                    model_number: device.model_number().unwrap_or_default().to_string(),
+                    udn: device.udn().to_string(),
+                    quirks: Quirks::default(),
+                };
+                render.apply_quirks(&QuirksDatabase::builtin());
+                Some(render)
+            }
             None => {
                 warn!("No AVTransport service found on {}", device.friendly_name());
                 None
@@ -156,6 +327,36 @@ impl std::fmt::Display for Render {
     }
 }
 
+/// Retrieves the protocol info of the media a device can play, through the GetProtocolInfo action
+async fn sink_protocol_info(
+    device: &rupnp::Device,
+    connection_manager: &rupnp::Service,
+) -> Vec<ProtocolInfo> {
+    match connection_manager
+        .action(device.url(), "GetProtocolInfo", "")
+        .await
+    {
+        Ok(response) => {
+            let sink = response.get("Sink").map(String::as_str).unwrap_or_default();
+            let sink_protocol_info = ProtocolInfo::parse_list(sink);
+            debug!(
+                "{} formats supported by {}",
+                sink_protocol_info.len(),
+                device.friendly_name()
+            );
+            sink_protocol_info
+        }
+        Err(err) => {
+            warn!(
+                "Failed to get the protocol info of {}: {}",
+                device.friendly_name(),
+                err
+            );
+            Vec::new()
+        }
+    }
+}
+
 async fn upnp_discover(
     search_target: &SearchTarget,
     timeout: Duration,
@@ -0,0 +1,588 @@
+use crate::{
+    control::RelTime,
+    error::{Error, Result},
+    media::{MediaKind, MediaProfile},
+};
+use serde::Deserialize;
+use std::fmt;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+const NS_DIDL_LITE: &str = "urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/";
+const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+const NS_UPNP: &str = "urn:schemas-upnp-org:metadata-1-0/upnp/";
+const NS_DLNA: &str = "urn:schemas-dlna-org:metadata-1-0/";
+const NS_SEC: &str = "http://www.sec.co.kr/";
+const NS_PV: &str = "http://www.pv.com/pvns/";
+
+/// The UPnP class of a DIDL-Lite item (`upnp:class`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpnpClass {
+    /// `object.item.videoItem`
+    VideoItem,
+    /// `object.item.videoItem.movie`
+    Movie,
+    /// `object.item.audioItem`
+    AudioItem,
+    /// `object.item.audioItem.musicTrack`
+    MusicTrack,
+    /// `object.item.imageItem`
+    ImageItem,
+    /// `object.item.imageItem.photo`
+    Photo,
+    /// Any other class
+    Other(String),
+}
+
+impl UpnpClass {
+    /// Returns the most usual class for a kind of media
+    pub fn for_kind(kind: MediaKind) -> Self {
+        match kind {
+            MediaKind::Audio => Self::MusicTrack,
+            MediaKind::Image => Self::Photo,
+            MediaKind::Video | MediaKind::Subtitle => Self::Movie,
+            MediaKind::Unknown => Self::Other("object.item".to_string()),
+        }
+    }
+
+    /// Returns the class as written in DIDL-Lite
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::VideoItem => "object.item.videoItem",
+            Self::Movie => "object.item.videoItem.movie",
+            Self::AudioItem => "object.item.audioItem",
+            Self::MusicTrack => "object.item.audioItem.musicTrack",
+            Self::ImageItem => "object.item.imageItem",
+            Self::Photo => "object.item.imageItem.photo",
+            Self::Other(class) => class,
+        }
+    }
+}
+
+impl fmt::Display for UpnpClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A resource of a DIDL-Lite item (`res`), i.e. an URI where the item can be fetched
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidlResource {
+    uri: String,
+    protocol_info: String,
+    size: Option<u64>,
+    duration: Option<RelTime>,
+    resolution: Option<(u32, u32)>,
+    bitrate: Option<u32>,
+    audio_channels: Option<u32>,
+    sample_frequency: Option<u32>,
+}
+
+impl DidlResource {
+    /// Create a new resource, with its URI and protocol info (`http-get:*:<mime>:<features>`).
+    pub fn new(uri: impl Into<String>, protocol_info: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            protocol_info: protocol_info.into(),
+            size: None,
+            duration: None,
+            resolution: None,
+            bitrate: None,
+            audio_channels: None,
+            sample_frequency: None,
+        }
+    }
+
+    /// Sets the size of the resource, in bytes.
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Sets the playback duration of the resource.
+    pub fn duration(mut self, duration: RelTime) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets the resolution of the resource, in pixels.
+    pub fn resolution(mut self, width: u32, height: u32) -> Self {
+        self.resolution = Some((width, height));
+        self
+    }
+
+    /// Sets the bitrate of the resource, in bytes per second (as defined by UPnP).
+    pub fn bitrate(mut self, bitrate: u32) -> Self {
+        self.bitrate = Some(bitrate);
+        self
+    }
+
+    /// Sets the number of audio channels of the resource.
+    pub fn audio_channels(mut self, audio_channels: u32) -> Self {
+        self.audio_channels = Some(audio_channels);
+        self
+    }
+
+    /// Sets the sample frequency of the audio of the resource, in Hz.
+    pub fn sample_frequency(mut self, sample_frequency: u32) -> Self {
+        self.sample_frequency = Some(sample_frequency);
+        self
+    }
+}
+
+/// A vendor extension of DIDL-Lite through which renders find the subtitles of an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleExtension {
+    /// `pv:subtitleFileUri` and `pv:subtitleFileType` attributes of the main resource (first subtitle only)
+    Pv,
+    /// `sec:CaptionInfoEx` and `sec:CaptionInfo` elements (Samsung)
+    Sec,
+    /// Resources of their own, with the protocol info of the format they are served in
+    Res,
+}
+
+impl SubtitleExtension {
+    /// All the extensions, for renders whose one is not known
+    pub const ALL: [Self; 3] = [Self::Pv, Self::Sec, Self::Res];
+}
+
+impl fmt::Display for SubtitleExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let extension = match self {
+            Self::Pv => "pv",
+            Self::Sec => "sec",
+            Self::Res => "res",
+        };
+        write!(f, "{}", extension)
+    }
+}
+
+/// A subtitle of a DIDL-Lite item, announced through the vendor extensions understood by renders
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidlSubtitle {
+    uri: String,
+    subtitle_type: String,
+    language: Option<String>,
+    mime: Option<String>,
+}
+
+impl DidlSubtitle {
+    /// Create a new subtitle, with its URI and type (its format, e.g. `srt`).
+    pub fn new(uri: impl Into<String>, subtitle_type: impl Into<String>) -> Self {
+        Self {
+            uri: uri.into(),
+            subtitle_type: subtitle_type.into(),
+            language: None,
+            mime: None,
+        }
+    }
+
+    /// Sets the language of the subtitle, as a tag like `en` or `pt-BR`, so that renders can offer a choice.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Sets the MIME type of the resource of the subtitle (the one of its type by default).
+    pub fn mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    /// Returns the MIME type the subtitle is served with
+    fn served_mime(&self) -> String {
+        let profile = MediaProfile::from_extension(&self.subtitle_type);
+        match &self.mime {
+            Some(mime) => mime.clone(),
+            None if profile.kind == MediaKind::Subtitle => profile.mime.to_string(),
+            None => format!("text/{}", self.subtitle_type),
+        }
+    }
+}
+
+/// An item of a DIDL-Lite document, describing a media to be played
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DidlItem {
+    id: String,
+    parent_id: String,
+    title: String,
+    class: UpnpClass,
+    creator: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    track_number: Option<u32>,
+    album_art_uri: Option<String>,
+    resources: Vec<DidlResource>,
+    subtitles: Vec<DidlSubtitle>,
+    subtitle_extensions: Vec<SubtitleExtension>,
+}
+
+impl DidlItem {
+    /// Create a new item, with its title and class.
+    pub fn new(title: impl Into<String>, class: UpnpClass) -> Self {
+        Self {
+            id: "0".to_string(),
+            parent_id: "-1".to_string(),
+            title: title.into(),
+            class,
+            creator: None,
+            artist: None,
+            album: None,
+            track_number: None,
+            album_art_uri: None,
+            resources: Vec::new(),
+            subtitles: Vec::new(),
+            subtitle_extensions: SubtitleExtension::ALL.to_vec(),
+        }
+    }
+
+    /// Sets the ID of the item and of its parent container (`0` and `-1` by default).
+    pub fn id(mut self, id: impl Into<String>, parent_id: impl Into<String>) -> Self {
+        self.id = id.into();
+        self.parent_id = parent_id.into();
+        self
+    }
+
+    /// Sets the creator of the item (`dc:creator`).
+    pub fn creator(mut self, creator: impl Into<String>) -> Self {
+        self.creator = Some(creator.into());
+        self
+    }
+
+    /// Sets the artist of the item (`upnp:artist`).
+    pub fn artist(mut self, artist: impl Into<String>) -> Self {
+        self.artist = Some(artist.into());
+        self
+    }
+
+    /// Sets the album of the item (`upnp:album`).
+    pub fn album(mut self, album: impl Into<String>) -> Self {
+        self.album = Some(album.into());
+        self
+    }
+
+    /// Sets the number of the track in its album (`upnp:originalTrackNumber`).
+    pub fn track_number(mut self, track_number: u32) -> Self {
+        self.track_number = Some(track_number);
+        self
+    }
+
+    /// Sets the URI of the album art of the item (`upnp:albumArtURI`).
+    pub fn album_art_uri(mut self, album_art_uri: impl Into<String>) -> Self {
+        self.album_art_uri = Some(album_art_uri.into());
+        self
+    }
+
+    /// Adds a resource to the item; the first one is the main resource.
+    pub fn resource(mut self, resource: DidlResource) -> Self {
+        self.resources.push(resource);
+        self
+    }
+
+    /// Adds a subtitle to the item.
+    pub fn subtitle(mut self, subtitle: DidlSubtitle) -> Self {
+        self.subtitles.push(subtitle);
+        self
+    }
+
+    /// Sets the vendor extensions the subtitles are announced with (all of them by default).
+    pub fn subtitle_extensions(mut self, subtitle_extensions: &[SubtitleExtension]) -> Self {
+        self.subtitle_extensions = subtitle_extensions.to_vec();
+        self
+    }
+
+    fn write<W: std::io::Write>(&self, writer: &mut EventWriter<W>) -> xml::writer::Result<()> {
+        writer.write(
+            XmlEvent::start_element("item")
+                .attr("id", &self.id)
+                .attr("parentID", &self.parent_id)
+                .attr("restricted", "1"),
+        )?;
+        write_text_element(writer, "dc:title", &self.title)?;
+        if let Some(creator) = &self.creator {
+            write_text_element(writer, "dc:creator", creator)?;
+        }
+        if let Some(artist) = &self.artist {
+            write_text_element(writer, "upnp:artist", artist)?;
+        }
+        if let Some(album) = &self.album {
+            write_text_element(writer, "upnp:album", album)?;
+        }
+        if let Some(track_number) = self.track_number {
+            write_text_element(
+                writer,
+                "upnp:originalTrackNumber",
+                &track_number.to_string(),
+            )?;
+        }
+        if let Some(album_art_uri) = &self.album_art_uri {
+            write_text_element(writer, "upnp:albumArtURI", album_art_uri)?;
+        }
+
+        for (index, resource) in self.resources.iter().enumerate() {
+            let size = resource.size.map(|size| size.to_string());
+            let duration = resource.duration.map(|duration| duration.to_string());
+            let resolution = resource
+                .resolution
+                .map(|(width, height)| format!("{}x{}", width, height));
+            let bitrate = resource.bitrate.map(|bitrate| bitrate.to_string());
+            let audio_channels = resource.audio_channels.map(|channels| channels.to_string());
+            let sample_frequency = resource
+                .sample_frequency
+                .map(|frequency| frequency.to_string());
+
+            let mut element =
+                XmlEvent::start_element("res").attr("protocolInfo", &resource.protocol_info);
+            for (name, value) in [
+                ("size", &size),
+                ("duration", &duration),
+                ("resolution", &resolution),
+                ("bitrate", &bitrate),
+                ("nrAudioChannels", &audio_channels),
+                ("sampleFrequency", &sample_frequency),
+            ] {
+                if let Some(value) = value {
+                    element = element.attr(name, value);
+                }
+            }
+            // the main resource also carries the first subtitle, for renders using the pv extension
+            let pv = self.subtitle_extensions.contains(&SubtitleExtension::Pv);
+            if let Some(subtitle) = self.subtitles.first().filter(|_| index == 0 && pv) {
+                element = element
+                    .attr("pv:subtitleFileUri", &subtitle.uri)
+                    .attr("pv:subtitleFileType", &subtitle.subtitle_type);
+            }
+            writer.write(element)?;
+            writer.write(XmlEvent::characters(&resource.uri))?;
+            writer.write(XmlEvent::end_element())?;
+        }
+
+        let res = self.subtitle_extensions.contains(&SubtitleExtension::Res);
+        let sec = self.subtitle_extensions.contains(&SubtitleExtension::Sec);
+        for subtitle in &self.subtitles {
+            if res {
+                let protocol_info = format!("http-get:*:{}:*", subtitle.served_mime());
+                let mut element =
+                    XmlEvent::start_element("res").attr("protocolInfo", &protocol_info);
+                if let Some(language) = &subtitle.language {
+                    element = element.attr("xml:lang", language);
+                }
+                writer.write(element)?;
+                writer.write(XmlEvent::characters(&subtitle.uri))?;
+                writer.write(XmlEvent::end_element())?;
+            }
+            for &element in ["sec:CaptionInfoEx", "sec:CaptionInfo"]
+                .iter()
+                .filter(|_| sec)
+            {
+                let mut element =
+                    XmlEvent::start_element(element).attr("sec:type", &subtitle.subtitle_type);
+                if let Some(language) = &subtitle.language {
+                    element = element.attr("sec:lang", language);
+                }
+                writer.write(element)?;
+                writer.write(XmlEvent::characters(&subtitle.uri))?;
+                writer.write(XmlEvent::end_element())?;
+            }
+        }
+
+        write_text_element(writer, "upnp:class", self.class.as_str())?;
+        writer.write(XmlEvent::end_element())
+    }
+}
+
+/// A DIDL-Lite document, the metadata sent to a render along with the URI of a media.
+///
+/// ```
+/// use crab_dlna::{DidlItem, DidlLite, DidlResource, UpnpClass};
+///
+/// let didl = DidlLite::new()
+///     .item(
+///         DidlItem::new("Big Buck Bunny", UpnpClass::Movie)
+///             .resource(DidlResource::new("http://192.168.1.2:9000/bunny.mp4", "http-get:*:video/mp4:*")),
+///     )
+///     .to_xml()
+///     .unwrap();
+/// assert!(didl.contains("<dc:title>Big Buck Bunny</dc:title>"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DidlLite {
+    items: Vec<DidlItem>,
+}
+
+impl DidlLite {
+    /// Create a new, empty, DIDL-Lite document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an item to the document.
+    pub fn item(mut self, item: DidlItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Serializes the document to XML.
+    pub fn to_xml(&self) -> Result<String> {
+        let mut buffer = Vec::new();
+        let mut writer = EmitterConfig::new()
+            .write_document_declaration(false)
+            .create_writer(&mut buffer);
+        self.write(&mut writer)
+            .map_err(|err| Error::DLNAMetadataError(err.to_string()))?;
+        String::from_utf8(buffer).map_err(|err| Error::DLNAMetadataError(err.to_string()))
+    }
+
+    fn write<W: std::io::Write>(&self, writer: &mut EventWriter<W>) -> xml::writer::Result<()> {
+        writer.write(
+            XmlEvent::start_element("DIDL-Lite")
+                .default_ns(NS_DIDL_LITE)
+                .ns("dc", NS_DC)
+                .ns("upnp", NS_UPNP)
+                .ns("dlna", NS_DLNA)
+                .ns("sec", NS_SEC)
+                .ns("pv", NS_PV),
+        )?;
+        for item in &self.items {
+            item.write(writer)?;
+        }
+        writer.write(XmlEvent::end_element())
+    }
+}
+
+fn write_text_element<W: std::io::Write>(
+    writer: &mut EventWriter<W>,
+    name: &str,
+    text: &str,
+) -> xml::writer::Result<()> {
+    writer.write(XmlEvent::start_element(name))?;
+    writer.write(XmlEvent::characters(text))?;
+    writer.write(XmlEvent::end_element())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIDL_LITE_START: &str = r#"<DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dlna="urn:schemas-dlna-org:metadata-1-0/" xmlns:pv="http://www.pv.com/pvns/" xmlns:sec="http://www.sec.co.kr/" xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/">"#;
+
+    fn item_xml(item: DidlItem) -> String {
+        let didl = DidlLite::new().item(item).to_xml().unwrap();
+        didl.strip_prefix(DIDL_LITE_START)
+            .and_then(|didl| didl.strip_suffix("</DIDL-Lite>"))
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn empty_document() {
+        assert_eq!(
+            DidlLite::new().to_xml().unwrap(),
+            format!("{} />", DIDL_LITE_START.trim_end_matches('>'))
+        );
+    }
+
+    #[test]
+    fn music_track() {
+        let item = DidlItem::new("Tom & Jerry <1>", UpnpClass::MusicTrack)
+            .id("7", "1")
+            .creator("Artist")
+            .artist("Artist")
+            .album("Album")
+            .track_number(3)
+            .album_art_uri("http://h/cover.jpg?a=1&b=2")
+            .resource(
+                DidlResource::new("http://h/song.mp3", "http-get:*:audio/mpeg:*")
+                    .size(1000)
+                    .duration(RelTime::from_secs(3723))
+                    .bitrate(16000)
+                    .audio_channels(2)
+                    .sample_frequency(44100),
+            );
+        assert_eq!(
+            item_xml(item),
+            concat!(
+                r#"<item id="7" parentID="1" restricted="1">"#,
+                "<dc:title>Tom &amp; Jerry &lt;1&gt;</dc:title>",
+                "<dc:creator>Artist</dc:creator>",
+                "<upnp:artist>Artist</upnp:artist>",
+                "<upnp:album>Album</upnp:album>",
+                "<upnp:originalTrackNumber>3</upnp:originalTrackNumber>",
+                "<upnp:albumArtURI>http://h/cover.jpg?a=1&amp;b=2</upnp:albumArtURI>",
+                r#"<res protocolInfo="http-get:*:audio/mpeg:*" size="1000" duration="1:02:03" bitrate="16000" nrAudioChannels="2" sampleFrequency="44100">http://h/song.mp3</res>"#,
+                "<upnp:class>object.item.audioItem.musicTrack</upnp:class>",
+                "</item>"
+            )
+        );
+    }
+
+    #[test]
+    fn movie_with_subtitles() {
+        let item = DidlItem::new("Movie", UpnpClass::for_kind(MediaKind::Video))
+            .resource(
+                DidlResource::new("http://h/movie.mkv", "http-get:*:video/x-matroska:*")
+                    .resolution(1920, 1080),
+            )
+            .subtitle(DidlSubtitle::new("http://h/movie.srt", "srt").language("en"))
+            .subtitle(DidlSubtitle::new("http://h/movie.smi", "smi"))
+            .subtitle(DidlSubtitle::new("http://h/movie.vtt", "vtt").mime("text/plain"));
+        assert_eq!(
+            item_xml(item),
+            concat!(
+                r#"<item id="0" parentID="-1" restricted="1">"#,
+                "<dc:title>Movie</dc:title>",
+                r#"<res protocolInfo="http-get:*:video/x-matroska:*" resolution="1920x1080" pv:subtitleFileUri="http://h/movie.srt" pv:subtitleFileType="srt">http://h/movie.mkv</res>"#,
+                r#"<res protocolInfo="http-get:*:text/srt:*" xml:lang="en">http://h/movie.srt</res>"#,
+                r#"<sec:CaptionInfoEx sec:type="srt" sec:lang="en">http://h/movie.srt</sec:CaptionInfoEx>"#,
+                r#"<sec:CaptionInfo sec:type="srt" sec:lang="en">http://h/movie.srt</sec:CaptionInfo>"#,
+                r#"<res protocolInfo="http-get:*:smi/caption:*">http://h/movie.smi</res>"#,
+                r#"<sec:CaptionInfoEx sec:type="smi">http://h/movie.smi</sec:CaptionInfoEx>"#,
+                r#"<sec:CaptionInfo sec:type="smi">http://h/movie.smi</sec:CaptionInfo>"#,
+                r#"<res protocolInfo="http-get:*:text/plain:*">http://h/movie.vtt</res>"#,
+                r#"<sec:CaptionInfoEx sec:type="vtt">http://h/movie.vtt</sec:CaptionInfoEx>"#,
+                r#"<sec:CaptionInfo sec:type="vtt">http://h/movie.vtt</sec:CaptionInfo>"#,
+                "<upnp:class>object.item.videoItem.movie</upnp:class>",
+                "</item>"
+            )
+        );
+    }
+
+    #[test]
+    fn subtitle_extensions() {
+        let item = |subtitle_extensions: &[SubtitleExtension]| {
+            item_xml(
+                DidlItem::new("Movie", UpnpClass::Movie)
+                    .resource(DidlResource::new(
+                        "http://h/movie.mp4",
+                        "http-get:*:video/mp4:*",
+                    ))
+                    .subtitle(DidlSubtitle::new("http://h/movie.srt", "srt"))
+                    .subtitle_extensions(subtitle_extensions),
+            )
+        };
+        let pv = item(&[SubtitleExtension::Pv]);
+        assert!(pv.contains("pv:subtitleFileUri"));
+        assert!(!pv.contains("sec:CaptionInfo") && !pv.contains("text/srt"));
+        let sec = item(&[SubtitleExtension::Sec]);
+        assert!(sec.contains("<sec:CaptionInfoEx"));
+        assert!(!sec.contains("pv:") && !sec.contains("text/srt"));
+        let none = item(&[]);
+        assert!(!none.contains("movie.srt"));
+    }
+
+    #[test]
+    fn upnp_classes() {
+        assert_eq!(
+            UpnpClass::for_kind(MediaKind::Image).as_str(),
+            "object.item.imageItem.photo"
+        );
+        assert_eq!(
+            UpnpClass::for_kind(MediaKind::Unknown).to_string(),
+            "object.item"
+        );
+        assert_eq!(
+            UpnpClass::Other("object.item.videoItem.musicVideoClip".to_string()).as_str(),
+            "object.item.videoItem.musicVideoClip"
+        );
+    }
+}
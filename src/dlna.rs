@@ -1,66 +1,286 @@
 use crate::{
+    control::{RelTime, RenderController},
     devices::Render,
+    didl::{DidlItem, DidlLite, DidlResource, DidlSubtitle, UpnpClass},
     error::{Error, Result},
-    streaming::MediaStreamingServer,
+    media::MediaKind,
+    playlist::RepeatMode,
+    probe::MediaProbe,
+    quirks::Quirks,
+    status::{PositionInfo, TransportState},
+    streaming::{MediaItem, MediaStreamingServer},
+    tags::AudioTags,
 };
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::{future::Future, time::Duration};
+use tokio::sync::oneshot;
 use xml::escape::escape_str_attribute;
 
-const PAYLOAD_PLAY: &str = r#"
-    <InstanceID>0</InstanceID>
-    <Speed>1</Speed>
-"#;
+const PLAYBACK_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const PLAYBACK_POLL_MAX_FAILURES: u32 = 5;
+/// Margin to consider that a track reached its end when the render stops
+const PLAYBACK_END_MARGIN: Duration = Duration::from_secs(3);
+/// Polls of a stopped render, without it having played the track, after which the track is considered rejected
+const PLAYBACK_START_MAX_STOPPED_POLLS: u32 = 5;
 
-/// Plays a media file in a DLNA compatible device render, according to the render and media streaming server provided
+/// Options of the playback of the media items
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackOptions {
+    /// How the media items are repeated
+    pub repeat: RepeatMode,
+    /// Refuse to play when the render declares it can not play a media item, instead of only warning
+    pub strict: bool,
+}
+
+/// Options of a slideshow of images
+#[derive(Debug, Clone, Default)]
+pub struct SlideshowOptions {
+    /// The time each image is shown; without it, only the first image is shown, until the render stops it
+    pub interval: Option<Duration>,
+    /// How the images are repeated
+    pub repeat: RepeatMode,
+}
+
+/// How the playback of a track ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrackEnd {
+    /// The render reached the end of the track
+    Finished,
+    /// The render was stopped before the end of the track
+    Stopped,
+    /// The render advanced to the next track on its own (gapless playback)
+    Advanced,
+    /// The render stopped without playing the track, usually because it could not load it
+    Rejected,
+}
+
+/// Plays a media file in a DLNA compatible device render, according to the render and media streaming server provided.
+///
+/// All the media items of the streaming server are played in order.
+/// Returns once the render finishes playing the media (or it is stopped).
 pub async fn play(render: Render, streaming_server: MediaStreamingServer) -> Result<()> {
-    let subtitle_uri = streaming_server.subtitle_uri();
-    let payload_subtitle = match subtitle_uri {
-        Some(subtitle_uri) => {
-            escape_str_attribute(
-                format!(r###"
-                    <DIDL-Lite xmlns="urn:schemas-upnp-org:metadata-1-0/DIDL-Lite/"
-                               xmlns:dc="http://purl.org/dc/elements/1.1/" 
-                               xmlns:upnp="urn:schemas-upnp-org:metadata-1-0/upnp/" 
-                               xmlns:dlna="urn:schemas-dlna-org:metadata-1-0/" 
-                               xmlns:sec="http://www.sec.co.kr/" 
-                               xmlns:xbmc="urn:schemas-xbmc-org:metadata-1-0/">
-                        <item id="0" parentID="-1" restricted="1">
-                            <dc:title>nano-dlna Video</dc:title>
-                            <res protocolInfo="http-get:*:video/{type_video}:" xmlns:pv="http://www.pv.com/pvns/" pv:subtitleFileUri="{uri_sub}" pv:subtitleFileType="{type_sub}">{uri_video}</res>
-                            <res protocolInfo="http-get:*:text/srt:*">{uri_sub}</res>
-                            <res protocolInfo="http-get:*:smi/caption:*">{uri_sub}</res>
-                            <sec:CaptionInfoEx sec:type="{type_sub}">{uri_sub}</sec:CaptionInfoEx>
-                            <sec:CaptionInfo sec:type="{type_sub}">{uri_sub}</sec:CaptionInfo>
-                            <upnp:class>object.item.videoItem.movie</upnp:class>
-                        </item>
-                    </DIDL-Lite>
-                    "###,
-                    uri_video = streaming_server.video_uri(),
-                    type_video = streaming_server.video_type(),
-                    uri_sub = subtitle_uri,
-                    type_sub = streaming_server.subtitle_type().unwrap_or_else(|| "unknown".to_string())
-                ).as_str()
-           ).to_string()
+    play_with_options(render, streaming_server, PlaybackOptions::default()).await
+}
+
+/// Plays the media items of the streaming server in a DLNA compatible device render, according to the playback options provided.
+///
+/// The media items are checked against the formats the render declares, once fetched with
+/// [`Render::fetch_sink_protocol_info`].
+/// Returns once the render finishes playing the media items (or it is stopped).
+pub async fn play_with_options(
+    render: Render,
+    mut streaming_server: MediaStreamingServer,
+    options: PlaybackOptions,
+) -> Result<()> {
+    streaming_server.set_quirks(render.quirks.clone());
+    let media_items = streaming_server.media_items().to_vec();
+    for media_item in &media_items {
+        let profile = media_item.video_profile();
+        if render.supports(profile) {
+            continue;
+        }
+        let video_path = media_item.video_path().display().to_string();
+        match options.strict {
+            true => {
+                return Err(Error::DLNAMediaNotSupported(
+                    video_path,
+                    profile.to_string(),
+                ))
+            }
+            false => warn!(
+                "The render does not declare it can play '{}' ({}), trying anyway",
+                video_path, profile
+            ),
+        }
+    }
+    serve_while(
+        streaming_server,
+        play_media_items(&render, &media_items, &options),
+    )
+    .await
+}
+
+/// Shows the images of the streaming server in a DLNA compatible device render, as a slideshow
+/// according to the options provided.
+///
+/// Returns once the slideshow ends (or the render is stopped).
+pub async fn show(
+    render: Render,
+    mut streaming_server: MediaStreamingServer,
+    options: SlideshowOptions,
+) -> Result<()> {
+    streaming_server.set_quirks(render.quirks.clone());
+    let media_items = streaming_server.media_items().to_vec();
+    serve_while(
+        streaming_server,
+        show_media_items(&render, &media_items, &options),
+    )
+    .await
+}
+
+/// Runs the streaming server until the playback completes
+async fn serve_while(
+    streaming_server: MediaStreamingServer,
+    playback: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    info!("Starting media streaming server...");
+    let (shutdown_sender, shutdown_receiver) = oneshot::channel::<()>();
+    let mut streaming_server_handle = tokio::spawn(async move {
+        streaming_server
+            .run_until(async {
+                shutdown_receiver.await.ok();
+            })
+            .await
+    });
+
+    tokio::select! {
+        result = &mut streaming_server_handle => {
+            return result.map_err(Error::DLNAStreamingError);
+        }
+        result = playback => result?,
+    }
+
+    info!("Stopping media streaming server...");
+    shutdown_sender.send(()).ok();
+    streaming_server_handle
+        .await
+        .map_err(Error::DLNAStreamingError)?;
+
+    Ok(())
+}
+
+/// Plays the media items one after the other, queueing the next one in the render when it supports gapless playback
+async fn play_media_items(
+    render: &Render,
+    media_items: &[MediaItem],
+    options: &PlaybackOptions,
+) -> Result<()> {
+    let controller = RenderController::new(render.clone());
+    let mut gapless = true;
+    let mut rejected = 0;
+    let mut current = Some(0);
+
+    while let Some(mut index) = current {
+        info!("Setting media URI");
+        set_av_transport_uri(render, &media_items[index]).await?;
+        controller.play().await?;
+
+        loop {
+            let next = options.repeat.next_index(index, media_items.len());
+            // repeating the media being played can not be queued: it is set again once it finishes
+            let next_item = next
+                .map(|next| &media_items[next])
+                .filter(|next_item| next_item.video_uri() != media_items[index].video_uri());
+            if let Some(next_item) = next_item.filter(|_| gapless) {
+                if let Err(err) = set_next_av_transport_uri(render, next_item).await {
+                    info!("Render does not support gapless playback: {}", err);
+                    gapless = false;
+                }
+            }
+
+            let next_uri = next_item
+                .filter(|_| gapless)
+                .map(|next_item| next_item.video_uri());
+            match wait_track_end(render, TrackPlayback::new(next_uri.as_deref(), true)).await? {
+                TrackEnd::Advanced => {
+                    info!("Render advanced to the next media");
+                    index = next.expect("advanced to a next media item");
+                    rejected = 0;
+                }
+                TrackEnd::Finished => {
+                    current = next;
+                    rejected = 0;
+                    break;
+                }
+                TrackEnd::Rejected => {
+                    let video_path = media_items[index].video_path().display().to_string();
+                    rejected += 1;
+                    if rejected >= media_items.len() {
+                        return Err(Error::DLNAMediaRejected(video_path));
+                    }
+                    warn!(
+                        "Render stopped without playing '{}', skipping it",
+                        video_path
+                    );
+                    current = next;
+                    break;
+                }
+                TrackEnd::Stopped => {
+                    current = None;
+                    break;
+                }
+            }
+        }
+    }
+
+    info!("Render finished playing");
+    Ok(())
+}
+
+/// Shows the images one after the other, advancing on a timer
+async fn show_media_items(
+    render: &Render,
+    media_items: &[MediaItem],
+    options: &SlideshowOptions,
+) -> Result<()> {
+    let controller = RenderController::new(render.clone());
+    let mut current = Some(0);
+
+    while let Some(index) = current {
+        info!(
+            "Showing image: {}",
+            media_items[index].video_path().display()
+        );
+        set_av_transport_uri(render, &media_items[index]).await?;
+        // renders usually show images as soon as their URI is set, but some wait for Play
+        if let Err(err) = controller.play().await {
+            debug!("Render did not accept Play for an image: {}", err);
         }
-        None => "".to_string()
-    };
-    debug!("Subtitle payload: '{}'", payload_subtitle);
 
+        match options.interval {
+            Some(interval) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    result = wait_track_end(render, TrackPlayback::new(None, false)) => {
+                        result?;
+                        info!("Render stopped the slideshow");
+                        return Ok(());
+                    }
+                }
+                current = options.repeat.next_index(index, media_items.len());
+            }
+            None => {
+                wait_track_end(render, TrackPlayback::new(None, false)).await?;
+                current = None;
+            }
+        }
+    }
+
+    info!("Render finished showing the images");
+    Ok(())
+}
+
+async fn set_av_transport_uri(render: &Render, media_item: &MediaItem) -> Result<()> {
+    if render.quirks.stop_before_set_uri {
+        // the render may refuse to stop when it has no media
+        if let Err(err) = RenderController::new(render.clone()).stop().await {
+            debug!(
+                "Render did not accept Stop before setting the media URI: {}",
+                err
+            );
+        }
+    }
     let payload_setavtransporturi = format!(
         r#"
         <InstanceID>0</InstanceID>
         <CurrentURI>{}</CurrentURI>
         <CurrentURIMetaData>{}</CurrentURIMetaData>
         "#,
-        streaming_server.video_uri(),
-        payload_subtitle
+        media_item.video_uri(),
+        metadata(media_item, &render.quirks)?
     );
     debug!("SetAVTransportURI payload: '{}'", payload_setavtransporturi);
 
-    info!("Starting media streaming server...");
-    let streaming_server_handle = tokio::spawn(async move { streaming_server.run().await });
-
-    info!("Setting Video URI");
     render
         .service
         .action(
@@ -70,17 +290,314 @@ pub async fn play(render: Render, streaming_server: MediaStreamingServer) -> Res
         )
         .await
         .map_err(Error::DLNASetAVTransportURIError)?;
+    Ok(())
+}
+
+async fn set_next_av_transport_uri(render: &Render, media_item: &MediaItem) -> Result<()> {
+    let payload_setnextavtransporturi = format!(
+        r#"
+        <InstanceID>0</InstanceID>
+        <NextURI>{}</NextURI>
+        <NextURIMetaData>{}</NextURIMetaData>
+        "#,
+        media_item.video_uri(),
+        metadata(media_item, &render.quirks)?
+    );
+    debug!(
+        "SetNextAVTransportURI payload: '{}'",
+        payload_setnextavtransporturi
+    );
 
-    info!("Playing video");
     render
         .service
-        .action(render.device.url(), "Play", PAYLOAD_PLAY)
+        .action(
+            render.device.url(),
+            "SetNextAVTransportURI",
+            payload_setnextavtransporturi.as_str(),
+        )
         .await
-        .map_err(Error::DLNAPlayError)?;
+        .map_err(Error::DLNASetNextAVTransportURIError)?;
+    Ok(())
+}
 
-    streaming_server_handle
-        .await
-        .map_err(Error::DLNAStreamingError)?;
+/// Builds the DIDL-Lite metadata of a media item, in the flavour of the render
+fn metadata(media_item: &MediaItem, quirks: &Quirks) -> Result<String> {
+    let video_path = media_item.video_path();
+    let video_profile = media_item.video_profile();
+    let title = video_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| media_item.video_uri());
 
-    Ok(())
+    let no_tags = AudioTags::default();
+    let tags = media_item.tags().unwrap_or(&no_tags);
+
+    let no_probe = MediaProbe::default();
+    let probe = media_item.probe().unwrap_or(&no_probe);
+
+    // the size and bitrate of a transcoded media are only known once it has been transcoded
+    let transcoded = video_profile.transcoded;
+    let mut resource = DidlResource::new(media_item.video_uri(), video_profile.protocol_info());
+    if let Some(file_metadata) = std::fs::metadata(video_path).ok().filter(|_| !transcoded) {
+        resource = resource.size(file_metadata.len());
+    }
+    if let Some(duration) = tags.duration.or(probe.duration) {
+        resource = resource.duration(duration);
+    }
+    if let Some((width, height)) = probe
+        .resolution
+        .filter(|_| video_profile.kind == MediaKind::Video)
+    {
+        resource = resource.resolution(width, height);
+    }
+    if let Some(bitrate) = probe.bitrate.filter(|_| !transcoded) {
+        resource = resource.bitrate(bitrate);
+    }
+    if let Some(audio_channels) = probe.audio_channels {
+        resource = resource.audio_channels(audio_channels);
+    }
+    if let Some(sample_rate) = probe.sample_rate {
+        resource = resource.sample_frequency(sample_rate);
+    }
+
+    let mut item = DidlItem::new(
+        tags.title.clone().unwrap_or(title),
+        UpnpClass::for_kind(video_profile.kind),
+    );
+    if let Some(artist) = tags.artist.clone() {
+        item = item.creator(artist.clone()).artist(artist);
+    }
+    if let Some(album) = tags.album.clone() {
+        item = item.album(album);
+    }
+    if let Some(track_number) = tags.track_number {
+        item = item.track_number(track_number);
+    }
+    if let Some(cover_art_uri) = media_item.cover_art_uri() {
+        item = item.album_art_uri(cover_art_uri);
+    }
+    item = item
+        .resource(resource)
+        .subtitle_extensions(&quirks.subtitle_extensions);
+    for subtitle_file in media_item.subtitle_files() {
+        let mut subtitle = DidlSubtitle::new(subtitle_file.uri(), subtitle_file.extension());
+        if let Some(language) = subtitle_file.language() {
+            subtitle = subtitle.language(language);
+        }
+        if let Some(subtitle_mime) = &quirks.subtitle_mime {
+            subtitle = subtitle.mime(subtitle_mime);
+        }
+        item = item.subtitle(subtitle);
+    }
+
+    let didl = DidlLite::new().item(item).to_xml()?;
+    debug!("DIDL-Lite metadata: '{}'", didl);
+    Ok(escape_str_attribute(&didl).to_string())
+}
+
+/// The playback of a track, followed through the polls of the render
+struct TrackPlayback<'a> {
+    /// The URI of the track queued after this one, to detect the render advancing to it
+    next_uri: Option<&'a str>,
+    /// Whether a render stopping without playing the track is considered to reject it
+    detect_rejection: bool,
+    has_played: bool,
+    has_transitioned: bool,
+    stopped_polls: u32,
+    position: Option<RelTime>,
+    duration: Option<RelTime>,
+}
+
+impl<'a> TrackPlayback<'a> {
+    fn new(next_uri: Option<&'a str>, detect_rejection: bool) -> Self {
+        Self {
+            next_uri,
+            detect_rejection,
+            has_played: false,
+            has_transitioned: false,
+            stopped_polls: 0,
+            position: None,
+            duration: None,
+        }
+    }
+
+    /// Updates the playback from a poll of the render, returning how the track ended if it did
+    fn update(&mut self, state: &TransportState, position_info: &PositionInfo) -> Option<TrackEnd> {
+        match state {
+            TransportState::Transitioning => self.has_transitioned = true,
+            TransportState::Playing | TransportState::PausedPlayback => {
+                self.has_played = true;
+                self.position = position_info.rel_time.or(self.position);
+                self.duration = position_info.track_duration.or(self.duration);
+            }
+            TransportState::Stopped | TransportState::NoMediaPresent if self.has_played => {
+                return Some(match (self.position, self.duration) {
+                    (Some(position), Some(duration))
+                        if duration.as_secs() > 0
+                            && position.as_duration() + PLAYBACK_END_MARGIN
+                                < duration.as_duration() =>
+                    {
+                        info!("Render stopped at {} of {}", position, duration);
+                        TrackEnd::Stopped
+                    }
+                    _ => TrackEnd::Finished,
+                });
+            }
+            // some renders go straight back to stopped when they can not load the track
+            TransportState::Stopped | TransportState::NoMediaPresent if self.detect_rejection => {
+                self.stopped_polls += 1;
+                if self.has_transitioned || self.stopped_polls >= PLAYBACK_START_MAX_STOPPED_POLLS {
+                    return Some(TrackEnd::Rejected);
+                }
+            }
+            _ => {}
+        }
+
+        let advanced = self
+            .next_uri
+            .is_some_and(|next_uri| position_info.track_uri == next_uri);
+        (self.has_played && advanced).then_some(TrackEnd::Advanced)
+    }
+}
+
+/// Waits until the render stops playing the current track, after having started to play it,
+/// or advances to the next track of the playback
+async fn wait_track_end(render: &Render, mut playback: TrackPlayback<'_>) -> Result<TrackEnd> {
+    let mut failures = 0;
+    loop {
+        tokio::time::sleep(PLAYBACK_POLL_INTERVAL).await;
+
+        let (state, position_info) = match poll_render(render).await {
+            Ok(polled) => {
+                failures = 0;
+                polled
+            }
+            Err(err) if failures < PLAYBACK_POLL_MAX_FAILURES => {
+                warn!("Failed to poll the render: {}", err);
+                failures += 1;
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        debug!("Transport state: {}", state);
+
+        if let Some(track_end) = playback.update(&state, &position_info) {
+            return Ok(track_end);
+        }
+    }
+}
+
+async fn poll_render(render: &Render) -> Result<(TransportState, PositionInfo)> {
+    let transport_info = render.transport_info().await?;
+    let position_info = render.position_info().await?;
+    Ok((transport_info.state, position_info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEXT_URI: &str = "http://192.168.1.2:9000/1/movie.mkv";
+
+    fn position_info(track_uri: &str, rel_time: u64, track_duration: u64) -> PositionInfo {
+        PositionInfo {
+            track: 1,
+            track_duration: Some(RelTime::from_secs(track_duration)),
+            track_uri: track_uri.to_string(),
+            rel_time: Some(RelTime::from_secs(rel_time)),
+        }
+    }
+
+    /// Updates a playback with the polls, returning how the track ended and at which poll
+    fn track_end(
+        playback: &mut TrackPlayback,
+        polls: &[(TransportState, PositionInfo)],
+    ) -> Option<(usize, TrackEnd)> {
+        polls
+            .iter()
+            .enumerate()
+            .find_map(|(index, (state, position_info))| {
+                playback
+                    .update(state, position_info)
+                    .map(|track_end| (index, track_end))
+            })
+    }
+
+    #[test]
+    fn track_finished_or_stopped() {
+        let at = |rel_time| position_info("", rel_time, 60);
+        let finished = [
+            (TransportState::Transitioning, at(0)),
+            (TransportState::Playing, at(58)),
+            (TransportState::Stopped, at(0)),
+        ];
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, true), &finished),
+            Some((2, TrackEnd::Finished))
+        );
+        let stopped = [
+            (TransportState::PausedPlayback, at(10)),
+            (TransportState::NoMediaPresent, at(0)),
+        ];
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, true), &stopped),
+            Some((1, TrackEnd::Stopped))
+        );
+    }
+
+    #[test]
+    fn track_advanced() {
+        let polls = [
+            (TransportState::Playing, position_info("", 59, 60)),
+            (TransportState::Playing, position_info(NEXT_URI, 0, 60)),
+        ];
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(Some(NEXT_URI), true), &polls),
+            Some((1, TrackEnd::Advanced))
+        );
+        assert_eq!(track_end(&mut TrackPlayback::new(None, true), &polls), None);
+    }
+
+    #[test]
+    fn track_rejected_after_transitioning() {
+        let polls = [
+            (TransportState::Transitioning, position_info("", 0, 0)),
+            (TransportState::Stopped, position_info("", 0, 0)),
+        ];
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, true), &polls),
+            Some((1, TrackEnd::Rejected))
+        );
+    }
+
+    #[test]
+    fn track_rejected_without_transitioning() {
+        let stopped = vec![
+            (TransportState::NoMediaPresent, position_info("", 0, 0));
+            PLAYBACK_START_MAX_STOPPED_POLLS as usize
+        ];
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, true), &stopped),
+            Some((stopped.len() - 1, TrackEnd::Rejected))
+        );
+        // a render showing an image may stay stopped
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, false), &stopped),
+            None
+        );
+    }
+
+    #[test]
+    fn track_started_late() {
+        let mut polls = vec![
+            (TransportState::Stopped, position_info("", 0, 0));
+            PLAYBACK_START_MAX_STOPPED_POLLS as usize - 1
+        ];
+        polls.push((TransportState::Playing, position_info("", 1, 60)));
+        polls.push((TransportState::Stopped, position_info("", 0, 0)));
+        assert_eq!(
+            track_end(&mut TrackPlayback::new(None, true), &polls),
+            Some((polls.len() - 1, TrackEnd::Stopped))
+        );
+    }
 }
@@ -0,0 +1,979 @@
+use crate::{
+    error::{Error, Result},
+    probe::{
+        ebml_elements, ebml_uint, MKV_CLUSTER, MKV_CODEC_ID, MKV_INFO, MKV_SEGMENT,
+        MKV_TIMECODE_SCALE, MKV_TIMECODE_SCALE_DEFAULT, MKV_TRACKS, MKV_TRACK_ENTRY,
+        MKV_TRACK_TYPE,
+    },
+    subtitles::{ass_text, language_rank, match_language, vtt_text, SubtitleCue, Subtitles},
+    tags::{duration_from_samples, mp4_boxes, mp4_child, read_mp4_moov, ByteReader},
+};
+use log::{debug, info};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+/// Duration of the subtitles of Matroska blocks without duration
+const BLOCK_DURATION_DEFAULT: Duration = Duration::from_secs(5);
+
+/// Matroska element IDs
+const MKV_EBML: u32 = 0x1a45_dfa3;
+const MKV_TRACK_NUMBER: u32 = 0xd7;
+const MKV_LANGUAGE: u32 = 0x22_b59c;
+const MKV_LANGUAGE_IETF: u32 = 0x22_b59d;
+const MKV_NAME: u32 = 0x536e;
+const MKV_FLAG_DEFAULT: u32 = 0x88;
+const MKV_FLAG_FORCED: u32 = 0x55aa;
+const MKV_CLUSTER_TIMESTAMP: u32 = 0xe7;
+const MKV_SIMPLE_BLOCK: u32 = 0xa3;
+const MKV_BLOCK_GROUP: u32 = 0xa0;
+const MKV_BLOCK: u32 = 0xa1;
+const MKV_BLOCK_DURATION: u32 = 0x9b;
+const MKV_TRACK_TYPE_SUBTITLE: u64 = 0x11;
+
+/// The codec of a text subtitle track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleCodec {
+    /// Plain UTF-8 text, possibly with HTML style tags (Matroska `S_TEXT/UTF8`)
+    Text,
+    /// ASS/SSA events (Matroska `S_TEXT/ASS` and `S_TEXT/SSA`)
+    Ass,
+    /// WebVTT cues (Matroska `S_TEXT/WEBVTT`)
+    WebVtt,
+    /// 3GPP timed text (MP4 `tx3g`)
+    TimedText,
+}
+
+impl SubtitleCodec {
+    fn from_matroska(codec_id: &str) -> Option<Self> {
+        match codec_id {
+            "S_TEXT/UTF8" => Some(Self::Text),
+            "S_TEXT/ASS" | "S_TEXT/SSA" => Some(Self::Ass),
+            "S_TEXT/WEBVTT" => Some(Self::WebVtt),
+            _ => None,
+        }
+    }
+
+    /// Converts the content of a block or sample of the track to the text of a cue
+    fn cue_text(&self, data: &[u8]) -> Option<String> {
+        match self {
+            Self::Text => Some(String::from_utf8_lossy(data).trim().replace("\r\n", "\n")),
+            // ReadOrder, Layer, Style, Name, MarginL, MarginR, MarginV, Effect, Text
+            Self::Ass => ass_text(String::from_utf8_lossy(data).splitn(9, ',').nth(8)?),
+            Self::WebVtt => Some(
+                String::from_utf8_lossy(data)
+                    .trim()
+                    .lines()
+                    .map(vtt_text)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+            Self::TimedText => {
+                let mut reader = ByteReader::new(data);
+                let length = reader.u16_be()? as usize;
+                let text = reader.bytes(length)?;
+                let text = match text {
+                    [0xfe, 0xff, utf16 @ ..] => String::from_utf16_lossy(
+                        &utf16
+                            .chunks_exact(2)
+                            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+                            .collect::<Vec<_>>(),
+                    ),
+                    _ => String::from_utf8_lossy(text).to_string(),
+                };
+                Some(text.trim().replace("\r\n", "\n"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for SubtitleCodec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codec = match self {
+            Self::Text => "text",
+            Self::Ass => "ass",
+            Self::WebVtt => "webvtt",
+            Self::TimedText => "tx3g",
+        };
+        write!(f, "{}", codec)
+    }
+}
+
+/// A text subtitle track embedded in a Matroska or MP4 file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmbeddedSubtitleTrack {
+    /// The index of the track among the subtitle tracks of the file, from 1
+    pub index: usize,
+    /// The number (Matroska) or ID (MP4) of the track in the container
+    pub track_number: u64,
+    /// The codec of the track
+    pub codec: SubtitleCodec,
+    /// The language of the track, if known
+    pub language: Option<String>,
+    /// The name of the track
+    pub name: Option<String>,
+    /// Whether the track is flagged as the default one
+    pub default: bool,
+    /// Whether the track is flagged as forced
+    pub forced: bool,
+}
+
+impl fmt::Display for EmbeddedSubtitleTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} ({}, {}",
+            self.index,
+            self.codec,
+            self.language.as_deref().unwrap_or("unknown language")
+        )?;
+        if let Some(name) = &self.name {
+            write!(f, ", '{}'", name)?;
+        }
+        if self.forced {
+            write!(f, ", forced")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// How an embedded subtitle track is selected: by its index among the subtitle tracks, or by its language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtitleTrackSelector {
+    /// The index of the track among the subtitle tracks of the file, from 1
+    Index(usize),
+    /// The language of the track (e.g. `en`, `pt-BR` or `eng`)
+    Language(String),
+}
+
+impl SubtitleTrackSelector {
+    /// Selects a track among the subtitle tracks of a file.
+    ///
+    /// Without selector, it is the first of the [`rank_subtitle_tracks`].
+    pub fn select<'a>(
+        selector: Option<&Self>,
+        tracks: &'a [EmbeddedSubtitleTrack],
+        languages: &[String],
+    ) -> Option<&'a EmbeddedSubtitleTrack> {
+        match selector {
+            Some(Self::Index(index)) => tracks.iter().find(|track| track.index == *index),
+            Some(Self::Language(language)) => tracks
+                .iter()
+                .filter_map(|track| {
+                    let exact = match_language(track.language.as_deref()?, language)?;
+                    Some((!exact, track.forced, track))
+                })
+                .min_by_key(|(inexact, forced, track)| (*inexact, *forced, track.index))
+                .map(|(_, _, track)| track),
+            None => rank_subtitle_tracks(tracks, languages).first().copied(),
+        }
+    }
+}
+
+/// Ranks the subtitle tracks of a file: the tracks in the preferred `languages` come first, then the
+/// default and full (not forced) ones.
+pub fn rank_subtitle_tracks<'a>(
+    tracks: &'a [EmbeddedSubtitleTrack],
+    languages: &[String],
+) -> Vec<&'a EmbeddedSubtitleTrack> {
+    let mut ranked = tracks.iter().collect::<Vec<_>>();
+    ranked.sort_by_key(|track| {
+        (
+            language_rank(track.language.as_deref(), languages),
+            track.forced,
+            !track.default,
+            track.index,
+        )
+    });
+    ranked
+}
+
+impl FromStr for SubtitleTrackSelector {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().parse::<usize>() {
+            Ok(index) => Ok(Self::Index(index)),
+            Err(_) => Ok(Self::Language(s.trim().to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for SubtitleTrackSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Index(index) => write!(f, "{}", index),
+            Self::Language(language) => write!(f, "{}", language),
+        }
+    }
+}
+
+/// Lists the text subtitle tracks embedded in a Matroska or MP4 file.
+pub fn list_subtitle_tracks(path: &Path) -> Result<Vec<EmbeddedSubtitleTrack>> {
+    let read_error = |err| Error::EmbeddedReadError(path.display().to_string(), err);
+    let mut file = File::open(path).map_err(read_error)?;
+    let tracks = match read_magic(&mut file).map_err(read_error)? {
+        Container::Matroska => read_matroska(&mut file, &[]).map_err(read_error)?.0,
+        Container::Mp4 => read_mp4_tracks(&mut file)
+            .map_err(read_error)?
+            .into_iter()
+            .map(|(track, _)| track)
+            .collect(),
+        Container::Other => Vec::new(),
+    };
+    debug!(
+        "Subtitle tracks of '{}': {:?}",
+        path.display(),
+        tracks
+            .iter()
+            .map(|track| track.to_string())
+            .collect::<Vec<_>>()
+    );
+    Ok(tracks)
+}
+
+/// Extracts the subtitles of a track embedded in a Matroska or MP4 file.
+pub fn extract_subtitle_track(path: &Path, track: &EmbeddedSubtitleTrack) -> Result<Subtitles> {
+    let mut subtitles = extract_subtitle_tracks(path, std::slice::from_ref(track))?;
+    Ok(subtitles.pop().unwrap_or_default())
+}
+
+/// Extracts the subtitles of several tracks embedded in a Matroska or MP4 file, reading it only once.
+///
+/// The subtitles are returned in the order of `tracks`.
+pub fn extract_subtitle_tracks(
+    path: &Path,
+    tracks: &[EmbeddedSubtitleTrack],
+) -> Result<Vec<Subtitles>> {
+    let read_error = |err| Error::EmbeddedReadError(path.display().to_string(), err);
+    for track in tracks {
+        info!(
+            "Extracting subtitle track {} of '{}'",
+            track,
+            path.display()
+        );
+    }
+    let mut file = File::open(path).map_err(read_error)?;
+    let tracks_cues = match read_magic(&mut file).map_err(read_error)? {
+        Container::Matroska => read_matroska(&mut file, tracks).map_err(read_error)?.1,
+        Container::Mp4 => {
+            let mp4_tracks = read_mp4_tracks(&mut file).map_err(read_error)?;
+            let mut tracks_cues = Vec::new();
+            for track in tracks {
+                let cues = match mp4_tracks
+                    .iter()
+                    .find(|(mp4_track, _)| mp4_track.track_number == track.track_number)
+                {
+                    Some((_, samples)) => {
+                        read_mp4_cues(&mut file, track.codec, samples).map_err(read_error)?
+                    }
+                    None => Vec::new(),
+                };
+                tracks_cues.push(cues);
+            }
+            tracks_cues
+        }
+        Container::Other => vec![Vec::new(); tracks.len()],
+    };
+    Ok(tracks_cues
+        .into_iter()
+        .map(|mut cues| {
+            cues.retain(|cue| !cue.text.trim().is_empty() && cue.end >= cue.start);
+            cues.sort_by_key(|cue| cue.start);
+            Subtitles { cues }
+        })
+        .collect())
+}
+
+enum Container {
+    Matroska,
+    Mp4,
+    Other,
+}
+
+fn read_magic(file: &mut File) -> std::io::Result<Container> {
+    let mut magic = [0u8; 8];
+    let magic_length = file.read(&mut magic)?;
+    file.rewind()?;
+    Ok(match &magic[..magic_length] {
+        [0x1a, 0x45, 0xdf, 0xa3, ..] => Container::Matroska,
+        [_, _, _, _, b'f', b't', b'y', b'p'] => Container::Mp4,
+        _ => Container::Other,
+    })
+}
+
+// Matroska
+
+/// Reads the ID (with its marker bit) or the size (without it) of an EBML element from a stream
+fn read_vint(reader: &mut impl Read, keep_marker: bool) -> std::io::Result<Option<(u64, usize)>> {
+    let mut first = [0u8; 1];
+    if reader.read(&mut first)? == 0 {
+        return Ok(None);
+    }
+    let length = first[0].leading_zeros() as usize + 1;
+    if length > 8 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "invalid EBML variable length integer",
+        ));
+    }
+    let mut rest = [0u8; 7];
+    reader.read_exact(&mut rest[..length - 1])?;
+    let mut value = match keep_marker {
+        true => first[0] as u64,
+        false => (first[0] as u64) & (0xff >> length),
+    };
+    for byte in &rest[..length - 1] {
+        value = (value << 8) | *byte as u64;
+    }
+    Ok(Some((value, length)))
+}
+
+/// Reads the ID and the size of the next EBML element of a stream; the size is `None` when unknown
+fn read_element_header(reader: &mut impl Read) -> std::io::Result<Option<(u32, Option<u64>)>> {
+    let id = match read_vint(reader, true)? {
+        Some((id, _)) => id as u32,
+        None => return Ok(None),
+    };
+    let size = match read_vint(reader, false)? {
+        Some((size, length)) => (size != (1 << (7 * length)) - 1).then_some(size),
+        None => return Ok(None),
+    };
+    Ok(Some((id, size)))
+}
+
+fn read_element(reader: &mut impl Read, size: u64) -> std::io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    reader.take(size).read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// Reads the text subtitle tracks of a Matroska file and the cues of the blocks of the extracted tracks,
+/// in their order.
+///
+/// Clusters are only read through when extracting tracks, skipping the blocks of the other tracks.
+fn read_matroska(
+    file: &mut File,
+    extracted_tracks: &[EmbeddedSubtitleTrack],
+) -> std::io::Result<(Vec<EmbeddedSubtitleTrack>, Vec<Vec<SubtitleCue>>)> {
+    let mut reader = BufReader::new(file);
+    let mut tracks = Vec::new();
+    let mut tracks_cues = vec![Vec::new(); extracted_tracks.len()];
+    let extracted = |track_number: u64| {
+        extracted_tracks
+            .iter()
+            .position(|track| track.track_number == track_number)
+    };
+    let mut timecode_scale = MKV_TIMECODE_SCALE_DEFAULT;
+    let mut cluster_timestamp = 0;
+
+    while let Some((id, size)) = read_element_header(&mut reader)? {
+        match (id, size) {
+            // the children of these elements are read as if they were siblings
+            (MKV_SEGMENT | MKV_CLUSTER, _) => {
+                if id == MKV_CLUSTER && extracted_tracks.is_empty() {
+                    break;
+                }
+            }
+            (MKV_EBML, Some(size)) => reader.seek_relative(size as i64)?,
+            (MKV_INFO, Some(size)) => {
+                for (id, value) in ebml_elements(&read_element(&mut reader, size)?) {
+                    if id == MKV_TIMECODE_SCALE {
+                        timecode_scale = ebml_uint(value);
+                    }
+                }
+            }
+            (MKV_TRACKS, Some(size)) => {
+                tracks = read_matroska_tracks(&read_element(&mut reader, size)?);
+                if tracks.is_empty() {
+                    break;
+                }
+            }
+            (MKV_CLUSTER_TIMESTAMP, Some(size)) => {
+                cluster_timestamp = ebml_uint(&read_element(&mut reader, size)?)
+            }
+            (MKV_SIMPLE_BLOCK, Some(size)) => {
+                // the track number is read first, so that the blocks of other tracks are skipped
+                let (track_number, length) = match read_vint(&mut reader, false)? {
+                    Some(track_number) => track_number,
+                    None => break,
+                };
+                let rest = size.saturating_sub(length as u64);
+                match extracted(track_number) {
+                    Some(position) => {
+                        let block = read_element(&mut reader, rest)?;
+                        tracks_cues[position].extend(matroska_cue(
+                            extracted_tracks[position].codec,
+                            &block,
+                            cluster_timestamp,
+                            None,
+                            timecode_scale,
+                        ));
+                    }
+                    None => reader.seek_relative(rest as i64)?,
+                }
+            }
+            (MKV_BLOCK_GROUP, Some(size)) => {
+                let block_group = read_element(&mut reader, size)?;
+                let mut block = None;
+                let mut duration = None;
+                for (id, value) in ebml_elements(&block_group) {
+                    match id {
+                        MKV_BLOCK => block = Some(value),
+                        MKV_BLOCK_DURATION => duration = Some(ebml_uint(value)),
+                        _ => {}
+                    }
+                }
+                let block = match block {
+                    Some(block) => block,
+                    None => continue,
+                };
+                let mut block_reader = ByteReader::new(block);
+                let position = crate::probe::ebml_vint(&mut block_reader, false)
+                    .and_then(|(track_number, _)| extracted(track_number));
+                if let Some(position) = position {
+                    tracks_cues[position].extend(matroska_cue(
+                        extracted_tracks[position].codec,
+                        block_reader.rest(),
+                        cluster_timestamp,
+                        duration,
+                        timecode_scale,
+                    ));
+                }
+            }
+            (_, Some(size)) => reader.seek_relative(size as i64)?,
+            (_, None) => break,
+        }
+    }
+    Ok((tracks, tracks_cues))
+}
+
+/// Reads the text subtitle tracks of the `Tracks` element of a Matroska file
+fn read_matroska_tracks(tracks: &[u8]) -> Vec<EmbeddedSubtitleTrack> {
+    let mut subtitle_tracks = Vec::new();
+    for (_, track_entry) in ebml_elements(tracks).filter(|(id, _)| *id == MKV_TRACK_ENTRY) {
+        let mut track_number = None;
+        let mut track_type = None;
+        let mut codec_id = String::new();
+        let mut language = Some("eng".to_string());
+        let mut language_ietf = None;
+        let mut name = None;
+        let mut default = true;
+        let mut forced = false;
+        for (id, value) in ebml_elements(track_entry) {
+            let text = || {
+                String::from_utf8_lossy(value)
+                    .trim_end_matches('\0')
+                    .to_string()
+            };
+            match id {
+                MKV_TRACK_NUMBER => track_number = Some(ebml_uint(value)),
+                MKV_TRACK_TYPE => track_type = Some(ebml_uint(value)),
+                MKV_CODEC_ID => codec_id = text(),
+                MKV_LANGUAGE => language = Some(text()),
+                MKV_LANGUAGE_IETF => language_ietf = Some(text()),
+                MKV_NAME => name = Some(text()).filter(|name| !name.is_empty()),
+                MKV_FLAG_DEFAULT => default = ebml_uint(value) != 0,
+                MKV_FLAG_FORCED => forced = ebml_uint(value) != 0,
+                _ => {}
+            }
+        }
+        if track_type != Some(MKV_TRACK_TYPE_SUBTITLE) {
+            continue;
+        }
+        let (track_number, codec) = match (track_number, SubtitleCodec::from_matroska(&codec_id)) {
+            (Some(track_number), Some(codec)) => (track_number, codec),
+            _ => {
+                debug!("Ignoring subtitle track with codec '{}'", codec_id);
+                continue;
+            }
+        };
+        subtitle_tracks.push(EmbeddedSubtitleTrack {
+            index: subtitle_tracks.len() + 1,
+            track_number,
+            codec,
+            language: language_ietf
+                .or(language)
+                .filter(|language| language != "und"),
+            name,
+            default,
+            forced,
+        });
+    }
+    subtitle_tracks
+}
+
+/// Builds a cue from the content of a Matroska block following its track number
+fn matroska_cue(
+    codec: SubtitleCodec,
+    block: &[u8],
+    cluster_timestamp: u64,
+    duration: Option<u64>,
+    timecode_scale: u64,
+) -> Option<SubtitleCue> {
+    let mut reader = ByteReader::new(block);
+    let relative_timestamp = reader.u16_be()? as i16;
+    // flags, without lacing for subtitles
+    reader.skip(1)?;
+    let timestamp = cluster_timestamp.saturating_add_signed(relative_timestamp as i64);
+    let to_duration = |ticks: u64| Duration::from_nanos(ticks.saturating_mul(timecode_scale));
+    let start = to_duration(timestamp);
+    let end = match duration {
+        Some(duration) => to_duration(timestamp.saturating_add(duration)),
+        None => start + BLOCK_DURATION_DEFAULT,
+    };
+    Some(SubtitleCue {
+        start,
+        end,
+        text: codec.cue_text(reader.rest())?,
+    })
+}
+
+// MP4
+
+/// The samples of an MP4 track, as their offset, size, start and duration (in the timescale of the track)
+struct Mp4Samples {
+    timescale: u32,
+    samples: Vec<(u64, u32, u64, u32)>,
+}
+
+/// Reads the text subtitle tracks of an MP4 file, with their samples
+fn read_mp4_tracks(file: &mut File) -> std::io::Result<Vec<(EmbeddedSubtitleTrack, Mp4Samples)>> {
+    let moov = match read_mp4_moov(file)? {
+        Some(moov) => moov,
+        None => return Ok(Vec::new()),
+    };
+    let file_length = file.metadata()?.len();
+    let mut tracks = Vec::new();
+    for (_, trak) in mp4_boxes(&moov).filter(|(box_type, _)| *box_type == b"trak") {
+        let track = (|| {
+            let mdia = mp4_child(trak, b"mdia")?;
+            let hdlr = mp4_child(mdia, b"hdlr")?;
+            if !matches!(hdlr.get(8..12)?, b"sbtl" | b"text") {
+                return None;
+            }
+            let stbl = mp4_child(mp4_child(mdia, b"minf")?, b"stbl")?;
+            // the type of the first sample entry, after the version, flags, entry count and entry size
+            if mp4_child(stbl, b"stsd")?.get(12..16)? != b"tx3g" {
+                return None;
+            }
+            let (timescale, language) = mp4_media_header(mp4_child(mdia, b"mdhd")?)?;
+            let name = hdlr
+                .get(24..)
+                .map(|name| {
+                    String::from_utf8_lossy(name)
+                        .trim_matches('\0')
+                        .trim()
+                        .to_string()
+                })
+                .filter(|name| !name.is_empty());
+            let track = EmbeddedSubtitleTrack {
+                index: tracks.len() + 1,
+                track_number: mp4_track_id(mp4_child(trak, b"tkhd")?)? as u64,
+                codec: SubtitleCodec::TimedText,
+                language,
+                name,
+                default: false,
+                forced: false,
+            };
+            let samples = Mp4Samples {
+                timescale,
+                samples: mp4_sample_table(stbl, file_length)?,
+            };
+            Some((track, samples))
+        })();
+        tracks.extend(track);
+    }
+    Ok(tracks)
+}
+
+/// Reads the ID of a track from its `tkhd` box
+fn mp4_track_id(tkhd: &[u8]) -> Option<u32> {
+    let mut reader = ByteReader::new(tkhd);
+    let times_length = match reader.u8()? {
+        1 => 16,
+        _ => 8,
+    };
+    reader.skip(3 + times_length)?;
+    reader.u32_be()
+}
+
+/// Reads the timescale and the language of a track from its `mdhd` box
+fn mp4_media_header(mdhd: &[u8]) -> Option<(u32, Option<String>)> {
+    let mut reader = ByteReader::new(mdhd);
+    let (times_length, duration_length) = match reader.u8()? {
+        1 => (16, 8),
+        _ => (8, 4),
+    };
+    reader.skip(3 + times_length)?;
+    let timescale = reader.u32_be()?;
+    reader.skip(duration_length)?;
+    // ISO 639-2/T code, as three 5 bits letters
+    let packed = reader.u16_be()?;
+    let language = [10, 5, 0]
+        .iter()
+        .map(|shift| (((packed >> shift) & 0x1f) as u8 + 0x60) as char)
+        .collect::<String>();
+    let language = Some(language)
+        .filter(|language| language != "und" && language.chars().all(|c| c.is_ascii_lowercase()));
+    Some((timescale, language))
+}
+
+/// Reads the offset, size, start and duration of the samples of a track from its sample table.
+///
+/// The counts of the tables are bounded by the length of their box, and the samples by the length of
+/// the file, so that a corrupt table is rejected instead of being allocated.
+fn mp4_sample_table(stbl: &[u8], file_length: u64) -> Option<Vec<(u64, u32, u64, u32)>> {
+    let table = |box_type: &[u8]| {
+        let mut reader = ByteReader::new(mp4_child(stbl, box_type)?);
+        reader.skip(4)?;
+        Some(reader)
+    };
+    // the number of entries of a table, each of `entry_length` bytes
+    let entry_count = |reader: &mut ByteReader, entry_length: usize| {
+        let count = reader.u32_be()? as usize;
+        (count <= reader.remaining() / entry_length).then_some(count)
+    };
+
+    let mut stsz = table(b"stsz")?;
+    let uniform_size = stsz.u32_be()?;
+    let sizes = match uniform_size {
+        0 => {
+            let sample_count = entry_count(&mut stsz, 4)?;
+            (0..sample_count)
+                .map(|_| stsz.u32_be())
+                .collect::<Option<Vec<_>>>()?
+        }
+        size => {
+            let sample_count = stsz.u32_be()?;
+            if sample_count as u64 * size as u64 > file_length {
+                return None;
+            }
+            vec![size; sample_count as usize]
+        }
+    };
+
+    let mut stts = table(b"stts")?;
+    let mut timings = Vec::with_capacity(sizes.len());
+    let mut start = 0u64;
+    for _ in 0..entry_count(&mut stts, 8)? {
+        let (count, delta) = (stts.u32_be()?, stts.u32_be()?);
+        for _ in 0..(count as usize).min(sizes.len() - timings.len()) {
+            timings.push((start, delta));
+            start = start.saturating_add(delta as u64);
+        }
+    }
+
+    let chunk_offsets = match (table(b"stco"), table(b"co64")) {
+        (Some(mut stco), _) => (0..entry_count(&mut stco, 4)?)
+            .map(|_| stco.u32_be().map(|offset| offset as u64))
+            .collect::<Option<Vec<_>>>()?,
+        (None, Some(mut co64)) => (0..entry_count(&mut co64, 8)?)
+            .map(|_| co64.u64_be())
+            .collect::<Option<Vec<_>>>()?,
+        (None, None) => return None,
+    };
+
+    let mut stsc = table(b"stsc")?;
+    let chunk_runs = (0..entry_count(&mut stsc, 12)?)
+        .map(|_| {
+            let (first_chunk, samples_per_chunk) = (stsc.u32_be()?, stsc.u32_be()?);
+            stsc.skip(4)?;
+            Some((first_chunk as usize, samples_per_chunk as usize))
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut samples = Vec::with_capacity(sizes.len());
+    let mut sample = 0;
+    let mut total_size = 0u64;
+    for (chunk, chunk_offset) in chunk_offsets.iter().enumerate() {
+        let samples_per_chunk = chunk_runs
+            .iter()
+            .rev()
+            .find(|(first_chunk, _)| *first_chunk <= chunk + 1)
+            .map(|(_, samples_per_chunk)| *samples_per_chunk)?;
+        let mut offset = *chunk_offset;
+        for _ in 0..samples_per_chunk {
+            let (size, (start, duration)) = match (sizes.get(sample), timings.get(sample)) {
+                (Some(size), Some(timing)) => (*size, *timing),
+                _ => break,
+            };
+            total_size += size as u64;
+            if offset.checked_add(size as u64)? > file_length || total_size > file_length {
+                return None;
+            }
+            samples.push((offset, size, start, duration));
+            offset += size as u64;
+            sample += 1;
+        }
+    }
+    Some(samples)
+}
+
+/// Reads the cues of the samples of an MP4 text track
+fn read_mp4_cues(
+    file: &mut File,
+    codec: SubtitleCodec,
+    samples: &Mp4Samples,
+) -> std::io::Result<Vec<SubtitleCue>> {
+    let to_duration = |ticks: u64| {
+        duration_from_samples(ticks, samples.timescale.max(1))
+            .unwrap_or_default()
+            .as_duration()
+    };
+    let mut cues = Vec::new();
+    for (offset, size, start, duration) in &samples.samples {
+        file.seek(SeekFrom::Start(*offset))?;
+        let mut sample = vec![0u8; *size as usize];
+        file.read_exact(&mut sample)?;
+        if let Some(text) = codec.cue_text(&sample) {
+            cues.push(SubtitleCue {
+                start: to_duration(*start),
+                end: to_duration(start + *duration as u64),
+                text,
+            });
+        }
+    }
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ebml, mp4_box, with_temp_file};
+
+    fn cue(start_millis: u64, end_millis: u64, text: &str) -> SubtitleCue {
+        SubtitleCue {
+            start: Duration::from_millis(start_millis),
+            end: Duration::from_millis(end_millis),
+            text: text.to_string(),
+        }
+    }
+
+    fn matroska_file() -> Vec<u8> {
+        let video_track = [ebml(MKV_TRACK_NUMBER, &[1]), ebml(MKV_TRACK_TYPE, &[1])].concat();
+        let text_track = [
+            ebml(MKV_TRACK_NUMBER, &[2]),
+            ebml(MKV_TRACK_TYPE, &[0x11]),
+            ebml(MKV_CODEC_ID, b"S_TEXT/UTF8"),
+            ebml(MKV_LANGUAGE, b"fre"),
+            ebml(MKV_NAME, b"French"),
+        ]
+        .concat();
+        let ass_track = [
+            ebml(MKV_TRACK_NUMBER, &[3]),
+            ebml(MKV_TRACK_TYPE, &[0x11]),
+            ebml(MKV_CODEC_ID, b"S_TEXT/ASS"),
+            ebml(MKV_LANGUAGE, b"und"),
+            ebml(MKV_FLAG_DEFAULT, &[0]),
+            ebml(MKV_FLAG_FORCED, &[1]),
+        ]
+        .concat();
+        let tracks = [
+            ebml(MKV_TRACK_ENTRY, &video_track),
+            ebml(MKV_TRACK_ENTRY, &text_track),
+            ebml(MKV_TRACK_ENTRY, &ass_track),
+        ]
+        .concat();
+        // track number, relative timestamp and flags, then the content of the block
+        let block_group = [
+            ebml(MKV_BLOCK, b"\x82\x01\xf4\x00Two\r\nlines"),
+            ebml(MKV_BLOCK_DURATION, &[0x05, 0xdc]),
+        ]
+        .concat();
+        let cluster = [
+            ebml(MKV_CLUSTER_TIMESTAMP, &[0x03, 0xe8]),
+            ebml(MKV_SIMPLE_BLOCK, b"\x81\x00\x00\x80video"),
+            ebml(MKV_SIMPLE_BLOCK, b"\x82\x00\x00\x80First"),
+            ebml(
+                MKV_SIMPLE_BLOCK,
+                b"\x83\x00\x64\x800,0,Default,,0,0,0,,{\\i1}Forced{\\i0}",
+            ),
+            ebml(MKV_BLOCK_GROUP, &block_group),
+        ]
+        .concat();
+        let segment = [
+            ebml(MKV_INFO, &ebml(MKV_TIMECODE_SCALE, &[0x0f, 0x42, 0x40])),
+            ebml(MKV_TRACKS, &tracks),
+            ebml(MKV_CLUSTER, &cluster),
+        ]
+        .concat();
+        [ebml(MKV_EBML, &[]), ebml(MKV_SEGMENT, &segment)].concat()
+    }
+
+    fn mp4_table(box_type: &[u8], values: &[u32]) -> Vec<u8> {
+        let values = values
+            .iter()
+            .flat_map(|value| value.to_be_bytes())
+            .collect::<Vec<_>>();
+        mp4_box(box_type, &[&[0; 4], values.as_slice()].concat())
+    }
+
+    fn mp4_file(stsz: &[u32], stts: &[u32]) -> Vec<u8> {
+        // the samples, as their text length and text, the second one in UTF-16
+        let samples = b"\x00\x05Hello\x00\x06\xfe\xff\x00H\x00i";
+        let tkhd = [&[0; 12], 7u32.to_be_bytes().as_slice(), &[0; 68]].concat();
+        // version 0, times, timescale 1000, duration and language ("eng" as 5 bits letters)
+        let mdhd = [
+            &[0; 12],
+            1000u32.to_be_bytes().as_slice(),
+            &3500u32.to_be_bytes(),
+            &((5u16 << 10) | (14 << 5) | 7).to_be_bytes(),
+            &[0; 2],
+        ]
+        .concat();
+        let hdlr = [&[0; 8], b"sbtl".as_slice(), &[0; 12], b"English\0"].concat();
+        let stsd = [
+            &[0, 0, 0, 0, 0, 0, 0, 1],
+            46u32.to_be_bytes().as_slice(),
+            b"tx3g",
+            &[0; 38],
+        ]
+        .concat();
+        let stbl = [
+            mp4_box(b"stsd", &stsd),
+            mp4_table(b"stts", stts),
+            mp4_table(b"stsc", &[1, 1, 2, 1]),
+            mp4_table(b"stsz", stsz),
+            // the samples follow the 16 bytes of the ftyp box and the 8 bytes of the mdat header
+            mp4_table(b"stco", &[1, 24]),
+        ]
+        .concat();
+        let mdia = [
+            mp4_box(b"mdhd", &mdhd),
+            mp4_box(b"hdlr", &hdlr),
+            mp4_box(b"minf", &mp4_box(b"stbl", &stbl)),
+        ]
+        .concat();
+        let trak = [mp4_box(b"tkhd", &tkhd), mp4_box(b"mdia", &mdia)].concat();
+        [
+            mp4_box(b"ftyp", b"isom\0\0\0\0"),
+            mp4_box(b"mdat", samples),
+            mp4_box(b"moov", &mp4_box(b"trak", &trak)),
+        ]
+        .concat()
+    }
+
+    fn mp4_valid_file() -> Vec<u8> {
+        mp4_file(&[0, 2, 7, 10], &[2, 1, 1500, 1, 2000])
+    }
+
+    #[test]
+    fn matroska_subtitle_tracks() {
+        let (tracks, subtitles) = with_temp_file("tracks.mkv", &matroska_file(), |path| {
+            let tracks = list_subtitle_tracks(path).unwrap();
+            let subtitles = extract_subtitle_tracks(path, &tracks).unwrap();
+            (tracks, subtitles)
+        });
+        assert_eq!(
+            tracks,
+            [
+                EmbeddedSubtitleTrack {
+                    index: 1,
+                    track_number: 2,
+                    codec: SubtitleCodec::Text,
+                    language: Some("fre".to_string()),
+                    name: Some("French".to_string()),
+                    default: true,
+                    forced: false,
+                },
+                EmbeddedSubtitleTrack {
+                    index: 2,
+                    track_number: 3,
+                    codec: SubtitleCodec::Ass,
+                    language: None,
+                    name: None,
+                    default: false,
+                    forced: true,
+                }
+            ]
+        );
+        assert_eq!(
+            subtitles[0].cues,
+            [cue(1000, 6000, "First"), cue(1500, 3000, "Two\nlines")]
+        );
+        assert_eq!(subtitles[1].cues, [cue(1100, 6100, "<i>Forced</i>")]);
+        let french = SubtitleTrackSelector::from_str("fr").unwrap();
+        assert_eq!(
+            SubtitleTrackSelector::select(Some(&french), &tracks, &[]),
+            Some(&tracks[0])
+        );
+    }
+
+    #[test]
+    fn matroska_cue_with_large_timestamps() {
+        let block = b"\x7f\xff\x80Text";
+        let cue = matroska_cue(SubtitleCodec::Text, block, u64::MAX, Some(u64::MAX), 1).unwrap();
+        let max = Duration::from_nanos(u64::MAX);
+        assert_eq!((cue.start, cue.end), (max, max));
+        let cue = matroska_cue(SubtitleCodec::Text, b"\x80\x00\x80Text", 0, None, 1).unwrap();
+        assert_eq!(
+            (cue.start, cue.end),
+            (Duration::ZERO, BLOCK_DURATION_DEFAULT)
+        );
+    }
+
+    #[test]
+    fn mp4_subtitle_track() {
+        let (tracks, subtitles) = with_temp_file("track.mp4", &mp4_valid_file(), |path| {
+            let tracks = list_subtitle_tracks(path).unwrap();
+            let subtitles = extract_subtitle_track(path, &tracks[0]).unwrap();
+            (tracks, subtitles)
+        });
+        assert_eq!(
+            tracks,
+            [EmbeddedSubtitleTrack {
+                index: 1,
+                track_number: 7,
+                codec: SubtitleCodec::TimedText,
+                language: Some("eng".to_string()),
+                name: Some("English".to_string()),
+                default: false,
+                forced: false,
+            }]
+        );
+        assert_eq!(
+            subtitles.cues,
+            [cue(0, 1500, "Hello"), cue(1500, 3500, "Hi")]
+        );
+    }
+
+    #[test]
+    fn mp4_corrupt_sample_tables() {
+        for (stsz, stts) in [
+            // sample counts larger than their table
+            (vec![0, u32::MAX, 7, 10], vec![2, 1, 1500, 1, 2000]),
+            (vec![0, 2, 7, 10], vec![u32::MAX, 1, 1500]),
+            // a uniform sample size larger than the file
+            (vec![u32::MAX, u32::MAX], vec![1, u32::MAX, 1000]),
+            // samples beyond the end of the file
+            (vec![0, 2, 7, 1 << 30], vec![2, 1, 1500, 1, 2000]),
+        ] {
+            let tracks = with_temp_file("corrupt.mp4", &mp4_file(&stsz, &stts), |path| {
+                list_subtitle_tracks(path).unwrap()
+            });
+            assert!(tracks.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated_files() {
+        for (name, data) in [
+            ("truncated.mkv", matroska_file()),
+            ("truncated.mp4", mp4_valid_file()),
+        ] {
+            let tracks = with_temp_file(name, &data, |path| list_subtitle_tracks(path).unwrap());
+            for length in 0..data.len() {
+                with_temp_file(name, &data[..length], |path| {
+                    let _ = list_subtitle_tracks(path);
+                    let _ = extract_subtitle_tracks(path, &tracks);
+                });
+            }
+        }
+    }
+}
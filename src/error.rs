@@ -12,20 +12,100 @@ pub enum Error {
     DevicesCreateError(String, rupnp::Error),
     /// An error occurred when the specified render is not found
     DevicesRenderNotFound(RenderSpec),
+    /// An error occurred while parsing a protocol info entry
+    DevicesProtocolInfoParseError(String),
+    /// An error occurred while parsing the output format of the list of devices
+    DevicesListFormatParseError(String),
     /// An error occurred parsing a host or IP address
     StreamingHostParseError(String),
     /// An error occurred when a certain media file does not exist
     StreamingFileDoesNotExist(String),
+    /// An error occurred when the type of a media file is not known
+    StreamingUnknownMediaType(String),
     /// An error occurred while trying to connect to the render
     StreamingRemoteRenderConnectFail(String, std::io::Error),
     /// An error occurred while trying to identify the host IP address
     StreamingIdentifyLocalAddressError(local_ip_address::Error),
+    /// An error occurred when the render declares it can not play a media
+    DLNAMediaNotSupported(String, String),
+    /// An error occurred when the render stops without playing any media
+    DLNAMediaRejected(String),
     /// An error occurred while sending the SetAVTransportURI DLNA action to the render
     DLNASetAVTransportURIError(rupnp::Error),
+    /// An error occurred while sending the SetNextAVTransportURI DLNA action to the render
+    DLNASetNextAVTransportURIError(rupnp::Error),
     /// An error occurred while sending the Play DLNA action to the render
     DLNAPlayError(rupnp::Error),
+    /// An error occurred while sending the Pause DLNA action to the render
+    DLNAPauseError(rupnp::Error),
+    /// An error occurred while sending the Stop DLNA action to the render
+    DLNAStopError(rupnp::Error),
+    /// An error occurred while sending the Seek DLNA action to the render
+    DLNASeekError(rupnp::Error),
+    /// An error occurred while sending the GetTransportInfo DLNA action to the render
+    DLNAGetTransportInfoError(rupnp::Error),
+    /// An error occurred while sending the GetPositionInfo DLNA action to the render
+    DLNAGetPositionInfoError(rupnp::Error),
+    /// An error occurred while sending the GetMediaInfo DLNA action to the render
+    DLNAGetMediaInfoError(rupnp::Error),
+    /// An error occurred when the render does not provide the RenderingControl service
+    DLNARenderingControlNotSupported(String),
+    /// An error occurred while sending the GetVolume DLNA action to the render
+    DLNAGetVolumeError(rupnp::Error),
+    /// An error occurred while sending the SetVolume DLNA action to the render
+    DLNASetVolumeError(rupnp::Error),
+    /// An error occurred while sending the GetMute DLNA action to the render
+    DLNAGetMuteError(rupnp::Error),
+    /// An error occurred while sending the SetMute DLNA action to the render
+    DLNASetMuteError(rupnp::Error),
+    /// An error occurred while parsing a volume level
+    DLNAVolumeParseError(String),
+    /// An error occurred while subscribing to the events of the render
+    DLNASubscribeError(String, String),
+    /// An error occurred while reading a playlist or a directory
+    PlaylistReadError(String, std::io::Error),
+    /// An error occurred when no media file was found to be played
+    PlaylistEmpty,
+    /// An error occurred while parsing a repeat mode
+    PlaylistRepeatModeParseError(String),
+    /// An error occurred while handling the terminal in interactive mode
+    InteractiveTerminalError(std::io::Error),
+    /// An error occurred while parsing a time position
+    DLNARelTimeParseError(String),
+    /// An error occurred while parsing a track number
+    DLNATrackNumberParseError(String),
     /// An error occurred while serving and streaming the media files
     DLNAStreamingError(tokio::task::JoinError),
+    /// An error occurred while serializing the DIDL-Lite metadata of a media
+    DLNAMetadataError(String),
+    /// An error occurred while reading the tags of an audio file
+    TagsReadError(String, std::io::Error),
+    /// An error occurred while probing the container of a media file
+    ProbeReadError(String, std::io::Error),
+    /// An error occurred while reading a subtitle file
+    SubtitlesReadError(String, std::io::Error),
+    /// The format of a subtitle file is not supported, or it has no subtitles
+    SubtitlesUnsupportedError(String),
+    /// An error occurred while parsing a subtitle format
+    SubtitlesFormatParseError(String),
+    /// An error occurred while parsing a character encoding of subtitles
+    SubtitlesEncodingParseError(String),
+    /// An error occurred while reading the subtitle tracks embedded in a media file
+    EmbeddedReadError(String, std::io::Error),
+    /// An error occurred while parsing a transcoding profile
+    TranscodeProfileParseError(String),
+    /// An error occurred while parsing a transcoding mode
+    TranscodeModeParseError(String),
+    /// An error occurred while starting ffmpeg to transcode a media
+    TranscodeSpawnError(String, std::io::Error),
+    /// An error occurred while parsing an image size
+    ImagesSizeParseError(String),
+    /// An error occurred while rotating or downscaling an image
+    ImagesProcessingError(String, String),
+    /// An error occurred while reading a quirks file
+    QuirksReadError(String, std::io::Error),
+    /// An error occurred while parsing a quirks file
+    QuirksParseError(String, String),
 }
 
 impl fmt::Display for Error {
@@ -51,21 +131,139 @@ impl fmt::Display for Error {
                     write!(f, "No render found within {} seconds", timeout)
                 }
             },
+            Error::DevicesProtocolInfoParseError(protocol_info) => {
+                write!(f, "Failed to parse protocol info '{}'", protocol_info)
+            }
+            Error::DevicesListFormatParseError(format) => write!(
+                f,
+                "Failed to parse list format '{}' (expected 'json', 'yaml', 'table' or 'tsv')",
+                format
+            ),
             Error::StreamingHostParseError(addr) => {
                 write!(f, "Failed to parse host address '{}'", addr)
             }
             Error::StreamingFileDoesNotExist(file) => write!(f, "File '{}' does not exist", file),
+            Error::StreamingUnknownMediaType(file) => {
+                write!(f, "The type of '{}' is not known, it can not be played", file)
+            }
             Error::StreamingRemoteRenderConnectFail(host, err) => {
                 write!(f, "Failed to connect to remote render '{}': {}", host, err)
             }
             Error::StreamingIdentifyLocalAddressError(err) => {
                 write!(f, "Failed to identify local address: {}", err)
             }
+            Error::DLNAMediaNotSupported(file, profile) => write!(
+                f,
+                "The render does not declare it can play '{}' ({})",
+                file, profile
+            ),
+            Error::DLNAMediaRejected(file) => write!(
+                f,
+                "The render stopped without playing '{}', it may not support it",
+                file
+            ),
             Error::DLNASetAVTransportURIError(err) => {
                 write!(f, "Failed to set AVTransportURI: {}", err)
             }
+            Error::DLNASetNextAVTransportURIError(err) => {
+                write!(f, "Failed to set NextAVTransportURI: {}", err)
+            }
             Error::DLNAPlayError(err) => write!(f, "Failed to Play: {}", err),
+            Error::DLNAPauseError(err) => write!(f, "Failed to Pause: {}", err),
+            Error::DLNAStopError(err) => write!(f, "Failed to Stop: {}", err),
+            Error::DLNASeekError(err) => write!(f, "Failed to Seek: {}", err),
+            Error::DLNAGetTransportInfoError(err) => {
+                write!(f, "Failed to get transport info: {}", err)
+            }
+            Error::DLNAGetPositionInfoError(err) => {
+                write!(f, "Failed to get position info: {}", err)
+            }
+            Error::DLNAGetMediaInfoError(err) => write!(f, "Failed to get media info: {}", err),
+            Error::DLNARenderingControlNotSupported(render) => {
+                write!(f, "Render does not support RenderingControl: {}", render)
+            }
+            Error::DLNAGetVolumeError(err) => write!(f, "Failed to get volume: {}", err),
+            Error::DLNASetVolumeError(err) => write!(f, "Failed to set volume: {}", err),
+            Error::DLNAGetMuteError(err) => write!(f, "Failed to get mute: {}", err),
+            Error::DLNASetMuteError(err) => write!(f, "Failed to set mute: {}", err),
+            Error::DLNAVolumeParseError(volume) => {
+                write!(f, "Failed to parse volume '{}'", volume)
+            }
+            Error::DLNASubscribeError(url, reason) => {
+                write!(f, "Failed to subscribe to events at '{}': {}", url, reason)
+            }
+            Error::PlaylistReadError(path, err) => write!(f, "Failed to read '{}': {}", path, err),
+            Error::PlaylistEmpty => write!(f, "No media file found to be played"),
+            Error::PlaylistRepeatModeParseError(repeat_mode) => write!(
+                f,
+                "Failed to parse repeat mode '{}' (expected 'off', 'one' or 'all')",
+                repeat_mode
+            ),
+            Error::InteractiveTerminalError(err) => {
+                write!(f, "Failed to handle the terminal: {}", err)
+            }
+            Error::DLNARelTimeParseError(time) => {
+                write!(f, "Failed to parse time position '{}'", time)
+            }
+            Error::DLNATrackNumberParseError(track) => {
+                write!(f, "Failed to parse track number '{}'", track)
+            }
             Error::DLNAStreamingError(err) => write!(f, "Failed to stream: {}", err),
+            Error::DLNAMetadataError(err) => write!(f, "Failed to build metadata: {}", err),
+            Error::TagsReadError(file, err) => {
+                write!(f, "Failed to read the tags of '{}': {}", file, err)
+            }
+            Error::ProbeReadError(file, err) => {
+                write!(f, "Failed to probe '{}': {}", file, err)
+            }
+            Error::SubtitlesReadError(file, err) => {
+                write!(f, "Failed to read subtitle file '{}': {}", file, err)
+            }
+            Error::SubtitlesUnsupportedError(file) => {
+                write!(f, "Unsupported subtitle file '{}'", file)
+            }
+            Error::SubtitlesFormatParseError(format) => write!(
+                f,
+                "Failed to parse subtitle format '{}' (expected 'srt', 'vtt' or 'smi')",
+                format
+            ),
+            Error::SubtitlesEncodingParseError(encoding) => {
+                write!(f, "Unknown subtitle encoding '{}'", encoding)
+            }
+            Error::EmbeddedReadError(file, err) => {
+                write!(
+                    f,
+                    "Failed to read the subtitle tracks of '{}': {}",
+                    file, err
+                )
+            }
+            Error::TranscodeProfileParseError(profile) => write!(
+                f,
+                "Failed to parse transcoding profile '{}' (expected 'h264-aac-ts', 'h264-aac-mp4', 'mp3', 'h264-aac-ts-subs' or 'copy-ts')",
+                profile
+            ),
+            Error::TranscodeModeParseError(mode) => write!(
+                f,
+                "Failed to parse transcoding mode '{}' (expected 'auto', 'never' or 'always')",
+                mode
+            ),
+            Error::TranscodeSpawnError(ffmpeg, err) => {
+                write!(f, "Failed to run '{}' to transcode: {}", ffmpeg, err)
+            }
+            Error::ImagesSizeParseError(size) => write!(
+                f,
+                "Failed to parse image size '{}' (expected 'medium' or 'large')",
+                size
+            ),
+            Error::ImagesProcessingError(file, err) => {
+                write!(f, "Failed to prepare image '{}': {}", file, err)
+            }
+            Error::QuirksReadError(file, err) => {
+                write!(f, "Failed to read quirks file '{}': {}", file, err)
+            }
+            Error::QuirksParseError(file, err) => {
+                write!(f, "Failed to parse quirks file '{}': {}", file, err)
+            }
         }
     }
 }
@@ -78,8 +276,27 @@ impl std::error::Error for Error {
             Error::StreamingRemoteRenderConnectFail(_, err) => Some(err),
             Error::StreamingIdentifyLocalAddressError(err) => Some(err),
             Error::DLNASetAVTransportURIError(err) => Some(err),
+            Error::DLNASetNextAVTransportURIError(err) => Some(err),
             Error::DLNAPlayError(err) => Some(err),
+            Error::DLNAPauseError(err) => Some(err),
+            Error::DLNAStopError(err) => Some(err),
+            Error::DLNASeekError(err) => Some(err),
+            Error::DLNAGetTransportInfoError(err) => Some(err),
+            Error::DLNAGetPositionInfoError(err) => Some(err),
+            Error::DLNAGetMediaInfoError(err) => Some(err),
+            Error::DLNAGetVolumeError(err) => Some(err),
+            Error::DLNASetVolumeError(err) => Some(err),
+            Error::DLNAGetMuteError(err) => Some(err),
+            Error::DLNASetMuteError(err) => Some(err),
+            Error::PlaylistReadError(_, err) => Some(err),
+            Error::InteractiveTerminalError(err) => Some(err),
             Error::DLNAStreamingError(err) => Some(err),
+            Error::TagsReadError(_, err) => Some(err),
+            Error::ProbeReadError(_, err) => Some(err),
+            Error::SubtitlesReadError(_, err) => Some(err),
+            Error::EmbeddedReadError(_, err) => Some(err),
+            Error::TranscodeSpawnError(_, err) => Some(err),
+            Error::QuirksReadError(_, err) => Some(err),
             _ => None,
         }
     }
@@ -0,0 +1,481 @@
+use crate::{
+    control::RelTime,
+    devices::Render,
+    error::{Error, Result},
+    status::TransportState,
+};
+use futures::{
+    channel::mpsc,
+    stream::{Stream, StreamExt},
+    task::{Context, Poll},
+};
+use http::{Request, Uri};
+use log::{debug, info, warn};
+use std::{collections::VecDeque, pin::Pin, time::Duration};
+use xml::reader::{EventReader, XmlEvent};
+
+/// Path of the streaming server where renders notify their events
+pub(crate) const EVENTS_PATH: &str = "events";
+
+const SUBSCRIPTION_TIMEOUT_SECS: u32 = 300;
+
+/// An event notification received by the streaming server
+#[derive(Debug, Clone)]
+pub(crate) struct EventNotification {
+    pub sid: String,
+    pub body: String,
+}
+
+/// The callback endpoint where a render notifies its events.
+///
+/// It is obtained from [`MediaStreamingServer::event_callback`](crate::MediaStreamingServer::event_callback),
+/// and must be used to subscribe to the render events while the streaming server is running.
+#[derive(Debug)]
+pub struct EventCallback {
+    pub(crate) uri: String,
+    pub(crate) receiver: mpsc::UnboundedReceiver<EventNotification>,
+}
+
+/// A change in the state of a render, notified through UPnP eventing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderEvent {
+    /// The transport state changed
+    TransportState(TransportState),
+    /// The transport status changed
+    TransportStatus(String),
+    /// The URI of the current track changed
+    CurrentTrackURI(String),
+    /// The duration of the current track changed
+    CurrentTrackDuration(RelTime),
+    /// The master volume changed
+    Volume(u16),
+    /// The master mute changed
+    Mute(bool),
+    /// Any other state variable changed, with its name and value
+    Other(String, String),
+}
+
+impl RenderEvent {
+    fn from_variable(name: &str, value: &str) -> Self {
+        match name {
+            "TransportState" => Self::TransportState(TransportState::from(value)),
+            "TransportStatus" => Self::TransportStatus(value.to_owned()),
+            "CurrentTrackURI" => Self::CurrentTrackURI(value.to_owned()),
+            "CurrentTrackDuration" => match value.parse() {
+                Ok(duration) => Self::CurrentTrackDuration(duration),
+                Err(_) => Self::Other(name.to_owned(), value.to_owned()),
+            },
+            "Volume" => match value.parse() {
+                Ok(volume) => Self::Volume(volume),
+                Err(_) => Self::Other(name.to_owned(), value.to_owned()),
+            },
+            "Mute" => Self::Mute(value == "1" || value == "true"),
+            _ => Self::Other(name.to_owned(), value.to_owned()),
+        }
+    }
+}
+
+/// A stream of events of a render, from its AVTransport and RenderingControl services.
+///
+/// The subscriptions are renewed in background while the stream is alive.
+pub struct RenderEvents {
+    render: Render,
+    receiver: mpsc::UnboundedReceiver<EventNotification>,
+    subscriptions: Vec<(rupnp::Service, String)>,
+    pending: VecDeque<RenderEvent>,
+    renewal_handle: tokio::task::JoinHandle<()>,
+}
+
+impl RenderEvents {
+    /// Subscribes to the events of the render, which will be notified to the callback endpoint.
+    pub async fn subscribe(render: &Render, callback: EventCallback) -> Result<Self> {
+        let services: Vec<rupnp::Service> = std::iter::once(render.service.clone())
+            .chain(render.rendering_control.clone())
+            .collect();
+
+        let mut subscriptions = Vec::new();
+        let mut timeout_secs = SUBSCRIPTION_TIMEOUT_SECS;
+        for service in services {
+            let event_sub_url = event_sub_url(render, &service).await?;
+            let (sid, timeout) = subscribe(&event_sub_url, &callback.uri).await?;
+            info!(
+                "Subscribed to events of {} with SID {}",
+                service.service_type(),
+                sid
+            );
+            timeout_secs = timeout_secs.min(timeout);
+            subscriptions.push((service, sid));
+        }
+
+        let renewal_handle = tokio::spawn(renew_subscriptions(
+            render.device.url().clone(),
+            subscriptions.clone(),
+            timeout_secs,
+        ));
+
+        Ok(Self {
+            render: render.clone(),
+            receiver: callback.receiver,
+            subscriptions,
+            pending: VecDeque::new(),
+            renewal_handle,
+        })
+    }
+
+    /// Cancels the subscriptions to the render events.
+    pub async fn unsubscribe(self) {
+        self.renewal_handle.abort();
+        for (service, sid) in &self.subscriptions {
+            if let Err(err) = service.unsubscribe(self.render.device.url(), sid).await {
+                warn!("Failed to unsubscribe {}: {}", sid, err);
+            }
+        }
+    }
+}
+
+impl Stream for RenderEvents {
+    type Item = RenderEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Poll::Ready(Some(event));
+            }
+            match self.receiver.poll_next_unpin(cx) {
+                Poll::Ready(Some(notification)) => {
+                    if !self
+                        .subscriptions
+                        .iter()
+                        .any(|(_, sid)| *sid == notification.sid)
+                    {
+                        debug!("Ignoring event of unknown SID {}", notification.sid);
+                        continue;
+                    }
+                    let events = parse_notification(&notification.body);
+                    self.pending.extend(events);
+                }
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl Drop for RenderEvents {
+    fn drop(&mut self) {
+        self.renewal_handle.abort();
+    }
+}
+
+async fn renew_subscriptions(
+    device_url: Uri,
+    subscriptions: Vec<(rupnp::Service, String)>,
+    timeout_secs: u32,
+) {
+    let renewal_interval = Duration::from_secs((timeout_secs / 2).max(1) as u64);
+    loop {
+        tokio::time::sleep(renewal_interval).await;
+        for (service, sid) in &subscriptions {
+            debug!("Renewing subscription {}", sid);
+            if let Err(err) = service
+                .renew_subscription(&device_url, sid, timeout_secs)
+                .await
+            {
+                warn!("Failed to renew subscription {}: {}", sid, err);
+            }
+        }
+    }
+}
+
+/// Retrieves the event subscription URL of a service from the device description
+async fn event_sub_url(render: &Render, service: &rupnp::Service) -> Result<Uri> {
+    let device_url = render.device.url();
+    let subscribe_error =
+        |reason: String| Error::DLNASubscribeError(device_url.to_string(), reason);
+
+    let response = hyper::Client::new()
+        .get(device_url.clone())
+        .await
+        .map_err(|err| subscribe_error(err.to_string()))?;
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(|err| subscribe_error(err.to_string()))?;
+
+    let service_type = service.service_type().to_string();
+    let mut current_element = String::new();
+    let mut current_service_type = String::new();
+    let mut current_event_sub_url = String::new();
+    for event in EventReader::new(body.as_ref()) {
+        match event.map_err(|err| subscribe_error(err.to_string()))? {
+            XmlEvent::StartElement { name, .. } => {
+                if name.local_name == "service" {
+                    current_service_type.clear();
+                    current_event_sub_url.clear();
+                }
+                current_element = name.local_name;
+            }
+            XmlEvent::Characters(text) => match current_element.as_str() {
+                "serviceType" => current_service_type.push_str(text.trim()),
+                "eventSubURL" => current_event_sub_url.push_str(text.trim()),
+                _ => {}
+            },
+            XmlEvent::EndElement { name } => {
+                if name.local_name == "service" && current_service_type == service_type {
+                    return resolve_url(device_url, &current_event_sub_url)
+                        .ok_or_else(|| subscribe_error(current_event_sub_url.clone()));
+                }
+                current_element.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Err(subscribe_error(format!(
+        "no eventSubURL found for {}",
+        service_type
+    )))
+}
+
+fn resolve_url(base: &Uri, url: &str) -> Option<Uri> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return url.parse().ok();
+    }
+    let path = match url.starts_with('/') {
+        true => url.to_owned(),
+        false => format!("/{}", url),
+    };
+    let mut parts = base.clone().into_parts();
+    parts.path_and_query = Some(path.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+/// Sends a SUBSCRIBE request, returning the SID and the timeout of the subscription
+async fn subscribe(event_sub_url: &Uri, callback_uri: &str) -> Result<(String, u32)> {
+    let subscribe_error =
+        |reason: String| Error::DLNASubscribeError(event_sub_url.to_string(), reason);
+
+    debug!(
+        "Subscribing to {} with callback {}",
+        event_sub_url, callback_uri
+    );
+    let request = Request::builder()
+        .method("SUBSCRIBE")
+        .uri(event_sub_url.clone())
+        .header("CALLBACK", format!("<{}>", callback_uri))
+        .header("NT", "upnp:event")
+        .header("TIMEOUT", format!("Second-{}", SUBSCRIPTION_TIMEOUT_SECS))
+        .body(hyper::Body::empty())
+        .map_err(|err| subscribe_error(err.to_string()))?;
+
+    let response = hyper::Client::new()
+        .request(request)
+        .await
+        .map_err(|err| subscribe_error(err.to_string()))?;
+    if !response.status().is_success() {
+        return Err(subscribe_error(format!(
+            "unexpected status {}",
+            response.status()
+        )));
+    }
+
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned)
+    };
+    let sid = header("SID").ok_or_else(|| subscribe_error("missing SID header".to_string()))?;
+    let timeout = header("TIMEOUT")
+        .and_then(|timeout| timeout.trim_start_matches("Second-").parse::<u32>().ok())
+        .unwrap_or(SUBSCRIPTION_TIMEOUT_SECS);
+
+    Ok((sid, timeout))
+}
+
+/// Parses the property set of an event notification, expanding the `LastChange` variables
+fn parse_notification(body: &str) -> Vec<RenderEvent> {
+    let mut events = Vec::new();
+    for (name, value) in parse_elements(body, 3, None) {
+        match name.as_str() {
+            "LastChange" => events.extend(parse_last_change(&value)),
+            _ => events.push(RenderEvent::from_variable(&name, &value)),
+        }
+    }
+    events
+}
+
+fn parse_last_change(last_change: &str) -> Vec<RenderEvent> {
+    debug!("LastChange: {}", last_change);
+    parse_elements(last_change, 3, Some("val"))
+        .into_iter()
+        .map(|(name, value)| RenderEvent::from_variable(&name, &value))
+        .collect()
+}
+
+/// Collects the name and value of the elements at a given depth, reading the value either from
+/// an attribute or from the text content; elements of non master channels are skipped
+fn parse_elements(xml: &str, depth: usize, attribute: Option<&str>) -> Vec<(String, String)> {
+    let mut elements = Vec::new();
+    let mut current_depth = 0;
+    let mut current: Option<(String, String)> = None;
+    for event in EventReader::from_str(xml) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                current_depth += 1;
+                if current_depth != depth {
+                    continue;
+                }
+                let attr = |attr_name: &str| {
+                    attributes
+                        .iter()
+                        .find(|a| a.name.local_name == attr_name)
+                        .map(|a| a.value.clone())
+                };
+                if attr("channel").is_some_and(|channel| channel != "Master") {
+                    continue;
+                }
+                match attribute {
+                    Some(attribute) => {
+                        if let Some(value) = attr(attribute) {
+                            elements.push((name.local_name, value));
+                        }
+                    }
+                    None => current = Some((name.local_name, String::new())),
+                }
+            }
+            Ok(XmlEvent::Characters(text)) | Ok(XmlEvent::CData(text)) => {
+                if let Some((_, value)) = current.as_mut() {
+                    value.push_str(&text);
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => {
+                if current_depth == depth {
+                    elements.extend(current.take());
+                }
+                current_depth -= 1;
+            }
+            Ok(_) => {}
+            Err(err) => {
+                warn!("Failed to parse event: {}", err);
+                break;
+            }
+        }
+    }
+    elements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(properties: &str) -> String {
+        format!(
+            r#"<?xml version="1.0"?><e:propertyset xmlns:e="urn:schemas-upnp-org:event-1-0">{}</e:propertyset>"#,
+            properties
+        )
+    }
+
+    fn last_change(variables: &str) -> String {
+        let last_change = format!(
+            r#"<Event xmlns="urn:schemas-upnp-org:metadata-1-0/AVT/"><InstanceID val="0">{}</InstanceID></Event>"#,
+            variables
+        );
+        let escaped = last_change
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;");
+        notification(&format!(
+            "<e:property><LastChange>{}</LastChange></e:property>",
+            escaped
+        ))
+    }
+
+    #[test]
+    fn parse_last_change_notification() {
+        let body = last_change(concat!(
+            r#"<TransportState val="PLAYING"/>"#,
+            r#"<TransportStatus val="OK"/>"#,
+            r#"<CurrentTrackURI val="http://host/movie.mkv?a=1&amp;b=2"/>"#,
+            r#"<CurrentTrackDuration val="0:01:30"/>"#,
+            r#"<Volume channel="Master" val="42"/>"#,
+            r#"<Volume channel="LF" val="10"/>"#,
+            r#"<Mute channel="Master" val="1"/>"#,
+            r#"<NumberOfTracks val="1"/>"#,
+        ));
+        assert_eq!(
+            parse_notification(&body),
+            [
+                RenderEvent::TransportState(TransportState::Playing),
+                RenderEvent::TransportStatus("OK".to_owned()),
+                RenderEvent::CurrentTrackURI("http://host/movie.mkv?a=1&b=2".to_owned()),
+                RenderEvent::CurrentTrackDuration("0:01:30".parse().unwrap()),
+                RenderEvent::Volume(42),
+                RenderEvent::Mute(true),
+                RenderEvent::Other("NumberOfTracks".to_owned(), "1".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_plain_notification() {
+        let body = notification(concat!(
+            "<e:property><TransportState>STOPPED</TransportState></e:property>",
+            "<e:property><Volume>loud</Volume></e:property>",
+            "<e:property><CurrentTrackDuration>NOT_IMPLEMENTED</CurrentTrackDuration></e:property>",
+            "<e:property><Mute>0</Mute></e:property>",
+        ));
+        assert_eq!(
+            parse_notification(&body),
+            [
+                RenderEvent::TransportState(TransportState::Stopped),
+                RenderEvent::Other("Volume".to_owned(), "loud".to_owned()),
+                RenderEvent::Other(
+                    "CurrentTrackDuration".to_owned(),
+                    "NOT_IMPLEMENTED".to_owned()
+                ),
+                RenderEvent::Mute(false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_malformed_notification() {
+        assert!(parse_notification("").is_empty());
+        assert!(parse_notification("not xml").is_empty());
+        assert!(parse_notification(&notification("")).is_empty());
+        // the events parsed before the error are kept
+        let body = notification(
+            "<e:property><TransportState>PLAYING</TransportState></e:property><e:property><Mute>",
+        );
+        assert_eq!(
+            parse_notification(&body),
+            [RenderEvent::TransportState(TransportState::Playing)]
+        );
+        // an invalid LastChange is skipped
+        let body = notification("<e:property><LastChange>&lt;Event&gt;</LastChange></e:property>");
+        assert!(parse_notification(&body).is_empty());
+    }
+
+    #[test]
+    fn resolve_event_sub_urls() {
+        let base: Uri = "http://192.168.1.2:8080/description.xml".parse().unwrap();
+        let resolve = |url| resolve_url(&base, url).map(|uri| uri.to_string());
+        assert_eq!(
+            resolve("/AVTransport/event").as_deref(),
+            Some("http://192.168.1.2:8080/AVTransport/event")
+        );
+        assert_eq!(
+            resolve("AVTransport/event").as_deref(),
+            Some("http://192.168.1.2:8080/AVTransport/event")
+        );
+        assert_eq!(
+            resolve("http://192.168.1.3/event").as_deref(),
+            Some("http://192.168.1.3/event")
+        );
+        assert_eq!(resolve("/invalid path"), None);
+    }
+}
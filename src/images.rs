@@ -0,0 +1,215 @@
+use crate::error::{Error, Result};
+use image::{
+    codecs::jpeg::JpegEncoder, metadata::Orientation, DynamicImage, ImageDecoder, ImageReader,
+};
+use log::{debug, info};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const JPEG_QUALITY: u8 = 90;
+
+/// A DLNA JPEG profile, bounding the resolution of the images shown in a render
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageSize {
+    /// `JPEG_MED`, up to 1024x768
+    Medium,
+    /// `JPEG_LRG`, up to 4096x4096
+    Large,
+}
+
+impl ImageSize {
+    /// Returns the maximum width and height of the profile
+    pub fn max_dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Medium => (1024, 768),
+            Self::Large => (4096, 4096),
+        }
+    }
+
+    /// Returns the DLNA profile name
+    pub fn dlna_profile(&self) -> &'static str {
+        match self {
+            Self::Medium => "JPEG_MED",
+            Self::Large => "JPEG_LRG",
+        }
+    }
+
+    fn fits(&self, width: u32, height: u32) -> bool {
+        let (max_width, max_height) = self.max_dimensions();
+        width <= max_width && height <= max_height
+    }
+}
+
+impl FromStr for ImageSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "med" | "medium" | "jpeg_med" => Ok(Self::Medium),
+            "lrg" | "large" | "jpeg_lrg" => Ok(Self::Large),
+            _ => Err(Error::ImagesSizeParseError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for ImageSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let size = match self {
+            Self::Medium => "medium",
+            Self::Large => "large",
+        };
+        write!(f, "{}", size)
+    }
+}
+
+/// How images are prepared before being shown
+#[derive(Debug, Clone, Default)]
+pub struct ImageOptions {
+    /// Rotate the images according to their EXIF orientation
+    pub rotate: bool,
+    /// Downscale the images (and convert them to JPEG) to fit a DLNA profile
+    pub downscale: Option<ImageSize>,
+}
+
+/// A temporary directory holding the prepared images, removed when dropped
+#[derive(Debug)]
+pub struct PreparedImages {
+    directory: PathBuf,
+    paths: Vec<PathBuf>,
+}
+
+impl PreparedImages {
+    /// Returns the paths of the images to be shown, in order
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+}
+
+impl Drop for PreparedImages {
+    fn drop(&mut self) {
+        if self.directory.exists() {
+            debug!("Removing prepared images: {}", self.directory.display());
+            std::fs::remove_dir_all(&self.directory).ok();
+        }
+    }
+}
+
+/// Prepares the images to be shown, rotating and downscaling them when required by the options.
+///
+/// Images that need no change are shown as they are.
+pub fn prepare_images(image_paths: &[PathBuf], options: &ImageOptions) -> Result<PreparedImages> {
+    let mut prepared_images = PreparedImages {
+        directory: create_temp_directory()?,
+        paths: Vec::new(),
+    };
+    for (index, image_path) in image_paths.iter().enumerate() {
+        let output_directory = prepared_images.directory.join(index.to_string());
+        let prepared_path = prepare_image(image_path, options, &output_directory)?;
+        prepared_images.paths.push(prepared_path);
+    }
+    Ok(prepared_images)
+}
+
+/// Creates a directory of its own in the temporary directory, with a random name not predictable by other users
+fn create_temp_directory() -> Result<PathBuf> {
+    loop {
+        let directory = std::env::temp_dir().join(format!(
+            "crab-dlna-{}-{:016x}",
+            std::process::id(),
+            rand::random::<u64>()
+        ));
+        match std::fs::create_dir(&directory) {
+            Ok(()) => return Ok(directory),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => {
+                return Err(Error::ImagesProcessingError(
+                    directory.display().to_string(),
+                    err.to_string(),
+                ))
+            }
+        }
+    }
+}
+
+fn prepare_image(
+    image_path: &Path,
+    options: &ImageOptions,
+    output_directory: &Path,
+) -> Result<PathBuf> {
+    let image_error = |err: image::ImageError| {
+        Error::ImagesProcessingError(image_path.display().to_string(), err.to_string())
+    };
+    let io_error = |err: std::io::Error| {
+        Error::ImagesProcessingError(image_path.display().to_string(), err.to_string())
+    };
+
+    let mut decoder = ImageReader::open(image_path)
+        .map_err(io_error)?
+        .with_guessed_format()
+        .map_err(io_error)?
+        .into_decoder()
+        .map_err(image_error)?;
+    let orientation = match options.rotate {
+        true => decoder.orientation().map_err(image_error)?,
+        false => Orientation::NoTransforms,
+    };
+    let (width, height) = decoder.dimensions();
+    let is_jpeg = image_path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| ["jpg", "jpeg"].contains(&extension.to_lowercase().as_str()));
+    let needs_downscale = options
+        .downscale
+        .is_some_and(|size| !size.fits(width, height) || !is_jpeg);
+
+    if orientation == Orientation::NoTransforms && !needs_downscale {
+        return Ok(image_path.to_owned());
+    }
+
+    info!("Preparing image: {}", image_path.display());
+    let mut image = DynamicImage::from_decoder(decoder).map_err(image_error)?;
+    image.apply_orientation(orientation);
+    if let Some(size) = options.downscale {
+        let (max_width, max_height) = size.max_dimensions();
+        if !size.fits(image.width(), image.height()) {
+            debug!(
+                "Downscaling {}x{} image to fit {}",
+                image.width(),
+                image.height(),
+                size.dlna_profile()
+            );
+            image = image.resize(max_width, max_height, image::imageops::FilterType::Lanczos3);
+        }
+    }
+
+    std::fs::create_dir(output_directory).map_err(io_error)?;
+    let output_path = output_directory
+        .join(image_path.file_stem().unwrap_or_default())
+        .with_extension("jpg");
+    let mut output = std::fs::File::create(&output_path).map_err(io_error)?;
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY))
+        .map_err(image_error)?;
+    debug!("Prepared image: {}", output_path.display());
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepared_images_directories() {
+        let first = prepare_images(&[], &ImageOptions::default()).unwrap();
+        let second = prepare_images(&[], &ImageOptions::default()).unwrap();
+        assert_ne!(first.directory, second.directory);
+        let second_directory = second.directory.clone();
+        drop(first);
+        assert!(second_directory.is_dir());
+        drop(second);
+        assert!(!second_directory.exists());
+    }
+}
@@ -0,0 +1,162 @@
+use rupnp::{
+    scpd::{Argument, StateVariable, StateVariableKind, SCPD},
+    DeviceSpec,
+};
+use std::{future::Future, pin::Pin};
+
+/// The tree of a UPnP device: its services, with their actions and state variables, and its embedded devices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceTree {
+    /// The device type (URN)
+    pub device_type: String,
+    /// The friendly name
+    pub friendly_name: String,
+    /// The services of the device
+    pub services: Vec<ServiceSpec>,
+    /// The devices embedded in the device
+    pub devices: Vec<DeviceTree>,
+}
+
+/// A service of a device, from its service description (SCPD)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceSpec {
+    /// The service type (URN)
+    pub service_type: String,
+    /// The service ID
+    pub service_id: String,
+    /// The actions of the service
+    pub actions: Vec<ActionSpec>,
+    /// The state variables of the service
+    pub state_variables: Vec<StateVariableSpec>,
+    /// The error reading the service description, if it can not be read
+    pub error: Option<String>,
+}
+
+/// An action of a service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionSpec {
+    /// The name of the action
+    pub name: String,
+    /// The input arguments of the action
+    pub input_arguments: Vec<ArgumentSpec>,
+    /// The output arguments of the action
+    pub output_arguments: Vec<ArgumentSpec>,
+}
+
+/// An argument of an action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentSpec {
+    /// The name of the argument
+    pub name: String,
+    /// The name of the state variable giving the type of the argument
+    pub state_variable: String,
+}
+
+/// A state variable of a service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateVariableSpec {
+    /// The name of the state variable
+    pub name: String,
+    /// The data type, when the variable is not restricted to a list or a range of values
+    pub data_type: Option<String>,
+    /// The values the variable is restricted to (empty when not restricted to a list)
+    pub allowed_values: Vec<String>,
+    /// The range the variable is restricted to (`minimum..=maximum:step`)
+    pub allowed_range: Option<String>,
+    /// The default value
+    pub default: Option<String>,
+    /// Whether changes of the variable are sent as events
+    pub sends_events: bool,
+}
+
+impl DeviceTree {
+    /// Fetches the description of every service of a device and of its embedded devices.
+    pub async fn inspect(device: &rupnp::Device) -> Self {
+        Self::inspect_spec(device, device.url()).await
+    }
+
+    fn inspect_spec<'a>(
+        device: &'a DeviceSpec,
+        url: &'a http::Uri,
+    ) -> Pin<Box<dyn Future<Output = Self> + 'a>> {
+        Box::pin(async move {
+            let mut services = Vec::new();
+            for service in device.services() {
+                let scpd = service.scpd(url).await;
+                services.push(ServiceSpec::new(service, scpd));
+            }
+            let mut devices = Vec::new();
+            for embedded_device in device.devices() {
+                devices.push(Self::inspect_spec(embedded_device, url).await);
+            }
+            Self {
+                device_type: device.device_type().to_string(),
+                friendly_name: device.friendly_name().to_string(),
+                services,
+                devices,
+            }
+        })
+    }
+}
+
+impl ServiceSpec {
+    fn new(service: &rupnp::Service, scpd: Result<SCPD, rupnp::Error>) -> Self {
+        let mut service_spec = Self {
+            service_type: service.service_type().to_string(),
+            service_id: service.service_id().to_string(),
+            actions: Vec::new(),
+            state_variables: Vec::new(),
+            error: None,
+        };
+        match scpd {
+            Ok(scpd) => {
+                service_spec.actions = scpd
+                    .actions()
+                    .iter()
+                    .map(|action| ActionSpec {
+                        name: action.name().to_string(),
+                        input_arguments: action.input_arguments().map(ArgumentSpec::new).collect(),
+                        output_arguments: action
+                            .output_arguments()
+                            .map(ArgumentSpec::new)
+                            .collect(),
+                    })
+                    .collect();
+                service_spec.state_variables = scpd
+                    .state_variables()
+                    .iter()
+                    .map(|state_variable| StateVariableSpec::new(state_variable))
+                    .collect();
+            }
+            Err(err) => service_spec.error = Some(err.to_string()),
+        }
+        service_spec
+    }
+}
+
+impl ArgumentSpec {
+    fn new(argument: &Argument) -> Self {
+        Self {
+            name: argument.name().to_string(),
+            state_variable: argument.related_state_variable().name().to_string(),
+        }
+    }
+}
+
+impl StateVariableSpec {
+    fn new(state_variable: &StateVariable) -> Self {
+        let (data_type, allowed_values, allowed_range) = match state_variable.kind() {
+            StateVariableKind::Simple(data_type) => (Some(data_type.to_string()), Vec::new(), None),
+            StateVariableKind::Enum(values) => (None, values.clone(), None),
+            StateVariableKind::Range(range) => (None, Vec::new(), Some(range.to_string())),
+        };
+        Self {
+            name: state_variable.name().to_string(),
+            data_type,
+            allowed_values,
+            allowed_range,
+            default: state_variable.default().map(str::to_string),
+            sends_events: state_variable.sends_events(),
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use crate::{
+    control::{RelTime, RenderController},
+    error::{Error, Result},
+    status::TransportState,
+};
+use crossterm::{
+    cursor::MoveToColumn,
+    event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    execute,
+    terminal::{self, Clear, ClearType},
+};
+use futures::StreamExt;
+use std::{
+    io::{stdout, Write},
+    time::Duration,
+};
+
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+const SEEK_STEP_SHORT_SECS: i64 = 10;
+const SEEK_STEP_LONG_SECS: i64 = 60;
+const VOLUME_STEP: i32 = 5;
+
+/// Restores the terminal from raw mode when dropped
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn enable() -> Result<Self> {
+        terminal::enable_raw_mode().map_err(Error::InteractiveTerminalError)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        terminal::disable_raw_mode().ok();
+        println!();
+    }
+}
+
+/// The last known state of the playback
+#[derive(Default)]
+struct PlaybackState {
+    transport_state: Option<TransportState>,
+    position: Option<RelTime>,
+    duration: Option<RelTime>,
+    volume: Option<u16>,
+    muted: bool,
+    error: Option<String>,
+}
+
+impl PlaybackState {
+    async fn refresh(&mut self, controller: &RenderController) {
+        let render = controller.render();
+        if let Ok(transport_info) = render.transport_info().await {
+            self.transport_state = Some(transport_info.state);
+        }
+        if let Ok(position_info) = render.position_info().await {
+            self.position = position_info.rel_time;
+            self.duration = position_info.track_duration;
+        }
+        if render.rendering_control.is_some() {
+            self.volume = controller.volume().await.ok();
+            self.muted = controller.is_muted().await.unwrap_or_default();
+        }
+    }
+
+    fn status_line(&self) -> String {
+        let format_time = |time: &Option<RelTime>| match time {
+            Some(time) => time.to_string(),
+            None => "-:--:--".to_string(),
+        };
+        let state = match &self.transport_state {
+            Some(TransportState::Playing) => "Playing".to_string(),
+            Some(TransportState::PausedPlayback) => "Paused".to_string(),
+            Some(TransportState::Stopped) => "Stopped".to_string(),
+            Some(TransportState::Transitioning) => "Loading".to_string(),
+            Some(state) => state.to_string(),
+            None => "Unknown".to_string(),
+        };
+        let volume = match (self.volume, self.muted) {
+            (_, true) => "muted".to_string(),
+            (Some(volume), false) => volume.to_string(),
+            (None, false) => "-".to_string(),
+        };
+        let help = match &self.error {
+            Some(error) => error.to_owned(),
+            None => "space: pause, arrows: seek, +/-: volume, m: mute, q: quit".to_string(),
+        };
+        format!(
+            "[{}] {} / {} | volume: {} | {}",
+            state,
+            format_time(&self.position),
+            format_time(&self.duration),
+            volume,
+            help
+        )
+    }
+}
+
+/// Controls the playback through the keyboard, showing a status bar, until the user quits
+pub(crate) async fn run(controller: RenderController) -> Result<()> {
+    let _raw_mode = RawModeGuard::enable()?;
+    let mut keys = EventStream::new();
+    let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+    let mut state = PlaybackState::default();
+
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                state.refresh(&controller).await;
+                print_status(&state.status_line())?;
+            }
+            key = keys.next() => match key {
+                Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                    if is_quit(&key) {
+                        controller.stop().await?;
+                        return Ok(());
+                    }
+                    state.error = handle_key(&controller, &state, key.code)
+                        .await
+                        .err()
+                        .map(|err| err.to_string());
+                    state.refresh(&controller).await;
+                    print_status(&state.status_line())?;
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => return Err(Error::InteractiveTerminalError(err)),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+fn is_quit(key: &KeyEvent) -> bool {
+    match key.code {
+        KeyCode::Char('q') | KeyCode::Esc => true,
+        KeyCode::Char('c') => key.modifiers.contains(KeyModifiers::CONTROL),
+        _ => false,
+    }
+}
+
+async fn handle_key(
+    controller: &RenderController,
+    state: &PlaybackState,
+    key: KeyCode,
+) -> Result<()> {
+    match key {
+        KeyCode::Char(' ') => match state.transport_state {
+            Some(TransportState::Playing) => controller.pause().await,
+            _ => controller.resume().await,
+        },
+        KeyCode::Left => seek_relative(controller, state, -SEEK_STEP_SHORT_SECS).await,
+        KeyCode::Right => seek_relative(controller, state, SEEK_STEP_SHORT_SECS).await,
+        KeyCode::Down => seek_relative(controller, state, -SEEK_STEP_LONG_SECS).await,
+        KeyCode::Up => seek_relative(controller, state, SEEK_STEP_LONG_SECS).await,
+        KeyCode::Char('+') | KeyCode::Char('=') => {
+            controller.change_volume(VOLUME_STEP).await.map(|_| ())
+        }
+        KeyCode::Char('-') => controller.change_volume(-VOLUME_STEP).await.map(|_| ()),
+        KeyCode::Char('m') => controller.set_mute(!state.muted).await,
+        _ => Ok(()),
+    }
+}
+
+async fn seek_relative(
+    controller: &RenderController,
+    state: &PlaybackState,
+    step_secs: i64,
+) -> Result<()> {
+    let position = state.position.unwrap_or_default().as_secs() as i64;
+    let mut target = (position + step_secs).max(0) as u64;
+    if let Some(duration) = state.duration.filter(|duration| duration.as_secs() > 0) {
+        target = target.min(duration.as_secs());
+    }
+    controller.seek(RelTime::from_secs(target)).await
+}
+
+fn print_status(status_line: &str) -> Result<()> {
+    let mut stdout = stdout();
+    execute!(stdout, MoveToColumn(0), Clear(ClearType::CurrentLine))
+        .map_err(Error::InteractiveTerminalError)?;
+    let width = terminal::size()
+        .ok()
+        .map(|(width, _)| width as usize)
+        .filter(|width| *width > 0)
+        .unwrap_or(80);
+    let status_line: String = status_line.chars().take(width.saturating_sub(1)).collect();
+    write!(stdout, "{}", status_line).map_err(Error::InteractiveTerminalError)?;
+    stdout.flush().map_err(Error::InteractiveTerminalError)
+}
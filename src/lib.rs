@@ -8,9 +8,26 @@ It allows you to play a local video file in your TV (or any other DLNA compatibl
 This crate provides both a library and a command line tool.
 
 # Features
- - Searching available DLNA devices in the local network
- - Streaming audio
+ - Searching available DLNA devices in the local network, and listing them with their services and actions (JSON, YAML, table or TSV)
+ - Inspecting the device tree of a device, with the actions, arguments and state variables of all its services
+ - Checking the formats a device declares it can play (ConnectionManager `GetProtocolInfo`) before playing
+ - Adapting to the quirks of Samsung, LG, Sony, Panasonic, Kodi, VLC, gmediarender and Xbox renders (subtitle extensions of the DIDL-Lite, headers, seek format), overridable from a quirks file
+ - Streaming audio, with its tags (title, artist, album, track number, duration and cover art)
  - Streaming video, with subtitle support
+ - Finding the subtitles of videos (`movie.en.srt`, `movie.pt-BR.forced.ass`, `Subs/` directories), by preferred language
+ - Extracting the text subtitle tracks embedded in MKV and MP4 files, when a video has no subtitle file
+ - Serving several subtitle tracks per video, tagged with their language, for the renders offering a subtitle menu
+ - Converting subtitles (WebVTT, ASS/SSA, MicroDVD, SAMI) to SRT, or to WebVTT or SAMI for the renders that need them
+ - Resyncing subtitles, with a delay and a frame rate correction
+ - Detecting the character encoding of subtitles and serving them in UTF-8 (or in another encoding)
+ - Detecting the type of the media files (MIME type and DLNA profile), and probing their duration, bitrate and resolution (MP4, Matroska, MPEG-TS)
+ - Transcoding the media the renders can not play with ffmpeg, on the fly (H.264/AAC in MPEG-TS or fragmented MP4, MP3, burned-in subtitles), deciding automatically from the formats they declare and the codecs of each file whether to stream, remux or transcode it
+ - Playing multiple files, directories and playlists (`.m3u`, `.m3u8`, `.pls`)
+ - Showing images (JPEG, PNG, GIF) and slideshows, with EXIF rotation and downscaling
+ - Controlling the playback (pause, resume, stop and seek)
+ - Controlling the volume (volume level and mute)
+ - Querying the playback status (transport state, position and media)
+ - Subscribing to the events of a device (transport state, volume, mute)
 
 # Example: discover and list devices
 
@@ -67,6 +84,48 @@ async fn main() -> Result<(), Error> {
 }
 ```
 
+# Example: react to the events of a render
+
+The render can notify changes in its state (such as the transport state or the volume)
+to the streaming server, which are provided as a stream of events.
+
+```rust,no_run
+use std::path::PathBuf;
+use futures::StreamExt;
+use crab_dlna::{
+    Render,
+    RenderSpec,
+    RenderEvents,
+    MediaStreamingServer,
+    STREAMING_PORT_DEFAULT,
+    get_local_ip,
+    Error,
+};
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let discover_timeout_secs = 5;
+    let render_spec = RenderSpec::Query(discover_timeout_secs, "Kodi".to_string());
+    let render = Render::new(render_spec).await?;
+    let host_ip = get_local_ip().await?;
+    let host_port = STREAMING_PORT_DEFAULT;
+    let video_path = PathBuf::from("/home/crab/Videos/my_video.mp4");
+    let mut media_streaming_server = MediaStreamingServer::new(
+        &video_path,
+        &None,
+        &host_ip,
+        &host_port,
+    )?;
+    let event_callback = media_streaming_server.event_callback();
+    tokio::spawn(async move { media_streaming_server.run().await });
+    let mut events = RenderEvents::subscribe(&render, event_callback).await?;
+    while let Some(event) = events.next().await {
+        println!("{:?}", event);
+    }
+    Ok(())
+}
+```
+
 # Technical Details
 
 crab-dlna is basically a one-file DLNA MediaServer and a self DLNA MediaController.
@@ -79,27 +138,105 @@ How does `list` work?
 How does `play` work?
 1. Setup an HTTP server to provide the media files to be streamed (including subtitles)
 2. Send a `SetAVTransportURI` message to the device, specifying the HTTP URLs of the media files
+   and their DIDL-Lite metadata (title, class, type and subtitles)
 3. Send a `Play` message to the device
+4. If there are more media files, queue the next one with a `SetNextAVTransportURI` message
+   (or send a new `SetAVTransportURI` once the current one ends, if the device does not support it)
+5. Poll the transport state of the device, stopping the HTTP server once it finishes playing
 */
 
 /// Discovery of render devices in the network
 mod devices;
 
+/// Inspection of the services, actions and state variables of devices
+mod inspect;
+
 /// Streaming of media files
 mod streaming;
 
+/// Parsing of the protocol info of the media renders can play
+mod protocol;
+
+/// Detection of the type of media files
+mod media;
+
+/// Reading of the tags of audio files
+mod tags;
+
+/// Probing of the properties of media containers
+mod probe;
+
+/// Conversion of subtitles
+mod subtitles;
+
+/// Extraction of the subtitle tracks embedded in media containers
+mod embedded;
+
+/// Transcoding of media through an external ffmpeg process
+mod transcode;
+
+/// Preparation of the images to be shown
+mod images;
+
 /// Handling of the DLNA protocol
 mod dlna;
 
+/// Building of the DIDL-Lite metadata of media items
+mod didl;
+
+/// Expansion of playlists and directories into media files
+mod playlist;
+
+/// Control of the media playback in a render
+mod control;
+
+/// Querying of the playback status of a render
+mod status;
+
+/// Subscription to the events of a render
+mod events;
+
+/// Vendor-specific behaviour of renders, matched from their device description
+mod quirks;
+
 /// Command line interface
 pub mod cli;
 
+/// Interactive control of the playback through the keyboard
+mod interactive;
+
 /// Definition of the errors
 mod error;
 
-pub use devices::{Render, RenderSpec};
-pub use dlna::play;
+/// Fixtures shared by the unit tests
+#[cfg(test)]
+mod test_utils;
+
+pub use control::{RelTime, RenderController, SeekFormat};
+pub use devices::{Render, RenderDescription, RenderSpec, ServiceDescription};
+pub use didl::{DidlItem, DidlLite, DidlResource, DidlSubtitle, SubtitleExtension, UpnpClass};
+pub use dlna::{play, play_with_options, show, PlaybackOptions, SlideshowOptions};
+pub use embedded::{
+    extract_subtitle_track, extract_subtitle_tracks, list_subtitle_tracks, rank_subtitle_tracks,
+    EmbeddedSubtitleTrack, SubtitleCodec, SubtitleTrackSelector,
+};
 pub use error::Error;
+pub use events::{EventCallback, RenderEvent, RenderEvents};
+pub use images::{prepare_images, ImageOptions, ImageSize, PreparedImages};
+pub use inspect::{ActionSpec, ArgumentSpec, DeviceTree, ServiceSpec, StateVariableSpec};
+pub use media::{MediaKind, MediaProfile};
+pub use playlist::{expand_image_paths, expand_media_paths, RepeatMode};
+pub use probe::MediaProbe;
+pub use protocol::ProtocolInfo;
+pub use quirks::{Quirks, QuirksDatabase};
+pub use status::{MediaInfo, PositionInfo, RenderStatus, TransportInfo, TransportState};
 pub use streaming::{
-    get_local_ip, infer_subtitle_from_video, MediaStreamingServer, STREAMING_PORT_DEFAULT,
+    get_local_ip, infer_subtitle_from_video, infer_subtitle_from_video_with_languages,
+    infer_subtitles_from_video, MediaFile, MediaItem, MediaStreamingServer, STREAMING_PORT_DEFAULT,
+};
+pub use subtitles::{
+    find_subtitle_candidates, read_subtitle_text, SubtitleCandidate, SubtitleCue, SubtitleEncoding,
+    SubtitleFormat, SubtitleOptions, Subtitles,
 };
+pub use tags::{AudioTags, CoverArt};
+pub use transcode::{TranscodeMode, TranscodeProfile, Transcoder, FFMPEG_DEFAULT};
@@ -0,0 +1,451 @@
+use log::{debug, warn};
+use std::{fmt, io::Read, path::Path};
+
+/// Number of bytes read from the beginning of a file to detect its type
+const SNIFF_LENGTH: usize = 1024;
+
+/// Size of the packets of MPEG transport streams, plain and with a timestamp (M2TS)
+const TS_PACKET_SIZE: usize = 188;
+const M2TS_PACKET_SIZE: usize = 192;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// DLNA flags of the `contentFeatures.dlna.org` field (DLNA guidelines, 7.4.1.3.24)
+const DLNA_FLAG_STREAMING_TRANSFER_MODE: u32 = 1 << 24;
+const DLNA_FLAG_INTERACTIVE_TRANSFER_MODE: u32 = 1 << 23;
+const DLNA_FLAG_BACKGROUND_TRANSFER_MODE: u32 = 1 << 22;
+const DLNA_FLAG_CONNECTION_STALL: u32 = 1 << 21;
+const DLNA_FLAG_DLNA_V15: u32 = 1 << 20;
+
+/// The kind of a media, which defines how it is presented and transferred to the render
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    /// A video, possibly with audio
+    Video,
+    /// An audio track
+    Audio,
+    /// A still image
+    Image,
+    /// A subtitle track
+    Subtitle,
+    /// A file whose type is not known, which is not played
+    Unknown,
+}
+
+impl MediaKind {
+    /// Whether the media is played over time (audio or video)
+    pub fn is_av(&self) -> bool {
+        matches!(self, Self::Video | Self::Audio)
+    }
+}
+
+impl fmt::Display for MediaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            Self::Video => "video",
+            Self::Audio => "audio",
+            Self::Image => "image",
+            Self::Subtitle => "subtitle",
+            Self::Unknown => "unknown",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// The type of a media file: its MIME type, its DLNA profile (when known) and its kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MediaProfile {
+    /// The MIME type of the media
+    pub mime: &'static str,
+    /// The DLNA profile name of the media (`DLNA.ORG_PN`), if it can be told from the container
+    pub dlna_profile: Option<&'static str>,
+    /// The kind of the media
+    pub kind: MediaKind,
+    /// Whether the media is transcoded on the fly, so that its size is unknown and it cannot be seeked
+    pub transcoded: bool,
+}
+
+impl MediaProfile {
+    const fn new(mime: &'static str, dlna_profile: Option<&'static str>, kind: MediaKind) -> Self {
+        Self {
+            mime,
+            dlna_profile,
+            kind,
+            transcoded: false,
+        }
+    }
+
+    /// Create the type of a media transcoded on the fly.
+    pub const fn transcoded(
+        mime: &'static str,
+        dlna_profile: Option<&'static str>,
+        kind: MediaKind,
+    ) -> Self {
+        Self {
+            mime,
+            dlna_profile,
+            kind,
+            transcoded: true,
+        }
+    }
+
+    /// Detects the type of a media file from its first bytes, falling back to its extension.
+    pub fn detect(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default();
+        let sniffed = match read_header(path) {
+            Ok(header) => Self::from_bytes(&header, extension),
+            Err(err) => {
+                warn!("Failed to read '{}': {}", path.display(), err);
+                None
+            }
+        };
+        let mut profile = sniffed.unwrap_or_else(|| Self::from_extension(extension));
+        if profile.kind == MediaKind::Image {
+            if let Ok((width, height)) = image::image_dimensions(path) {
+                profile.dlna_profile = image_dlna_profile(profile.mime, width, height);
+            }
+        }
+        debug!("Media type of '{}': {}", path.display(), profile);
+        profile
+    }
+
+    /// Detects the type of a media from the magic bytes at the beginning of its content.
+    ///
+    /// The extension is only used to disambiguate containers of different kinds of media.
+    pub fn from_bytes(header: &[u8], extension: &str) -> Option<Self> {
+        use MediaKind::*;
+
+        let extension = extension.to_lowercase();
+        let starts_with = |magic: &[u8]| header.starts_with(magic);
+        let at =
+            |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+
+        if at(4, b"ftyp") {
+            let brand = header.get(8..12).unwrap_or_default();
+            return Some(match brand {
+                b"M4A " | b"M4B " | b"M4P " => Self::new("audio/mp4", None, Audio),
+                b"qt  " => Self::new("video/quicktime", None, Video),
+                _ if brand.starts_with(b"3g") => Self::new("video/3gpp", None, Video),
+                _ if extension == "m4a" => Self::new("audio/mp4", None, Audio),
+                _ => Self::new("video/mp4", None, Video),
+            });
+        }
+        if starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+            let is_webm = header.windows(4).any(|window| window == b"webm");
+            return Some(match (is_webm, extension == "mka" || extension == "weba") {
+                (true, true) => Self::new("audio/webm", None, Audio),
+                (true, false) => Self::new("video/webm", None, Video),
+                (false, true) => Self::new("audio/x-matroska", None, Audio),
+                (false, false) => Self::new("video/x-matroska", None, Video),
+            });
+        }
+        if starts_with(b"RIFF") && at(8, b"AVI ") {
+            return Some(Self::new("video/x-msvideo", None, Video));
+        }
+        if starts_with(b"RIFF") && at(8, b"WAVE") {
+            return Some(Self::new("audio/wav", None, Audio));
+        }
+        if is_transport_stream(header, 0, TS_PACKET_SIZE) {
+            return Some(Self::new("video/mpeg", None, Video));
+        }
+        if is_transport_stream(header, 4, M2TS_PACKET_SIZE) {
+            return Some(Self::new("video/vnd.dlna.mpeg-tts", None, Video));
+        }
+        if starts_with(&[0x00, 0x00, 0x01, 0xba]) {
+            return Some(Self::new("video/mpeg", None, Video));
+        }
+        if starts_with(b"fLaC") {
+            return Some(Self::new("audio/flac", None, Audio));
+        }
+        if starts_with(b"OggS") {
+            return Some(match header.get(28..) {
+                Some(codec) if codec.starts_with(b"\x80theora") => {
+                    Self::new("video/ogg", None, Video)
+                }
+                _ => Self::new("audio/ogg", None, Audio),
+            });
+        }
+        if starts_with(b"ID3") || is_mpeg_audio_frame(header) {
+            return Some(Self::new("audio/mpeg", Some("MP3"), Audio));
+        }
+        if starts_with(&[0xff, 0xd8, 0xff]) {
+            return Some(Self::new("image/jpeg", Some("JPEG_LRG"), Image));
+        }
+        if starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(Self::new("image/png", Some("PNG_LRG"), Image));
+        }
+        None
+    }
+
+    /// Guesses the type of a media from its file extension.
+    pub fn from_extension(extension: &str) -> Self {
+        use MediaKind::*;
+
+        match extension.to_lowercase().as_str() {
+            "mp4" | "m4v" => Self::new("video/mp4", None, Video),
+            "mov" => Self::new("video/quicktime", None, Video),
+            "3gp" => Self::new("video/3gpp", None, Video),
+            "mkv" => Self::new("video/x-matroska", None, Video),
+            "webm" => Self::new("video/webm", None, Video),
+            "avi" => Self::new("video/x-msvideo", None, Video),
+            "ts" | "mpg" | "mpeg" => Self::new("video/mpeg", None, Video),
+            "m2ts" | "mts" => Self::new("video/vnd.dlna.mpeg-tts", None, Video),
+            "ogv" => Self::new("video/ogg", None, Video),
+            "wmv" => Self::new("video/x-ms-wmv", None, Video),
+            "flv" => Self::new("video/x-flv", None, Video),
+            "mp3" => Self::new("audio/mpeg", Some("MP3"), Audio),
+            "m4a" => Self::new("audio/mp4", None, Audio),
+            "aac" => Self::new("audio/aac", None, Audio),
+            "flac" => Self::new("audio/flac", None, Audio),
+            "ogg" | "oga" | "opus" => Self::new("audio/ogg", None, Audio),
+            "wav" => Self::new("audio/wav", None, Audio),
+            "wma" => Self::new("audio/x-ms-wma", None, Audio),
+            "mka" => Self::new("audio/x-matroska", None, Audio),
+            "jpg" | "jpeg" => Self::new("image/jpeg", Some("JPEG_LRG"), Image),
+            "png" => Self::new("image/png", Some("PNG_LRG"), Image),
+            "gif" => Self::new("image/gif", None, Image),
+            "srt" => Self::new("text/srt", None, Subtitle),
+            "vtt" => Self::new("text/vtt", None, Subtitle),
+            "ass" | "ssa" => Self::new("text/x-ssa", None, Subtitle),
+            "smi" | "sami" => Self::new("smi/caption", None, Subtitle),
+            "sub" => Self::new("text/plain", None, Subtitle),
+            _ => Self::new("application/octet-stream", None, Unknown),
+        }
+    }
+
+    /// Returns the fourth field of the protocol info of the media, as sent in the
+    /// `contentFeatures.dlna.org` header.
+    pub fn content_features(&self) -> String {
+        let flags = match self.kind.is_av() {
+            true => {
+                DLNA_FLAG_STREAMING_TRANSFER_MODE
+                    | DLNA_FLAG_BACKGROUND_TRANSFER_MODE
+                    | DLNA_FLAG_CONNECTION_STALL
+                    | DLNA_FLAG_DLNA_V15
+            }
+            false => {
+                DLNA_FLAG_INTERACTIVE_TRANSFER_MODE
+                    | DLNA_FLAG_BACKGROUND_TRANSFER_MODE
+                    | DLNA_FLAG_DLNA_V15
+            }
+        };
+        let profile = self
+            .dlna_profile
+            .map(|profile| format!("DLNA.ORG_PN={};", profile))
+            .unwrap_or_default();
+        // transcoded media can neither be seeked by byte range nor by time
+        let (operations, conversion) = match self.transcoded {
+            true => ("00", 1),
+            false => ("01", 0),
+        };
+        format!(
+            "{}DLNA.ORG_OP={};DLNA.ORG_CI={};DLNA.ORG_FLAGS={:08X}{}",
+            profile,
+            operations,
+            conversion,
+            flags,
+            "0".repeat(24)
+        )
+    }
+
+    /// Returns the protocol info of the media when served over HTTP, as used in DIDL-Lite.
+    pub fn protocol_info(&self) -> String {
+        format!("http-get:*:{}:{}", self.mime, self.content_features())
+    }
+}
+
+impl fmt::Display for MediaProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dlna_profile {
+            Some(dlna_profile) => write!(f, "{} ({}, {})", self.mime, dlna_profile, self.kind),
+            None => write!(f, "{} ({})", self.mime, self.kind),
+        }
+    }
+}
+
+/// Returns the DLNA profile of an image, which depends on its resolution
+fn image_dlna_profile(mime: &str, width: u32, height: u32) -> Option<&'static str> {
+    let fits = |max_width, max_height| width <= max_width && height <= max_height;
+    match mime {
+        "image/jpeg" if fits(640, 480) => Some("JPEG_SM"),
+        "image/jpeg" if fits(1024, 768) => Some("JPEG_MED"),
+        "image/jpeg" if fits(4096, 4096) => Some("JPEG_LRG"),
+        "image/png" if fits(4096, 4096) => Some("PNG_LRG"),
+        "image/gif" if fits(1600, 1200) => Some("GIF_LRG"),
+        _ => None,
+    }
+}
+
+fn read_header(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(SNIFF_LENGTH);
+    std::fs::File::open(path)?
+        .take(SNIFF_LENGTH as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Whether the header holds at least two packets of a transport stream
+fn is_transport_stream(header: &[u8], offset: usize, packet_size: usize) -> bool {
+    let packets = (offset..header.len())
+        .step_by(packet_size)
+        .take(3)
+        .map(|position| header[position])
+        .collect::<Vec<_>>();
+    packets.len() >= 2 && packets.iter().all(|byte| *byte == TS_SYNC_BYTE)
+}
+
+/// Whether the header starts with the frame sync of MPEG audio layer III
+fn is_mpeg_audio_frame(header: &[u8]) -> bool {
+    matches!(header, [0xff, second, ..] if second & 0xe6 == 0xe2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_temp_file;
+
+    fn sniff(header: &[u8], extension: &str) -> Option<(&'static str, MediaKind)> {
+        MediaProfile::from_bytes(header, extension).map(|profile| (profile.mime, profile.kind))
+    }
+
+    fn ts_packets(offset: usize, packet_size: usize) -> Vec<u8> {
+        let mut data = vec![0; offset + packet_size * 2 + 1];
+        data[offset] = TS_SYNC_BYTE;
+        data[offset + packet_size] = TS_SYNC_BYTE;
+        data[offset + packet_size * 2] = TS_SYNC_BYTE;
+        data
+    }
+
+    #[test]
+    fn sniff_containers() {
+        use MediaKind::*;
+
+        assert_eq!(
+            sniff(b"\0\0\0\x18ftypisom", "mp4"),
+            Some(("video/mp4", Video))
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x18ftypM4A ", "mp4"),
+            Some(("audio/mp4", Audio))
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x18ftypmp42", "m4a"),
+            Some(("audio/mp4", Audio))
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x14ftypqt  ", "mov"),
+            Some(("video/quicktime", Video))
+        );
+        assert_eq!(
+            sniff(b"\0\0\0\x14ftyp3gp5", ""),
+            Some(("video/3gpp", Video))
+        );
+        let matroska = b"\x1a\x45\xdf\xa3\x42\x82\x88matroska";
+        let webm = b"\x1a\x45\xdf\xa3\x42\x82\x84webm";
+        assert_eq!(sniff(matroska, "mkv"), Some(("video/x-matroska", Video)));
+        assert_eq!(sniff(matroska, "MKA"), Some(("audio/x-matroska", Audio)));
+        assert_eq!(sniff(webm, "mkv"), Some(("video/webm", Video)));
+        assert_eq!(sniff(webm, "weba"), Some(("audio/webm", Audio)));
+        assert_eq!(
+            sniff(b"RIFF\0\0\0\0AVI LIST", "avi"),
+            Some(("video/x-msvideo", Video))
+        );
+        assert_eq!(
+            sniff(b"RIFF\0\0\0\0WAVEfmt ", ""),
+            Some(("audio/wav", Audio))
+        );
+        assert_eq!(
+            sniff(&ts_packets(0, TS_PACKET_SIZE), ""),
+            Some(("video/mpeg", Video))
+        );
+        assert_eq!(
+            sniff(&ts_packets(4, M2TS_PACKET_SIZE), ""),
+            Some(("video/vnd.dlna.mpeg-tts", Video))
+        );
+        assert_eq!(
+            sniff(&[0, 0, 1, 0xba, 0x44], "mpg"),
+            Some(("video/mpeg", Video))
+        );
+    }
+
+    #[test]
+    fn sniff_audio_and_images() {
+        use MediaKind::*;
+
+        assert_eq!(sniff(b"fLaC\0\0\0\x22", ""), Some(("audio/flac", Audio)));
+        let mut ogg = b"OggS".to_vec();
+        ogg.resize(28, 0);
+        assert_eq!(sniff(&ogg, "ogg"), Some(("audio/ogg", Audio)));
+        ogg.extend_from_slice(b"\x80theora");
+        assert_eq!(sniff(&ogg, "ogg"), Some(("video/ogg", Video)));
+        assert_eq!(sniff(b"ID3\x04\0", ""), Some(("audio/mpeg", Audio)));
+        assert_eq!(
+            sniff(&[0xff, 0xfb, 0x90, 0x64], ""),
+            Some(("audio/mpeg", Audio))
+        );
+        assert_eq!(
+            sniff(&[0xff, 0xd8, 0xff, 0xe0], ""),
+            Some(("image/jpeg", Image))
+        );
+        assert_eq!(
+            sniff(b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR", ""),
+            Some(("image/png", Image))
+        );
+    }
+
+    #[test]
+    fn sniff_unknown_or_truncated_headers() {
+        for header in [
+            b"".as_slice(),
+            b"\0\0\0",
+            b"\0\0\0\x18fty",
+            b"RIFF\0\0\0\0",
+            &[0xff, 0xd8],
+            &[0xff, 0xf0, 0x00],
+            &[TS_SYNC_BYTE, 0, 0],
+            b"<html><body>",
+        ] {
+            assert_eq!(sniff(header, "mp4"), None, "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn profile_from_extension() {
+        let profile = MediaProfile::from_extension("MP3");
+        assert_eq!(
+            (profile.mime, profile.dlna_profile, profile.kind),
+            ("audio/mpeg", Some("MP3"), MediaKind::Audio)
+        );
+        assert_eq!(MediaProfile::from_extension("smi").mime, "smi/caption");
+        for extension in ["", "xyz", "exe"] {
+            assert_eq!(
+                MediaProfile::from_extension(extension).kind,
+                MediaKind::Unknown
+            );
+        }
+    }
+
+    #[test]
+    fn detect_files() {
+        // the content wins over the extension, which is only a fallback
+        let detect = |name, data: &[u8]| with_temp_file(name, data, MediaProfile::detect);
+        assert_eq!(detect("audio.mp4", b"fLaC\0\0\0\x22").mime, "audio/flac");
+        assert_eq!(detect("video.mkv", b"unknown").mime, "video/x-matroska");
+        assert_eq!(detect("file.xyz", b"unknown").kind, MediaKind::Unknown);
+    }
+
+    #[test]
+    fn image_profiles() {
+        assert_eq!(image_dlna_profile("image/jpeg", 640, 480), Some("JPEG_SM"));
+        assert_eq!(image_dlna_profile("image/jpeg", 641, 480), Some("JPEG_MED"));
+        assert_eq!(
+            image_dlna_profile("image/jpeg", 4096, 4096),
+            Some("JPEG_LRG")
+        );
+        assert_eq!(image_dlna_profile("image/jpeg", 4097, 1), None);
+        assert_eq!(image_dlna_profile("image/png", 100, 100), Some("PNG_LRG"));
+        assert_eq!(image_dlna_profile("image/gif", 1601, 100), None);
+    }
+}
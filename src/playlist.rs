@@ -0,0 +1,286 @@
+use crate::error::{Error, Result};
+use log::{debug, warn};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Extensions of the media files picked up when expanding a directory
+const MEDIA_EXTENSIONS: &[&str] = &[
+    "3gp", "aac", "avi", "flac", "flv", "m2ts", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "mpeg",
+    "mpg", "mts", "oga", "ogg", "ogv", "opus", "ts", "wav", "webm", "wma", "wmv",
+];
+
+/// Extensions of the image files picked up when expanding a directory
+const IMAGE_EXTENSIONS: &[&str] = &["gif", "jpeg", "jpg", "png"];
+
+/// How the media items of a playlist are repeated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RepeatMode {
+    /// Play each media item once
+    #[default]
+    Off,
+    /// Repeat the current media item forever
+    One,
+    /// Repeat the whole playlist forever
+    All,
+}
+
+impl RepeatMode {
+    /// Returns the index of the media item to play after `current`, in a playlist of `len` items
+    pub fn next_index(&self, current: usize, len: usize) -> Option<usize> {
+        match self {
+            Self::Off => Some(current + 1).filter(|next| *next < len),
+            Self::One => Some(current).filter(|_| len > 0),
+            Self::All => (current + 1).checked_rem(len),
+        }
+    }
+}
+
+impl FromStr for RepeatMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Ok(Self::Off),
+            "one" => Ok(Self::One),
+            "all" => Ok(Self::All),
+            _ => Err(Error::PlaylistRepeatModeParseError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for RepeatMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let repeat_mode = match self {
+            Self::Off => "off",
+            Self::One => "one",
+            Self::All => "all",
+        };
+        write!(f, "{}", repeat_mode)
+    }
+}
+
+/// Expands a list of paths into the media files to be played.
+///
+/// Directories are expanded into the media files they contain (recursively, sorted by path),
+/// and `.m3u`/`.m3u8`/`.pls` playlists into the files they reference.
+pub fn expand_media_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    expand_paths(paths, MEDIA_EXTENSIONS)
+}
+
+/// Expands a list of paths into the image files to be shown.
+///
+/// Directories are expanded into the images they contain (recursively, sorted by path),
+/// and `.m3u`/`.m3u8`/`.pls` playlists into the files they reference.
+pub fn expand_image_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    expand_paths(paths, IMAGE_EXTENSIONS)
+}
+
+fn expand_paths(paths: &[PathBuf], extensions: &[&str]) -> Result<Vec<PathBuf>> {
+    let mut media_paths = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            expand_directory(path, extensions, &mut media_paths)?;
+        } else if has_extension(path, &["m3u", "m3u8"]) {
+            media_paths.extend(parse_m3u(path)?);
+        } else if has_extension(path, &["pls"]) {
+            media_paths.extend(parse_pls(path)?);
+        } else {
+            media_paths.push(path.to_owned());
+        }
+    }
+    debug!("Expanded media files: {:?}", media_paths);
+    Ok(media_paths)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.contains(&extension.to_lowercase().as_str()))
+}
+
+fn expand_directory(
+    directory: &Path,
+    extensions: &[&str],
+    media_paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    let read_error = |err| Error::PlaylistReadError(directory.display().to_string(), err);
+    let mut entries = std::fs::read_dir(directory)
+        .map_err(read_error)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .map_err(read_error)?;
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() {
+            expand_directory(&entry, extensions, media_paths)?;
+        } else if has_extension(&entry, extensions) {
+            media_paths.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn read_playlist(playlist: &Path) -> Result<String> {
+    let content = std::fs::read(playlist)
+        .map_err(|err| Error::PlaylistReadError(playlist.display().to_string(), err))?;
+    Ok(String::from_utf8_lossy(&content).to_string())
+}
+
+/// Resolves an entry of a playlist, relative to the directory of the playlist
+fn resolve_entry(playlist: &Path, entry: &str) -> Option<PathBuf> {
+    let entry = entry.trim();
+    if entry.contains("://") && !entry.starts_with("file://") {
+        warn!(
+            "Skipping remote entry '{}' of playlist '{}'",
+            entry,
+            playlist.display()
+        );
+        return None;
+    }
+    let entry = PathBuf::from(entry.trim_start_matches("file://"));
+    Some(match entry.is_absolute() {
+        true => entry,
+        false => playlist
+            .parent()
+            .map(|parent| parent.join(&entry))
+            .unwrap_or(entry),
+    })
+}
+
+fn parse_m3u(playlist: &Path) -> Result<Vec<PathBuf>> {
+    debug!("Parsing M3U playlist: {}", playlist.display());
+    Ok(read_playlist(playlist)?
+        .lines()
+        .map(|line| line.trim_start_matches('\u{feff}').trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| resolve_entry(playlist, line))
+        .collect())
+}
+
+fn parse_pls(playlist: &Path) -> Result<Vec<PathBuf>> {
+    debug!("Parsing PLS playlist: {}", playlist.display());
+    let content = read_playlist(playlist)?;
+    let mut entries: Vec<(u32, PathBuf)> = content
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter_map(|(key, value)| {
+            let number = key.trim().strip_prefix("File")?.parse().ok()?;
+            Some((number, resolve_entry(playlist, value)?))
+        })
+        .collect();
+    entries.sort_by_key(|(number, _)| *number);
+    Ok(entries.into_iter().map(|(_, entry)| entry).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{with_temp_dir, with_temp_file};
+
+    #[test]
+    fn parse_repeat_modes() {
+        assert_eq!("off".parse::<RepeatMode>().unwrap(), RepeatMode::Off);
+        assert_eq!("None".parse::<RepeatMode>().unwrap(), RepeatMode::Off);
+        assert_eq!("ONE".parse::<RepeatMode>().unwrap(), RepeatMode::One);
+        assert_eq!("all".parse::<RepeatMode>().unwrap(), RepeatMode::All);
+        for invalid in ["", "once", "all "] {
+            assert!(matches!(
+                invalid.parse::<RepeatMode>(),
+                Err(Error::PlaylistRepeatModeParseError(_))
+            ));
+        }
+        for repeat_mode in [RepeatMode::Off, RepeatMode::One, RepeatMode::All] {
+            assert_eq!(
+                repeat_mode.to_string().parse::<RepeatMode>().unwrap(),
+                repeat_mode
+            );
+        }
+    }
+
+    #[test]
+    fn repeat_mode_next_index() {
+        assert_eq!(RepeatMode::Off.next_index(0, 2), Some(1));
+        assert_eq!(RepeatMode::Off.next_index(1, 2), None);
+        assert_eq!(RepeatMode::One.next_index(1, 2), Some(1));
+        assert_eq!(RepeatMode::All.next_index(0, 2), Some(1));
+        assert_eq!(RepeatMode::All.next_index(1, 2), Some(0));
+        // an empty playlist has nothing to play next
+        for repeat_mode in [RepeatMode::Off, RepeatMode::One, RepeatMode::All] {
+            assert_eq!(repeat_mode.next_index(0, 0), None);
+        }
+    }
+
+    #[test]
+    fn parse_m3u_playlist() {
+        let content = "\u{feff}#EXTM3U\r\n#EXTINF:10,Title\r\nmovie.mkv\r\n\r\n  /music/song.mp3  \r\nfile:///music/other.mp3\r\nhttp://host/stream.mp3\r\n";
+        with_temp_file("playlist.m3u", content.as_bytes(), |path| {
+            let directory = path.parent().unwrap();
+            assert_eq!(
+                parse_m3u(path).unwrap(),
+                [
+                    directory.join("movie.mkv"),
+                    PathBuf::from("/music/song.mp3"),
+                    PathBuf::from("/music/other.mp3"),
+                ]
+            );
+        });
+        with_temp_file("empty.m3u", b"#EXTM3U\n", |path| {
+            assert!(parse_m3u(path).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn parse_pls_playlist() {
+        let content = "[playlist]\nFile2=/music/second.mp3\nTitle2=Second\nFile1 = first.mp3\nFileX=/music/invalid.mp3\nFile3=http://host/stream.mp3\nNumberOfEntries=3\nVersion=2\n";
+        with_temp_file("playlist.pls", content.as_bytes(), |path| {
+            assert_eq!(
+                parse_pls(path).unwrap(),
+                [
+                    path.parent().unwrap().join("first.mp3"),
+                    PathBuf::from("/music/second.mp3"),
+                ]
+            );
+        });
+        with_temp_file("empty.pls", b"", |path| {
+            assert!(parse_pls(path).unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn parse_missing_playlist() {
+        let missing = Path::new("/nonexistent/playlist.m3u");
+        assert!(matches!(
+            parse_m3u(missing),
+            Err(Error::PlaylistReadError(..))
+        ));
+        assert!(matches!(
+            parse_pls(missing),
+            Err(Error::PlaylistReadError(..))
+        ));
+    }
+
+    #[test]
+    fn expand_directories() {
+        let files: &[(&str, &[u8])] = &[
+            ("b.mp4", b""),
+            ("a.MKV", b""),
+            ("cover.jpg", b""),
+            ("notes.txt", b""),
+        ];
+        with_temp_dir(files, |directory| {
+            let paths = [directory.to_owned()];
+            assert_eq!(
+                expand_media_paths(&paths).unwrap(),
+                [directory.join("a.MKV"), directory.join("b.mp4")]
+            );
+            assert_eq!(
+                expand_image_paths(&paths).unwrap(),
+                [directory.join("cover.jpg")]
+            );
+        });
+    }
+}
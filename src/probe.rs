@@ -0,0 +1,614 @@
+use crate::{
+    control::RelTime,
+    error::{Error, Result},
+    tags::{
+        duration_from_samples, mp4_boxes, mp4_child, mp4_movie_duration, read_mp4_moov, ByteReader,
+    },
+};
+use log::debug;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+/// Length of the beginning of a Matroska file read to find its Info and Tracks elements
+const MATROSKA_HEADER_LENGTH: u64 = 1024 * 1024;
+
+/// Length of the beginning and the end of a transport stream read to find its first and last PCRs
+const TS_SCAN_LENGTH: u64 = 1024 * 1024;
+
+/// Frequency of the program clock reference of MPEG transport streams
+const TS_PCR_FREQUENCY: u64 = 27_000_000;
+const TS_SYNC_BYTE: u8 = 0x47;
+
+/// Matroska element IDs
+pub(crate) const MKV_SEGMENT: u32 = 0x1853_8067;
+pub(crate) const MKV_INFO: u32 = 0x1549_a966;
+pub(crate) const MKV_TIMECODE_SCALE: u32 = 0x2a_d7b1;
+const MKV_DURATION: u32 = 0x4489;
+pub(crate) const MKV_TRACKS: u32 = 0x1654_ae6b;
+pub(crate) const MKV_TRACK_ENTRY: u32 = 0xae;
+const MKV_VIDEO: u32 = 0xe0;
+const MKV_PIXEL_WIDTH: u32 = 0xb0;
+const MKV_PIXEL_HEIGHT: u32 = 0xba;
+const MKV_AUDIO: u32 = 0xe1;
+const MKV_SAMPLING_FREQUENCY: u32 = 0xb5;
+const MKV_CHANNELS: u32 = 0x9f;
+pub(crate) const MKV_TRACK_TYPE: u32 = 0x83;
+pub(crate) const MKV_CODEC_ID: u32 = 0x86;
+const MKV_TRACK_TYPE_VIDEO: u64 = 1;
+const MKV_TRACK_TYPE_AUDIO: u64 = 2;
+pub(crate) const MKV_CLUSTER: u32 = 0x1f43_b675;
+pub(crate) const MKV_TIMECODE_SCALE_DEFAULT: u64 = 1_000_000;
+
+/// The properties of a media file, probed from the headers of its container (MP4, Matroska or MPEG-TS)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaProbe {
+    /// The duration of the media
+    pub duration: Option<RelTime>,
+    /// The size of the file, in bytes
+    pub size: u64,
+    /// The average bitrate of the media, in bytes per second (as used by UPnP)
+    pub bitrate: Option<u32>,
+    /// The resolution of the (first) video track, as width and height
+    pub resolution: Option<(u32, u32)>,
+    /// The number of channels of the (first) audio track
+    pub audio_channels: Option<u32>,
+    /// The sample rate of the (first) audio track, in Hz
+    pub sample_rate: Option<u32>,
+    /// The codec of the (first) video track (e.g. `h264`, `hevc`), not read from transport streams
+    pub video_codec: Option<String>,
+    /// The codec of the (first) audio track (e.g. `aac`, `ac3`), not read from transport streams
+    pub audio_codec: Option<String>,
+}
+
+impl MediaProbe {
+    /// Probes a media file, according to the magic bytes of its container.
+    ///
+    /// Properties that can not be read from the headers are left empty.
+    pub fn probe(path: &Path) -> Result<Self> {
+        let probe_error = |err| Error::ProbeReadError(path.display().to_string(), err);
+        let mut file = File::open(path).map_err(probe_error)?;
+        let size = file.metadata().map_err(probe_error)?.len();
+        let mut magic = [0u8; 8];
+        let magic_length = file.read(&mut magic).map_err(probe_error)?;
+        file.rewind().map_err(probe_error)?;
+
+        let mut probe = match &magic[..magic_length] {
+            [_, _, _, _, b'f', b't', b'y', b'p'] => probe_mp4(&mut file),
+            [0x1a, 0x45, 0xdf, 0xa3, ..] => probe_matroska(&mut file),
+            [TS_SYNC_BYTE, ..] => probe_transport_stream(&mut file, size, 0),
+            [_, _, _, _, TS_SYNC_BYTE, ..] => probe_transport_stream(&mut file, size, 4),
+            _ => Ok(Self::default()),
+        }
+        .map_err(probe_error)?;
+
+        probe.size = size;
+        probe.bitrate = probe
+            .duration
+            .map(|duration| duration.as_duration().as_secs_f64())
+            .filter(|secs| *secs > 0.0)
+            .map(|secs| (size as f64 / secs) as u32);
+        debug!("Probe of '{}': {:?}", path.display(), probe);
+        Ok(probe)
+    }
+}
+
+// MP4
+
+fn probe_mp4(file: &mut File) -> std::io::Result<MediaProbe> {
+    let mut probe = MediaProbe::default();
+    let moov = match read_mp4_moov(file)? {
+        Some(moov) => moov,
+        None => return Ok(probe),
+    };
+    probe.duration = mp4_movie_duration(&moov);
+
+    for (_, trak) in mp4_boxes(&moov).filter(|(box_type, _)| *box_type == b"trak") {
+        let mdia = match mp4_child(trak, b"mdia") {
+            Some(mdia) => mdia,
+            None => continue,
+        };
+        let handler = mp4_child(mdia, b"hdlr").and_then(|hdlr| hdlr.get(8..12));
+        let stsd = mp4_child(mdia, b"minf")
+            .and_then(|minf| mp4_child(minf, b"stbl"))
+            .and_then(|stbl| mp4_child(stbl, b"stsd"));
+        // skip the version, flags and entry count, then the size of the first entry
+        let sample_entry_type = stsd.and_then(|stsd| stsd.get(12..16));
+        let sample_entry = stsd.and_then(|stsd| stsd.get(16..));
+
+        match handler {
+            Some(b"vide") if probe.video_codec.is_none() => {
+                probe.resolution = mp4_child(trak, b"tkhd")
+                    .and_then(mp4_track_resolution)
+                    .or_else(|| sample_entry.and_then(mp4_visual_resolution));
+                probe.video_codec = sample_entry_type.map(mp4_codec);
+            }
+            Some(b"soun") if probe.audio_codec.is_none() => {
+                if let Some((channels, sample_rate)) = sample_entry.and_then(mp4_audio_format) {
+                    probe.audio_channels = Some(channels);
+                    probe.sample_rate = Some(sample_rate);
+                }
+                probe.audio_codec = sample_entry_type.map(mp4_codec);
+            }
+            _ => {}
+        }
+    }
+    Ok(probe)
+}
+
+/// Reads the presentation width and height (16.16 fixed point) of a `tkhd` box
+fn mp4_track_resolution(tkhd: &[u8]) -> Option<(u32, u32)> {
+    let mut reader = ByteReader::new(tkhd);
+    // times, track ID and duration; then reserved, layer, group, volume, reserved and matrix
+    let header_length = match reader.u8()? {
+        1 => 3 + 8 + 8 + 4 + 4 + 8,
+        _ => 3 + 4 + 4 + 4 + 4 + 4,
+    };
+    reader.skip(header_length + 8 + 2 + 2 + 2 + 2 + 36)?;
+    let width = reader.u32_be()? >> 16;
+    let height = reader.u32_be()? >> 16;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Reads the width and height of a visual sample entry
+fn mp4_visual_resolution(sample_entry: &[u8]) -> Option<(u32, u32)> {
+    let mut reader = ByteReader::new(sample_entry);
+    // reserved, data reference index, pre-defined and reserved fields
+    reader.skip(6 + 2 + 2 + 2 + 12)?;
+    let width = reader.u16_be()? as u32;
+    let height = reader.u16_be()? as u32;
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+/// Reads the channel count and sample rate (16.16 fixed point) of an audio sample entry
+fn mp4_audio_format(sample_entry: &[u8]) -> Option<(u32, u32)> {
+    let mut reader = ByteReader::new(sample_entry);
+    // reserved, data reference index, version, revision and vendor
+    reader.skip(6 + 2 + 2 + 2 + 4)?;
+    let channels = reader.u16_be()? as u32;
+    // sample size, compression ID and packet size
+    reader.skip(2 + 2 + 2)?;
+    let sample_rate = reader.u32_be()? >> 16;
+    Some((channels, sample_rate))
+}
+
+/// Returns the name of the codec of an MP4 sample entry type
+fn mp4_codec(sample_entry_type: &[u8]) -> String {
+    let codec = match sample_entry_type {
+        b"avc1" | b"avc3" => "h264",
+        b"hvc1" | b"hev1" => "hevc",
+        b"vp09" => "vp9",
+        b"av01" => "av1",
+        b"mp4v" => "mpeg4",
+        b"mp4a" => "aac",
+        b".mp3" => "mp3",
+        b"ac-3" => "ac3",
+        b"ec-3" => "eac3",
+        b"Opus" => "opus",
+        b"fLaC" => "flac",
+        b"alac" => "alac",
+        _ => {
+            return String::from_utf8_lossy(sample_entry_type)
+                .trim()
+                .to_lowercase()
+        }
+    };
+    codec.to_string()
+}
+
+// Matroska
+
+/// Reads a variable length integer of EBML, returning it with its length; IDs keep their marker bit
+pub(crate) fn ebml_vint(reader: &mut ByteReader, keep_marker: bool) -> Option<(u64, usize)> {
+    let first = reader.u8()?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 {
+        return None;
+    }
+    let mut value = match keep_marker {
+        true => first as u64,
+        false => (first as u64) & (0xff >> length),
+    };
+    for byte in reader.bytes(length - 1)? {
+        value = (value << 8) | *byte as u64;
+    }
+    Some((value, length))
+}
+
+/// Iterates over the EBML elements contained in a slice of bytes, as their ID and content.
+///
+/// An element of unknown size spans the rest of the slice.
+pub(crate) fn ebml_elements(data: &[u8]) -> impl Iterator<Item = (u32, &[u8])> {
+    let mut reader = ByteReader::new(data);
+    std::iter::from_fn(move || {
+        let (id, _) = ebml_vint(&mut reader, true)?;
+        let (size, size_length) = ebml_vint(&mut reader, false)?;
+        let unknown_size = size == (1 << (7 * size_length)) - 1;
+        let content = match unknown_size {
+            true => reader.rest(),
+            false => {
+                let size = size as usize;
+                reader.bytes(size).or_else(|| Some(reader.rest()))?
+            }
+        };
+        Some((id as u32, content))
+    })
+}
+
+pub(crate) fn ebml_uint(data: &[u8]) -> u64 {
+    data.iter()
+        .fold(0, |value, byte| (value << 8) | *byte as u64)
+}
+
+pub(crate) fn ebml_float(data: &[u8]) -> Option<f64> {
+    match data.len() {
+        4 => Some(f32::from_be_bytes(data.try_into().ok()?) as f64),
+        8 => Some(f64::from_be_bytes(data.try_into().ok()?)),
+        _ => None,
+    }
+}
+
+fn probe_matroska(file: &mut File) -> std::io::Result<MediaProbe> {
+    let mut probe = MediaProbe::default();
+    let mut header = Vec::new();
+    file.by_ref()
+        .take(MATROSKA_HEADER_LENGTH)
+        .read_to_end(&mut header)?;
+
+    let segment = match ebml_elements(&header).find(|(id, _)| *id == MKV_SEGMENT) {
+        Some((_, segment)) => segment,
+        None => return Ok(probe),
+    };
+    for (id, element) in ebml_elements(segment) {
+        match id {
+            MKV_INFO => {
+                let mut timecode_scale = MKV_TIMECODE_SCALE_DEFAULT;
+                let mut duration = None;
+                for (id, value) in ebml_elements(element) {
+                    match id {
+                        MKV_TIMECODE_SCALE => timecode_scale = ebml_uint(value),
+                        MKV_DURATION => duration = ebml_float(value),
+                        _ => {}
+                    }
+                }
+                probe.duration = duration
+                    .map(|duration| duration * timecode_scale as f64 / 1e9)
+                    .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                    .map(RelTime::from);
+            }
+            MKV_TRACKS => {
+                for (_, track) in ebml_elements(element).filter(|(id, _)| *id == MKV_TRACK_ENTRY) {
+                    let mut track_type = None;
+                    let mut codec = None;
+                    let mut video = None;
+                    let mut audio = None;
+                    for (id, value) in ebml_elements(track) {
+                        match id {
+                            MKV_TRACK_TYPE => track_type = Some(ebml_uint(value)),
+                            MKV_CODEC_ID => codec = Some(matroska_codec(value)),
+                            MKV_VIDEO => video = Some(value),
+                            MKV_AUDIO => audio = Some(value),
+                            _ => {}
+                        }
+                    }
+                    match track_type {
+                        Some(MKV_TRACK_TYPE_VIDEO) if probe.video_codec.is_none() => {
+                            probe.resolution = video.and_then(matroska_resolution);
+                            probe.video_codec = codec;
+                        }
+                        Some(MKV_TRACK_TYPE_AUDIO) if probe.audio_codec.is_none() => {
+                            for (id, value) in audio.into_iter().flat_map(ebml_elements) {
+                                match id {
+                                    MKV_SAMPLING_FREQUENCY => {
+                                        probe.sample_rate =
+                                            ebml_float(value).map(|rate| rate as u32)
+                                    }
+                                    MKV_CHANNELS => {
+                                        probe.audio_channels = Some(ebml_uint(value) as u32)
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            probe.audio_codec = codec;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            MKV_CLUSTER => break,
+            _ => {}
+        }
+    }
+    Ok(probe)
+}
+
+fn matroska_resolution(video: &[u8]) -> Option<(u32, u32)> {
+    let mut width = None;
+    let mut height = None;
+    for (id, value) in ebml_elements(video) {
+        match id {
+            MKV_PIXEL_WIDTH => width = Some(ebml_uint(value) as u32),
+            MKV_PIXEL_HEIGHT => height = Some(ebml_uint(value) as u32),
+            _ => {}
+        }
+    }
+    Some((width?, height?))
+}
+
+/// Returns the name of the codec of a Matroska codec ID
+fn matroska_codec(codec_id: &[u8]) -> String {
+    let codec_id = String::from_utf8_lossy(codec_id);
+    let codec_id = codec_id.trim_end_matches('\0');
+    let codec = match codec_id {
+        "V_MPEG4/ISO/AVC" => "h264",
+        "V_MPEGH/ISO/HEVC" => "hevc",
+        "V_VP8" => "vp8",
+        "V_VP9" => "vp9",
+        "V_AV1" => "av1",
+        "V_MPEG2" => "mpeg2",
+        "A_MPEG/L3" => "mp3",
+        "A_MPEG/L2" => "mp2",
+        "A_AC3" => "ac3",
+        "A_EAC3" => "eac3",
+        "A_OPUS" => "opus",
+        "A_VORBIS" => "vorbis",
+        "A_FLAC" => "flac",
+        "A_TRUEHD" => "truehd",
+        _ if codec_id.starts_with("V_MPEG4/ISO/") => "mpeg4",
+        _ if codec_id.starts_with("A_AAC") => "aac",
+        _ if codec_id.starts_with("A_DTS") => "dts",
+        _ => return codec_id.to_lowercase(),
+    };
+    codec.to_string()
+}
+
+// MPEG-TS
+
+/// Returns the PID and program clock reference of a transport stream packet, if it carries one
+fn ts_packet_pcr(packet: &[u8]) -> Option<(u16, u64)> {
+    let pid = u16::from_be_bytes([packet[1] & 0x1f, packet[2]]);
+    let has_adaptation_field = packet[3] & 0x20 != 0;
+    if !has_adaptation_field || packet[4] < 7 || packet[5] & 0x10 == 0 {
+        return None;
+    }
+    let pcr = packet.get(6..12)?;
+    let base = ((pcr[0] as u64) << 25)
+        | ((pcr[1] as u64) << 17)
+        | ((pcr[2] as u64) << 9)
+        | ((pcr[3] as u64) << 1)
+        | ((pcr[4] as u64) >> 7);
+    let extension = (((pcr[4] & 0x01) as u64) << 8) | pcr[5] as u64;
+    Some((pid, base * 300 + extension))
+}
+
+/// Collects the PCRs of the packets of a chunk of a transport stream
+fn ts_pcrs(chunk: &[u8], packet_size: usize, prefix_length: usize) -> Vec<(u16, u64)> {
+    let start = (0..packet_size.min(chunk.len()))
+        .find(|start| {
+            (0..3).all(|packet| {
+                chunk.get(start + prefix_length + packet * packet_size) == Some(&TS_SYNC_BYTE)
+            })
+        })
+        .unwrap_or(0);
+    chunk[start..]
+        .chunks_exact(packet_size)
+        .map(|packet| &packet[prefix_length..])
+        .filter(|packet| packet[0] == TS_SYNC_BYTE)
+        .filter_map(ts_packet_pcr)
+        .collect()
+}
+
+fn probe_transport_stream(
+    file: &mut File,
+    size: u64,
+    prefix_length: usize,
+) -> std::io::Result<MediaProbe> {
+    let mut probe = MediaProbe::default();
+    let packet_size = 188 + prefix_length;
+
+    let mut head = Vec::new();
+    file.by_ref().take(TS_SCAN_LENGTH).read_to_end(&mut head)?;
+    file.seek(SeekFrom::Start(size.saturating_sub(TS_SCAN_LENGTH)))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+
+    let first = ts_pcrs(&head, packet_size, prefix_length).first().copied();
+    if let Some((pid, first_pcr)) = first {
+        let last_pcr = ts_pcrs(&tail, packet_size, prefix_length)
+            .into_iter()
+            .rev()
+            .find(|(last_pid, _)| *last_pid == pid)
+            .map(|(_, pcr)| pcr);
+        if let Some(last_pcr) = last_pcr.filter(|last_pcr| *last_pcr > first_pcr) {
+            probe.duration = duration_from_samples(last_pcr - first_pcr, TS_PCR_FREQUENCY as u32);
+        }
+    }
+    Ok(probe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{ebml, mp4_box, with_temp_file};
+
+    fn probe(name: &str, data: &[u8]) -> MediaProbe {
+        with_temp_file(name, data, MediaProbe::probe).unwrap()
+    }
+
+    fn mp4_track(handler: &[u8], tkhd: &[u8], sample_entry_type: &[u8], entry: &[u8]) -> Vec<u8> {
+        let hdlr = [&[0; 8], handler, &[0; 12]].concat();
+        let stsd = [
+            &[0, 0, 0, 0, 0, 0, 0, 1],
+            (entry.len() as u32 + 8).to_be_bytes().as_slice(),
+            sample_entry_type,
+            entry,
+        ]
+        .concat();
+        let stbl = mp4_box(b"stbl", &mp4_box(b"stsd", &stsd));
+        let mdia = [mp4_box(b"hdlr", &hdlr), mp4_box(b"minf", &stbl)].concat();
+        mp4_box(
+            b"trak",
+            &[mp4_box(b"tkhd", tkhd), mp4_box(b"mdia", &mdia)].concat(),
+        )
+    }
+
+    fn mp4_file() -> Vec<u8> {
+        // version 0, times, timescale 1000 and duration 5000
+        let mvhd = [
+            &[0; 12],
+            1000u32.to_be_bytes().as_slice(),
+            &5000u32.to_be_bytes(),
+        ]
+        .concat();
+        let mut tkhd = vec![0; 76];
+        tkhd.extend_from_slice(&(1280u32 << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(720u32 << 16).to_be_bytes());
+        let mut audio_entry = vec![0; 16];
+        audio_entry.extend_from_slice(&2u16.to_be_bytes());
+        audio_entry.extend_from_slice(&[0; 6]);
+        audio_entry.extend_from_slice(&(48000u32 << 16).to_be_bytes());
+        let moov = [
+            mp4_box(b"mvhd", &mvhd),
+            mp4_track(b"vide", &tkhd, b"hvc1", &[0; 32]),
+            mp4_track(b"soun", &[0; 84], b"mp4a", &audio_entry),
+        ]
+        .concat();
+        [mp4_box(b"ftyp", b"isom\0\0\0\0"), mp4_box(b"moov", &moov)].concat()
+    }
+
+    fn matroska_file() -> Vec<u8> {
+        let info = [
+            ebml(MKV_TIMECODE_SCALE, &[0x0f, 0x42, 0x40]),
+            ebml(MKV_DURATION, &2500f64.to_be_bytes()),
+        ]
+        .concat();
+        let video_track = [
+            ebml(MKV_TRACK_TYPE, &[1]),
+            ebml(MKV_CODEC_ID, b"V_MPEGH/ISO/HEVC"),
+            ebml(
+                MKV_VIDEO,
+                &[
+                    ebml(MKV_PIXEL_WIDTH, &[0x07, 0x80]),
+                    ebml(MKV_PIXEL_HEIGHT, &[0x04, 0x38]),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat();
+        let audio_track = [
+            ebml(MKV_TRACK_TYPE, &[2]),
+            ebml(MKV_CODEC_ID, b"A_AAC/MPEG4/LC"),
+            ebml(
+                MKV_AUDIO,
+                &[
+                    ebml(MKV_SAMPLING_FREQUENCY, &44100f32.to_be_bytes()),
+                    ebml(MKV_CHANNELS, &[6]),
+                ]
+                .concat(),
+            ),
+        ]
+        .concat();
+        let tracks = [
+            ebml(MKV_TRACK_ENTRY, &video_track),
+            ebml(MKV_TRACK_ENTRY, &audio_track),
+        ]
+        .concat();
+        let segment = [ebml(MKV_INFO, &info), ebml(MKV_TRACKS, &tracks)].concat();
+        [ebml(0x1a45_dfa3, &[]), ebml(MKV_SEGMENT, &segment)].concat()
+    }
+
+    fn ts_packet(pcr_base: Option<u64>) -> Vec<u8> {
+        let mut packet = vec![TS_SYNC_BYTE, 0x01, 0x00, 0x20, 183, 0x00];
+        if let Some(base) = pcr_base {
+            packet[5] = 0x10;
+            packet.extend_from_slice(&[
+                (base >> 25) as u8,
+                (base >> 17) as u8,
+                (base >> 9) as u8,
+                (base >> 1) as u8,
+                ((base & 0x01) << 7) as u8 | 0x7e,
+                0x00,
+            ]);
+        }
+        packet.resize(188, 0xff);
+        packet
+    }
+
+    #[test]
+    fn probe_mp4() {
+        let probe = probe("movie.mp4", &mp4_file());
+        assert_eq!(probe.duration, Some(RelTime::from_secs(5)));
+        assert_eq!(probe.resolution, Some((1280, 720)));
+        assert_eq!(probe.video_codec.as_deref(), Some("hevc"));
+        assert_eq!(probe.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(probe.audio_channels, Some(2));
+        assert_eq!(probe.sample_rate, Some(48000));
+    }
+
+    #[test]
+    fn probe_matroska() {
+        let probe = probe("movie.mkv", &matroska_file());
+        assert_eq!(
+            probe
+                .duration
+                .map(|duration| duration.as_duration().as_millis()),
+            Some(2500)
+        );
+        assert_eq!(probe.resolution, Some((1920, 1080)));
+        assert_eq!(probe.video_codec.as_deref(), Some("hevc"));
+        assert_eq!(probe.audio_codec.as_deref(), Some("aac"));
+        assert_eq!(probe.audio_channels, Some(6));
+        assert_eq!(probe.sample_rate, Some(44100));
+    }
+
+    #[test]
+    fn probe_transport_stream() {
+        // 90 kHz PCR bases, 2 seconds apart
+        let data = [
+            ts_packet(Some(0)),
+            ts_packet(None),
+            ts_packet(Some(180_000)),
+        ]
+        .concat();
+        let probe = probe("movie.ts", &data);
+        assert_eq!(probe.duration, Some(RelTime::from_secs(2)));
+        assert_eq!(probe.bitrate, Some(282));
+    }
+
+    #[test]
+    fn probe_truncated_files() {
+        let ts = [ts_packet(Some(0)), ts_packet(Some(90_000))].concat();
+        for (name, data) in [("mp4", mp4_file()), ("mkv", matroska_file()), ("ts", ts)] {
+            for length in 0..data.len() {
+                probe(&format!("truncated.{}", name), &data[..length]);
+            }
+        }
+    }
+
+    #[test]
+    fn probe_matroska_with_invalid_duration() {
+        let data = matroska_file();
+        let duration = 2500f64.to_be_bytes();
+        let position = data
+            .windows(duration.len())
+            .position(|window| window == duration)
+            .unwrap();
+        for invalid in [1e30, -1.0, f64::NAN] {
+            let mut data = data.clone();
+            data[position..position + 8].copy_from_slice(&invalid.to_be_bytes());
+            assert_eq!(probe("invalid.mkv", &data).duration, None);
+        }
+    }
+
+    #[test]
+    fn probe_mp4_with_corrupt_box_size() {
+        let mut data = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        data.extend_from_slice(&[0, 0, 0, 1]);
+        data.extend_from_slice(b"mdat");
+        data.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert_eq!(probe("corrupt.mp4", &data).duration, None);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{
+    error::{Error, Result},
+    media::MediaProfile,
+};
+use log::debug;
+use std::{fmt, str::FromStr};
+
+/// MIME types which renders use interchangeably
+const MIME_ALIASES: [(&str, &str); 8] = [
+    ("video/x-matroska", "video/x-mkv"),
+    ("video/x-matroska", "video/mkv"),
+    ("video/x-msvideo", "video/avi"),
+    ("video/mpeg", "video/mp2t"),
+    ("audio/mpeg", "audio/mp3"),
+    ("audio/wav", "audio/x-wav"),
+    ("audio/flac", "audio/x-flac"),
+    ("audio/mp4", "audio/x-m4a"),
+];
+
+/// A protocol info entry (`<protocol>:<network>:<contentFormat>:<additionalInfo>`), as declared by
+/// a render for the media it can play.
+///
+/// ```
+/// use crab_dlna::ProtocolInfo;
+///
+/// let protocol_info: ProtocolInfo = "http-get:*:audio/mpeg:DLNA.ORG_PN=MP3".parse().unwrap();
+/// assert_eq!(protocol_info.mime, "audio/mpeg");
+/// assert_eq!(protocol_info.dlna_profile.as_deref(), Some("MP3"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolInfo {
+    /// The transport protocol (e.g. `http-get`), or `*`
+    pub protocol: String,
+    /// The network, usually `*`
+    pub network: String,
+    /// The MIME type of the media, possibly with wildcards (`*`, `video/*`)
+    pub mime: String,
+    /// The DLNA profile name of the media (`DLNA.ORG_PN`), when restricted to one
+    pub dlna_profile: Option<String>,
+    /// The additional info, as declared
+    pub additional_info: String,
+}
+
+impl ProtocolInfo {
+    /// Parses a list of protocol info entries separated by commas, such as the `Sink` of `GetProtocolInfo`,
+    /// skipping the invalid ones.
+    pub fn parse_list(list: &str) -> Vec<Self> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .filter_map(|entry| match entry.parse() {
+                Ok(protocol_info) => Some(protocol_info),
+                Err(err) => {
+                    debug!("{}", err);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Whether a list of protocol info, such as the `Sink` of a render, matches a media profile;
+    /// an empty list is assumed to match anything.
+    pub(crate) fn list_matches(list: &[Self], profile: &MediaProfile) -> bool {
+        list.is_empty()
+            || list
+                .iter()
+                .any(|protocol_info| protocol_info.matches(profile))
+    }
+
+    /// Whether a type of media, served over HTTP, matches the entry.
+    ///
+    /// The DLNA profiles are only compared when both are known.
+    pub fn matches(&self, profile: &MediaProfile) -> bool {
+        let protocol = self.protocol == "*" || self.protocol.eq_ignore_ascii_case("http-get");
+        let dlna_profile = match (&self.dlna_profile, profile.dlna_profile) {
+            (Some(dlna_profile), Some(media_dlna_profile)) => {
+                dlna_profile.eq_ignore_ascii_case(media_dlna_profile)
+            }
+            _ => true,
+        };
+        protocol && dlna_profile && mime_matches(&self.mime, profile.mime)
+    }
+}
+
+impl FromStr for ProtocolInfo {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields = s.trim().splitn(4, ':').collect::<Vec<_>>();
+        let (protocol, network, mime, additional_info) = match fields[..] {
+            [protocol, network, mime, additional_info] if !mime.is_empty() => {
+                (protocol, network, mime, additional_info)
+            }
+            _ => return Err(Error::DevicesProtocolInfoParseError(s.to_string())),
+        };
+        let dlna_profile = additional_info
+            .split(';')
+            .find_map(|parameter| parameter.trim().strip_prefix("DLNA.ORG_PN="))
+            .map(str::to_string);
+        Ok(Self {
+            protocol: protocol.to_string(),
+            network: network.to_string(),
+            mime: mime.to_lowercase(),
+            dlna_profile,
+            additional_info: additional_info.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for ProtocolInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.protocol, self.network, self.mime, self.additional_info
+        )
+    }
+}
+
+/// Whether a MIME type matches a declared one, which may be a wildcard (`*`, `*/*`, `video/*`) or an alias
+fn mime_matches(declared: &str, mime: &str) -> bool {
+    if matches!(declared, "*" | "*/*") || declared.eq_ignore_ascii_case(mime) {
+        return true;
+    }
+    if let Some(declared_type) = declared.strip_suffix("/*") {
+        return mime.split('/').next() == Some(declared_type);
+    }
+    MIME_ALIASES.iter().any(|(mime_type, alias)| {
+        (*mime_type == mime && declared == *alias) || (*alias == mime && declared == *mime_type)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::MediaKind;
+
+    fn matches(declared: &str, profile: MediaProfile) -> bool {
+        declared.parse::<ProtocolInfo>().unwrap().matches(&profile)
+    }
+
+    #[test]
+    fn parse_protocol_info() {
+        let protocol_info: ProtocolInfo =
+            " http-get:*:Video/MP4:DLNA.ORG_PN=AVC_MP4_BL_CIF15_AAC_520;DLNA.ORG_OP=01 "
+                .parse()
+                .unwrap();
+        assert_eq!(protocol_info.protocol, "http-get");
+        assert_eq!(protocol_info.network, "*");
+        assert_eq!(protocol_info.mime, "video/mp4");
+        assert_eq!(
+            protocol_info.dlna_profile.as_deref(),
+            Some("AVC_MP4_BL_CIF15_AAC_520")
+        );
+        assert_eq!(
+            protocol_info.to_string(),
+            "http-get:*:video/mp4:DLNA.ORG_PN=AVC_MP4_BL_CIF15_AAC_520;DLNA.ORG_OP=01"
+        );
+        // the additional info may hold colons
+        let protocol_info: ProtocolInfo = "rtsp-rtp-udp:*:video/mpeg:a=b:c".parse().unwrap();
+        assert_eq!(protocol_info.additional_info, "a=b:c");
+        assert_eq!(protocol_info.dlna_profile, None);
+    }
+
+    #[test]
+    fn parse_invalid_protocol_info() {
+        for invalid in ["", "http-get", "http-get:*:video/mp4", "http-get:*::*"] {
+            assert!(
+                matches!(
+                    invalid.parse::<ProtocolInfo>(),
+                    Err(Error::DevicesProtocolInfoParseError(_))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn parse_protocol_info_list() {
+        let list = "http-get:*:video/mp4:*, ,invalid,\r\n http-get:*:audio/mpeg:DLNA.ORG_PN=MP3,";
+        let mimes = ProtocolInfo::parse_list(list)
+            .into_iter()
+            .map(|protocol_info| protocol_info.mime)
+            .collect::<Vec<_>>();
+        assert_eq!(mimes, ["video/mp4", "audio/mpeg"]);
+        assert!(ProtocolInfo::parse_list("").is_empty());
+    }
+
+    #[test]
+    fn match_mime_types() {
+        let mp4 = MediaProfile::from_extension("mp4");
+        assert!(matches("http-get:*:video/mp4:*", mp4));
+        assert!(matches("*:*:*:*", mp4));
+        assert!(matches("http-get:*:*/*:*", mp4));
+        assert!(matches("HTTP-GET:*:video/*:*", mp4));
+        assert!(!matches("http-get:*:audio/*:*", mp4));
+        assert!(!matches("rtsp-rtp-udp:*:video/mp4:*", mp4));
+        assert!(!matches("http-get:*:video/mpeg:*", mp4));
+        // aliases, in both directions
+        assert!(matches(
+            "http-get:*:video/x-mkv:*",
+            MediaProfile::from_extension("mkv")
+        ));
+        assert!(matches(
+            "http-get:*:audio/mpeg:*",
+            MediaProfile::transcoded("audio/mp3", None, MediaKind::Audio)
+        ));
+        assert!(!matches(
+            "http-get:*:video/mkv:*",
+            MediaProfile::from_extension("avi")
+        ));
+    }
+
+    #[test]
+    fn match_dlna_profiles() {
+        let mp3 = MediaProfile::from_extension("mp3");
+        assert!(matches("http-get:*:audio/mpeg:DLNA.ORG_PN=mp3", mp3));
+        assert!(!matches("http-get:*:audio/mpeg:DLNA.ORG_PN=MP3X", mp3));
+        // profiles are only compared when both are known
+        assert!(matches("http-get:*:audio/mpeg:*", mp3));
+        assert!(matches(
+            "http-get:*:video/mp4:DLNA.ORG_PN=AVC_MP4_MP_HD_720p_AAC",
+            MediaProfile::from_extension("mp4")
+        ));
+    }
+}
@@ -0,0 +1,360 @@
+use crate::{
+    control::SeekFormat,
+    didl::SubtitleExtension,
+    error::{Error, Result},
+};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Name of the quirks file looked up in the configuration directory of crab-dlna
+const QUIRKS_FILE_NAME: &str = "quirks.toml";
+
+/// Quirks of known renders, in the format of the quirks files
+const BUILTIN_QUIRKS: &str = r#"
+[[render]]
+name = "samsung"
+manufacturer = "Samsung"
+subtitle-extensions = ["sec"]
+caption-info-header = true
+stop-before-set-uri = true
+
+[[render]]
+name = "lg-webos"
+manufacturer = "LG Electronics"
+subtitle-extensions = ["res"]
+stop-before-set-uri = true
+seek-format = "rel-time-padded"
+
+[[render]]
+name = "sony-bravia"
+manufacturer = "Sony"
+model-name = "BRAVIA"
+subtitle-extensions = ["res"]
+content-features-header = true
+
+[[render]]
+name = "panasonic"
+manufacturer = "Panasonic"
+subtitle-extensions = ["pv"]
+
+[[render]]
+name = "kodi"
+model-name = "Kodi"
+subtitle-extensions = ["res"]
+
+[[render]]
+name = "vlc"
+model-name = "VLC"
+subtitle-extensions = ["res"]
+subtitle-mime = "application/x-subrip"
+
+[[render]]
+name = "gmediarender"
+model-name = "gmediarender"
+subtitle-extensions = []
+
+[[render]]
+name = "xbox"
+model-name = "Xbox"
+subtitle-extensions = []
+content-features-header = true
+stop-before-set-uri = true
+"#;
+
+/// The vendor-specific behaviour of a render
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quirks {
+    /// The names of the rules which matched the render
+    pub names: Vec<String>,
+    /// The vendor extensions the subtitles are announced with in the DIDL-Lite metadata
+    pub subtitle_extensions: Vec<SubtitleExtension>,
+    /// Whether the URI of the subtitles is sent in a `CaptionInfo.sec` header of the media, even when not requested
+    pub caption_info_header: bool,
+    /// Whether the `contentFeatures.dlna.org` header is sent, even when not requested
+    pub content_features_header: bool,
+    /// Whether the playback is stopped before setting the URI of a media
+    pub stop_before_set_uri: bool,
+    /// The MIME type the subtitles are served with, instead of the one of their format
+    pub subtitle_mime: Option<String>,
+    /// The format of the positions sent to seek
+    pub seek_format: SeekFormat,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self {
+            names: Vec::new(),
+            subtitle_extensions: SubtitleExtension::ALL.to_vec(),
+            caption_info_header: false,
+            content_features_header: false,
+            stop_before_set_uri: false,
+            subtitle_mime: None,
+            seek_format: SeekFormat::default(),
+        }
+    }
+}
+
+/// A rule of the quirks database: the quirks set for the renders it matches.
+///
+/// The matchers are case-insensitive substrings of the device description; a rule without any
+/// matcher applies to every render.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct QuirksRule {
+    name: Option<String>,
+    manufacturer: Option<String>,
+    model_name: Option<String>,
+    friendly_name: Option<String>,
+    subtitle_extensions: Option<Vec<SubtitleExtension>>,
+    caption_info_header: Option<bool>,
+    content_features_header: Option<bool>,
+    stop_before_set_uri: Option<bool>,
+    subtitle_mime: Option<String>,
+    seek_format: Option<SeekFormat>,
+}
+
+impl QuirksRule {
+    fn matches(&self, manufacturer: &str, model_name: &str, friendly_name: &str) -> bool {
+        let matches = |matcher: &Option<String>, value: &str| {
+            matcher
+                .as_ref()
+                .is_none_or(|matcher| value.to_lowercase().contains(&matcher.to_lowercase()))
+        };
+        matches(&self.manufacturer, manufacturer)
+            && matches(&self.model_name, model_name)
+            && matches(&self.friendly_name, friendly_name)
+    }
+
+    fn apply(&self, quirks: &mut Quirks) {
+        if let Some(name) = &self.name {
+            quirks.names.push(name.clone());
+        }
+        if let Some(subtitle_extensions) = &self.subtitle_extensions {
+            quirks.subtitle_extensions = subtitle_extensions.clone();
+        }
+        if let Some(caption_info_header) = self.caption_info_header {
+            quirks.caption_info_header = caption_info_header;
+        }
+        if let Some(content_features_header) = self.content_features_header {
+            quirks.content_features_header = content_features_header;
+        }
+        if let Some(stop_before_set_uri) = self.stop_before_set_uri {
+            quirks.stop_before_set_uri = stop_before_set_uri;
+        }
+        if let Some(subtitle_mime) = &self.subtitle_mime {
+            quirks.subtitle_mime = Some(subtitle_mime.clone());
+        }
+        if let Some(seek_format) = self.seek_format {
+            quirks.seek_format = seek_format;
+        }
+    }
+}
+
+/// A database of the quirks of renders, matched by the manufacturer, model name and friendly name of
+/// their device description.
+///
+/// Every matching rule is applied in order, so the rules of a quirks file override the built-in ones.
+///
+/// ```
+/// use crab_dlna::{QuirksDatabase, SubtitleExtension};
+///
+/// let quirks_file = r#"
+/// [[render]]
+/// name = "bedroom"
+/// friendly-name = "Bedroom"
+/// subtitle-extensions = ["pv"]
+/// "#;
+/// let database = QuirksDatabase::builtin().with_rules(quirks_file.parse().unwrap());
+/// let quirks = database.quirks("Samsung Electronics", "UE40", "[TV] Bedroom");
+/// assert_eq!(quirks.names, ["samsung", "bedroom"]);
+/// assert_eq!(quirks.subtitle_extensions, [SubtitleExtension::Pv]);
+/// assert!(quirks.stop_before_set_uri);
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuirksDatabase {
+    #[serde(default, rename = "render")]
+    rules: Vec<QuirksRule>,
+}
+
+impl QuirksDatabase {
+    /// Returns the database of the quirks of known renders
+    pub fn builtin() -> Self {
+        BUILTIN_QUIRKS
+            .parse()
+            .expect("the built-in quirks are a valid quirks file")
+    }
+
+    /// Reads the rules of a quirks file, applied after the built-in ones.
+    pub fn load(path: &Path) -> Result<Self> {
+        let quirks_file = std::fs::read_to_string(path)
+            .map_err(|err| Error::QuirksReadError(path.display().to_string(), err))?;
+        let rules = Self::parse(&quirks_file, &path.display().to_string())?;
+        Ok(Self::builtin().with_rules(rules))
+    }
+
+    /// Returns the path of the quirks file of the user (`crab-dlna/quirks.toml` in the configuration
+    /// directory), if it exists.
+    pub fn user_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+        Some(config_dir.join("crab-dlna").join(QUIRKS_FILE_NAME)).filter(|path| path.is_file())
+    }
+
+    /// Adds the rules of another database, applied after the ones of this database.
+    pub fn with_rules(mut self, database: QuirksDatabase) -> Self {
+        self.rules.extend(database.rules);
+        self
+    }
+
+    fn parse(quirks_file: &str, source: &str) -> Result<Self> {
+        toml::from_str(quirks_file)
+            .map_err(|err| Error::QuirksParseError(source.to_string(), err.to_string()))
+    }
+
+    /// Returns the quirks of a render, from its device description.
+    pub fn quirks(&self, manufacturer: &str, model_name: &str, friendly_name: &str) -> Quirks {
+        let mut quirks = Quirks::default();
+        for rule in &self.rules {
+            if rule.matches(manufacturer, model_name, friendly_name) {
+                rule.apply(&mut quirks);
+            }
+        }
+        quirks
+    }
+}
+
+impl FromStr for QuirksDatabase {
+    type Err = Error;
+
+    /// Parses the rules of a quirks file, in TOML.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s, QUIRKS_FILE_NAME)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_temp_file;
+
+    fn rule(manufacturer: Option<&str>, model_name: Option<&str>) -> QuirksRule {
+        QuirksRule {
+            manufacturer: manufacturer.map(str::to_owned),
+            model_name: model_name.map(str::to_owned),
+            ..QuirksRule::default()
+        }
+    }
+
+    #[test]
+    fn match_rules() {
+        assert!(rule(None, None).matches("", "", ""));
+        assert!(rule(Some("samsung"), None).matches("Samsung Electronics", "UE40", "TV"));
+        assert!(!rule(Some("Samsung"), None).matches("LG Electronics", "UE40", "TV"));
+        assert!(rule(Some("sony"), Some("bravia")).matches("Sony", "KD-55 BRAVIA", "TV"));
+        assert!(!rule(Some("Sony"), Some("BRAVIA")).matches("Sony", "Walkman", "TV"));
+        assert!(!rule(Some("Sony"), None).matches("", "", "Sony TV"));
+    }
+
+    #[test]
+    fn builtin_quirks() {
+        let database = QuirksDatabase::builtin();
+        assert_eq!(
+            database.quirks("Unknown", "Render", "TV"),
+            Quirks::default()
+        );
+
+        let quirks = database.quirks("LG Electronics", "OLED55", "[LG] webOS TV");
+        assert_eq!(quirks.names, ["lg-webos"]);
+        assert_eq!(quirks.subtitle_extensions, [SubtitleExtension::Res]);
+        assert_eq!(quirks.seek_format, SeekFormat::RelTimePadded);
+        assert!(quirks.stop_before_set_uri);
+
+        let quirks = database.quirks("VideoLAN", "VLC media player", "VLC");
+        assert_eq!(
+            quirks.subtitle_mime.as_deref(),
+            Some("application/x-subrip")
+        );
+        assert!(database
+            .quirks("", "gmediarender", "")
+            .subtitle_extensions
+            .is_empty());
+    }
+
+    #[test]
+    fn override_quirks() {
+        let quirks_file = r#"
+[[render]]
+manufacturer = "samsung"
+stop-before-set-uri = false
+seek-format = "abs-time"
+
+[[render]]
+name = "all"
+content-features-header = true
+"#;
+        let database = QuirksDatabase::builtin().with_rules(quirks_file.parse().unwrap());
+        let quirks = database.quirks("Samsung Electronics", "UE40", "TV");
+        assert_eq!(quirks.names, ["samsung", "all"]);
+        assert_eq!(quirks.subtitle_extensions, [SubtitleExtension::Sec]);
+        assert!(quirks.caption_info_header);
+        assert!(!quirks.stop_before_set_uri);
+        assert!(quirks.content_features_header);
+        assert_eq!(quirks.seek_format, SeekFormat::AbsTime);
+
+        let quirks = database.quirks("Unknown", "Render", "TV");
+        assert_eq!(quirks.names, ["all"]);
+        assert_eq!(quirks.subtitle_extensions, SubtitleExtension::ALL);
+    }
+
+    #[test]
+    fn parse_invalid_quirks() {
+        assert!(""
+            .parse::<QuirksDatabase>()
+            .unwrap()
+            .quirks("Samsung", "", "")
+            .names
+            .is_empty());
+        for invalid in [
+            "[[render]]\nunknown = true",
+            "[[render]]\nsubtitle-extensions = [\"srt\"]",
+            "[[render]]\nseek-format = \"frames\"",
+            "[[render]]\nstop-before-set-uri = \"yes\"",
+            "[[render]",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<QuirksDatabase>(),
+                    Err(Error::QuirksParseError(..))
+                ),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn load_quirks_file() {
+        with_temp_file("quirks.toml", b"[[render]]\nname = \"all\"\n", |path| {
+            let quirks = QuirksDatabase::load(path)
+                .unwrap()
+                .quirks("Sony", "BRAVIA", "");
+            assert_eq!(quirks.names, ["sony-bravia", "all"]);
+        });
+        with_temp_file("quirks.toml", b"[[render]]\nname = 1\n", |path| {
+            assert!(matches!(
+                QuirksDatabase::load(path),
+                Err(Error::QuirksParseError(..))
+            ));
+        });
+        assert!(matches!(
+            QuirksDatabase::load(Path::new("/nonexistent/quirks.toml")),
+            Err(Error::QuirksReadError(..))
+        ));
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{
+    control::RelTime,
+    devices::Render,
+    error::{Error, Result},
+};
+use log::debug;
+use std::{collections::HashMap, fmt};
+
+const PAYLOAD_INSTANCE: &str = r#"
+    <InstanceID>0</InstanceID>
+"#;
+
+/// The transport state of a render, as reported by AVTransport.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TransportState {
+    /// No media is being played
+    Stopped,
+    /// A media is being played
+    Playing,
+    /// The render is loading or buffering a media
+    Transitioning,
+    /// The media being played is paused
+    PausedPlayback,
+    /// The media being recorded is paused
+    PausedRecording,
+    /// A media is being recorded
+    Recording,
+    /// There is no media set in the render
+    NoMediaPresent,
+    /// A vendor-defined state
+    Other(String),
+}
+
+impl From<&str> for TransportState {
+    fn from(state: &str) -> Self {
+        match state {
+            "STOPPED" => Self::Stopped,
+            "PLAYING" => Self::Playing,
+            "TRANSITIONING" => Self::Transitioning,
+            "PAUSED_PLAYBACK" => Self::PausedPlayback,
+            "PAUSED_RECORDING" => Self::PausedRecording,
+            "RECORDING" => Self::Recording,
+            "NO_MEDIA_PRESENT" => Self::NoMediaPresent,
+            other => Self::Other(other.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for TransportState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            Self::Stopped => "STOPPED",
+            Self::Playing => "PLAYING",
+            Self::Transitioning => "TRANSITIONING",
+            Self::PausedPlayback => "PAUSED_PLAYBACK",
+            Self::PausedRecording => "PAUSED_RECORDING",
+            Self::Recording => "RECORDING",
+            Self::NoMediaPresent => "NO_MEDIA_PRESENT",
+            Self::Other(other) => other,
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// The result of the GetTransportInfo action.
+#[derive(Debug, Clone)]
+pub struct TransportInfo {
+    /// The transport state
+    pub state: TransportState,
+    /// The transport status (`OK` or `ERROR_OCCURRED`)
+    pub status: String,
+    /// The playback speed
+    pub speed: String,
+}
+
+/// The result of the GetPositionInfo action.
+#[derive(Debug, Clone)]
+pub struct PositionInfo {
+    /// The number of the current track
+    pub track: u32,
+    /// The duration of the current track, if known
+    pub track_duration: Option<RelTime>,
+    /// The URI of the current track
+    pub track_uri: String,
+    /// The position in the current track, if known
+    pub rel_time: Option<RelTime>,
+}
+
+/// The result of the GetMediaInfo action.
+#[derive(Debug, Clone)]
+pub struct MediaInfo {
+    /// The number of tracks in the media
+    pub tracks: u32,
+    /// The duration of the media, if known
+    pub media_duration: Option<RelTime>,
+    /// The URI of the current media
+    pub current_uri: String,
+    /// The URI of the next media, if any was set
+    pub next_uri: String,
+}
+
+/// A snapshot of the playback status of a render.
+#[derive(Debug, Clone)]
+pub struct RenderStatus {
+    /// The transport information
+    pub transport: TransportInfo,
+    /// The position information
+    pub position: PositionInfo,
+    /// The media information
+    pub media: MediaInfo,
+}
+
+impl Render {
+    /// Queries the transport state of the render.
+    pub async fn transport_info(&self) -> Result<TransportInfo> {
+        let response = self
+            .query("GetTransportInfo")
+            .await
+            .map_err(Error::DLNAGetTransportInfoError)?;
+        Ok(TransportInfo {
+            state: TransportState::from(field(&response, "CurrentTransportState").as_str()),
+            status: field(&response, "CurrentTransportStatus"),
+            speed: field(&response, "CurrentSpeed"),
+        })
+    }
+
+    /// Queries the playback position of the render.
+    pub async fn position_info(&self) -> Result<PositionInfo> {
+        let response = self
+            .query("GetPositionInfo")
+            .await
+            .map_err(Error::DLNAGetPositionInfoError)?;
+        Ok(PositionInfo {
+            track: field(&response, "Track").parse().unwrap_or_default(),
+            track_duration: field(&response, "TrackDuration").parse().ok(),
+            track_uri: field(&response, "TrackURI"),
+            rel_time: field(&response, "RelTime").parse().ok(),
+        })
+    }
+
+    /// Queries the media currently set in the render.
+    pub async fn media_info(&self) -> Result<MediaInfo> {
+        let response = self
+            .query("GetMediaInfo")
+            .await
+            .map_err(Error::DLNAGetMediaInfoError)?;
+        Ok(MediaInfo {
+            tracks: field(&response, "NrTracks").parse().unwrap_or_default(),
+            media_duration: field(&response, "MediaDuration").parse().ok(),
+            current_uri: field(&response, "CurrentURI"),
+            next_uri: field(&response, "NextURI"),
+        })
+    }
+
+    /// Queries the transport, position and media information of the render.
+    pub async fn status(&self) -> Result<RenderStatus> {
+        Ok(RenderStatus {
+            transport: self.transport_info().await?,
+            position: self.position_info().await?,
+            media: self.media_info().await?,
+        })
+    }
+
+    async fn query(&self, action: &str) -> Result<HashMap<String, String>, rupnp::Error> {
+        let response = self
+            .service
+            .action(self.device.url(), action, PAYLOAD_INSTANCE)
+            .await?;
+        debug!("{} response: {:?}", action, response);
+        Ok(response)
+    }
+}
+
+fn field(response: &HashMap<String, String>, name: &str) -> String {
+    response.get(name).cloned().unwrap_or_default()
+}
@@ -1,19 +1,93 @@
-use crate::error::{Error, Result};
+use crate::{
+    embedded::{
+        extract_subtitle_tracks, list_subtitle_tracks, rank_subtitle_tracks, SubtitleTrackSelector,
+    },
+    error::{Error, Result},
+    events::{EventCallback, EventNotification, EVENTS_PATH},
+    media::{MediaKind, MediaProfile},
+    probe::MediaProbe,
+    quirks::Quirks,
+    subtitles::{
+        find_subtitle_candidates, language_tag, read_subtitle_text, SubtitleCandidate,
+        SubtitleEncoding, SubtitleOptions, Subtitles,
+    },
+    tags::AudioTags,
+    transcode::Transcoder,
+};
+use futures::channel::mpsc;
 use local_ip_address::local_ip;
 use log::{debug, info, warn};
 use slugify::slugify;
-use std::net::SocketAddr;
-use warp::Filter;
+use std::{future::Future, net::SocketAddr};
+use warp::{
+    filters::{fs::File, BoxedFilter},
+    http::{
+        header::{self, HeaderValue},
+        Method, StatusCode,
+    },
+    hyper::{body::Bytes, Body},
+    reply::{Reply, Response},
+    Filter,
+};
 
 /// Default port to use for the streaming server
 pub const STREAMING_PORT_DEFAULT: u32 = 9000;
 
+/// A DLNA transfer mode, as negotiated through the `transferMode.dlna.org` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferMode {
+    Streaming,
+    Interactive,
+    Background,
+}
+
+impl TransferMode {
+    fn from_header(value: &str) -> Option<Self> {
+        match value.trim() {
+            "Streaming" => Some(Self::Streaming),
+            "Interactive" => Some(Self::Interactive),
+            "Background" => Some(Self::Background),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Streaming => "Streaming",
+            Self::Interactive => "Interactive",
+            Self::Background => "Background",
+        }
+    }
+
+    /// Returns the transfer mode used when the render does not request one
+    fn default_for(kind: MediaKind) -> Self {
+        match kind.is_av() {
+            true => Self::Streaming,
+            false => Self::Interactive,
+        }
+    }
+
+    /// Whether the transfer mode can be used for a kind of media
+    fn supports(&self, kind: MediaKind) -> bool {
+        match self {
+            Self::Streaming => kind.is_av(),
+            Self::Interactive => !kind.is_av(),
+            Self::Background => true,
+        }
+    }
+}
+
 /// A media file to stream
 #[derive(Debug, Clone)]
 pub struct MediaFile {
     file_path: std::path::PathBuf,
     host_uri: String,
     file_uri: String,
+    profile: MediaProfile,
+    /// The content served instead of the one of the file, when it has been converted
+    content: Option<Bytes>,
+    /// The language of the content, for subtitles
+    language: Option<String>,
 }
 
 impl std::fmt::Display for MediaFile {
@@ -28,12 +102,202 @@ impl std::fmt::Display for MediaFile {
     }
 }
 
+impl MediaFile {
+    fn new(file_path: &std::path::Path, server_addr: &SocketAddr) -> Result<Self> {
+        match file_path.exists() {
+            true => Ok(Self {
+                file_path: file_path.to_path_buf(),
+                host_uri: format!("http://{}", server_addr),
+                file_uri: slugify!(file_path.display().to_string().as_str(), separator = "."),
+                profile: MediaProfile::detect(file_path),
+                content: None,
+                language: None,
+            }),
+            false => Err(Error::StreamingFileDoesNotExist(
+                file_path.display().to_string(),
+            )),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn uri(&self) -> String {
+        format!("{}/{}", self.host_uri, self.file_uri)
+    }
+
+    /// Returns the extension of the file as served, which is the one of its format once converted
+    pub fn extension(&self) -> String {
+        std::path::Path::new(&self.file_uri)
+            .extension()
+            .unwrap_or_default()
+            .to_str()
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Returns the language of the content of the file, as a tag like `en` or `pt-BR`, when known
+    pub fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+
+    /// Converts a subtitle file to the format and encoding it is served in.
+    ///
+    /// Files whose format is not supported are only re-encoded, and unreadable ones are served as they are.
+    fn convert_subtitles(&mut self, subtitle_options: &SubtitleOptions) {
+        let text = match read_subtitle_text(&self.file_path) {
+            Ok(text) => text,
+            Err(err) => {
+                warn!("{}, serving it as it is", err);
+                return;
+            }
+        };
+        match Subtitles::from_text(&self.file_path, &text) {
+            Ok(subtitles) => self.serve_subtitles(subtitles, subtitle_options),
+            Err(err) => {
+                warn!("{}, serving it without converting it", err);
+                self.content = Some(Bytes::from(subtitle_options.encoding.encode(&text)));
+            }
+        }
+    }
+
+    /// Serves subtitles in the format and encoding of the options, after retiming them
+    fn serve_subtitles(&mut self, mut subtitles: Subtitles, subtitle_options: &SubtitleOptions) {
+        let format = subtitle_options.format;
+        debug!(
+            "Converting subtitles of '{}' to {}",
+            self.file_path.display(),
+            format
+        );
+        self.file_uri = std::path::Path::new(&self.file_uri)
+            .with_extension(format.extension())
+            .display()
+            .to_string();
+        self.profile = MediaProfile::from_extension(format.extension());
+        if subtitle_options.delay != 0 || subtitle_options.framerate.is_some() {
+            debug!(
+                "Retiming subtitles with a delay of {} ms and framerate {:?}",
+                subtitle_options.delay, subtitle_options.framerate
+            );
+            subtitles.retime(subtitle_options.delay, subtitle_options.framerate);
+        }
+        let text = subtitles.to_format(format);
+        self.content = Some(Bytes::from(subtitle_options.encoding.encode(&text)));
+    }
+
+    /// Returns the subtitles of a subtitle file as served, in SRT, to be burned into a video
+    fn srt_text(&self) -> Option<String> {
+        let text = match &self.content {
+            Some(content) => SubtitleEncoding::decode(content).1,
+            None => read_subtitle_text(&self.file_path).ok()?,
+        };
+        match Subtitles::from_text(std::path::Path::new(&self.file_uri), &text) {
+            Ok(subtitles) => Some(subtitles.to_srt()),
+            Err(err) => {
+                warn!("Failed to burn the subtitles: {}", err);
+                None
+            }
+        }
+    }
+}
+
+/// A media item to stream, composed of a video (or audio) file and its subtitle files, if any
+#[derive(Debug, Clone)]
+pub struct MediaItem {
+    video_file: MediaFile,
+    subtitle_files: Vec<MediaFile>,
+    tags: Option<AudioTags>,
+    probe: Option<MediaProbe>,
+    transcoder: Option<Transcoder>,
+    /// The subtitles burned into the video when transcoding it, in SRT
+    burned_subtitles: Option<String>,
+}
+
+impl MediaItem {
+    /// Returns the path of the video file
+    pub fn video_path(&self) -> &std::path::Path {
+        &self.video_file.file_path
+    }
+
+    #[doc(hidden)]
+    pub fn video_uri(&self) -> String {
+        self.video_file.uri()
+    }
+
+    /// Returns the detected type of the video file
+    pub fn video_profile(&self) -> &MediaProfile {
+        &self.video_file.profile
+    }
+
+    #[doc(hidden)]
+    pub fn subtitle_uri(&self) -> Option<String> {
+        self.subtitle_files.first().map(MediaFile::uri)
+    }
+
+    #[doc(hidden)]
+    pub fn subtitle_type(&self) -> Option<String> {
+        self.subtitle_files.first().map(MediaFile::extension)
+    }
+
+    /// Returns the subtitle files of the item, the most relevant first
+    pub fn subtitle_files(&self) -> &[MediaFile] {
+        &self.subtitle_files
+    }
+
+    /// Returns the tags of the file, when it is an audio file
+    pub fn tags(&self) -> Option<&AudioTags> {
+        self.tags.as_ref()
+    }
+
+    /// Returns the properties probed from the container of the file, when it is a video or audio file
+    pub fn probe(&self) -> Option<&MediaProbe> {
+        self.probe.as_ref()
+    }
+
+    /// Transcodes the media with ffmpeg as it is streamed, instead of serving its file.
+    ///
+    /// When the profile burns the subtitles into the video, the most relevant subtitle file is burned
+    /// and none is served aside, as when it only keeps the audio.
+    pub fn transcode(&mut self, transcoder: Transcoder) {
+        let profile = transcoder.profile();
+        self.video_file.file_uri = format!("{}.{}", self.video_file.file_uri, profile.extension());
+        self.video_file.profile = profile.media_profile();
+        if profile.burns_subtitles() {
+            self.burned_subtitles = self.subtitle_files.first().and_then(MediaFile::srt_text);
+        }
+        if profile.burns_subtitles() || self.video_file.profile.kind != MediaKind::Video {
+            self.subtitle_files.clear();
+        }
+        self.transcoder = Some(transcoder);
+    }
+
+    /// Returns the transcoder of the media, when it is transcoded
+    pub fn transcoder(&self) -> Option<&Transcoder> {
+        self.transcoder.as_ref()
+    }
+
+    #[doc(hidden)]
+    pub fn cover_art_uri(&self) -> Option<String> {
+        self.cover_art_file_uri()
+            .map(|file_uri| format!("{}/{}", self.video_file.host_uri, file_uri))
+    }
+
+    fn cover_art_file_uri(&self) -> Option<String> {
+        let cover_art = self.tags.as_ref()?.cover_art.as_ref()?;
+        Some(format!(
+            "{}.cover.{}",
+            self.video_file.file_uri,
+            cover_art.extension()
+        ))
+    }
+}
+
 /// A media streaming server
 #[derive(Debug, Clone)]
 pub struct MediaStreamingServer {
-    video_file: MediaFile,
-    subtitle_file: Option<MediaFile>,
+    media_items: Vec<MediaItem>,
     server_addr: SocketAddr,
+    subtitle_options: SubtitleOptions,
+    event_sender: Option<mpsc::UnboundedSender<EventNotification>>,
+    quirks: Quirks,
 }
 
 impl MediaStreamingServer {
@@ -43,6 +307,28 @@ impl MediaStreamingServer {
         subtitle_path: &Option<std::path::PathBuf>,
         host_ip: &String,
         host_port: &u32,
+    ) -> Result<Self> {
+        let subtitles = subtitle_path
+            .iter()
+            .map(|subtitle_path| SubtitleCandidate::from_file(subtitle_path, video_path))
+            .collect::<Vec<_>>();
+        Self::new_with_options(
+            video_path,
+            &subtitles,
+            host_ip,
+            host_port,
+            SubtitleOptions::default(),
+        )
+    }
+
+    /// Create a new media streaming server, with several subtitle files for the video, converting
+    /// the subtitles of all its media according to `subtitle_options`
+    pub fn new_with_options(
+        video_path: &std::path::Path,
+        subtitles: &[SubtitleCandidate],
+        host_ip: &String,
+        host_port: &u32,
+        subtitle_options: SubtitleOptions,
     ) -> Result<Self> {
         let server_addr_str = format!("{}:{}", host_ip, host_port);
         let server_addr: SocketAddr = server_addr_str
@@ -51,57 +337,121 @@ impl MediaStreamingServer {
 
         debug!("Streaming server address: {}", server_addr);
 
+        let mut media_streaming_server = Self {
+            media_items: Vec::new(),
+            server_addr,
+            subtitle_options,
+            event_sender: None,
+            quirks: Quirks::default(),
+        };
+        media_streaming_server.add_media_with_subtitles(video_path, subtitles)?;
+
+        Ok(media_streaming_server)
+    }
+
+    /// Adds another media file (and its optional subtitle) to be served, after the ones already added
+    pub fn add_media(
+        &mut self,
+        video_path: &std::path::Path,
+        subtitle_path: &Option<std::path::PathBuf>,
+    ) -> Result<()> {
+        let subtitles = subtitle_path
+            .iter()
+            .map(|subtitle_path| SubtitleCandidate::from_file(subtitle_path, video_path))
+            .collect::<Vec<_>>();
+        self.add_media_with_subtitles(video_path, &subtitles)
+    }
+
+    /// Adds another media file, with several subtitle files (e.g. in different languages, the most
+    /// relevant first), to be served after the ones already added
+    pub fn add_media_with_subtitles(
+        &mut self,
+        video_path: &std::path::Path,
+        subtitles: &[SubtitleCandidate],
+    ) -> Result<()> {
         debug!("Creating video file route in streaming server");
-        let video_file = match video_path.exists() {
-            true => MediaFile {
-                file_path: video_path.to_path_buf(),
-                host_uri: format!("http://{}", server_addr),
-                file_uri: slugify!(video_path.display().to_string().as_str(), separator = "."),
+        let mut video_file = MediaFile::new(video_path, &self.server_addr)?;
+        if video_file.profile.kind == MediaKind::Unknown {
+            return Err(Error::StreamingUnknownMediaType(
+                video_path.display().to_string(),
+            ));
+        }
+        self.make_unique(&mut video_file, &[]);
+
+        debug!("Creating subtitle file routes in streaming server");
+        let mut subtitle_files: Vec<MediaFile> = Vec::new();
+        for subtitle in subtitles {
+            let mut subtitle_file = MediaFile::new(&subtitle.path, &self.server_addr)?;
+            subtitle_file.language = subtitle.language.as_deref().map(language_tag);
+            subtitle_file.convert_subtitles(&self.subtitle_options);
+            // the URI is only final once converted, as the conversion changes its extension
+            let added_files = std::iter::once(&video_file)
+                .chain(&subtitle_files)
+                .collect::<Vec<_>>();
+            self.make_unique(&mut subtitle_file, &added_files);
+            subtitle_files.push(subtitle_file);
+        }
+        if subtitle_files.is_empty()
+            && self.subtitle_options.embedded
+            && video_file.profile.kind == MediaKind::Video
+        {
+            subtitle_files = self.embedded_subtitle_files(&video_file);
+        }
+
+        let tags = match video_file.profile.kind {
+            MediaKind::Audio => match AudioTags::read(video_path) {
+                Ok(tags) => Some(tags),
+                Err(err) => {
+                    warn!("{}", err);
+                    None
+                }
             },
-            false => {
-                return Err(Error::StreamingFileDoesNotExist(
-                    video_path.display().to_string(),
-                ));
-            }
+            _ => None,
         };
 
-        debug!("Creating subtitle file route in streaming server");
-        let subtitle_file = match subtitle_path {
-            Some(subtitle_path) => match subtitle_path.exists() {
-                true => Some(MediaFile {
-                    file_path: subtitle_path.clone(),
-                    host_uri: format!("http://{}", server_addr),
-                    file_uri: slugify!(
-                        subtitle_path.display().to_string().as_str(),
-                        separator = "."
-                    ),
-                }),
-                false => {
-                    return Err(Error::StreamingFileDoesNotExist(
-                        subtitle_path.display().to_string(),
-                    ));
+        let probe = match video_file.profile.kind.is_av() {
+            true => match MediaProbe::probe(video_path) {
+                Ok(probe) => Some(probe),
+                Err(err) => {
+                    warn!("{}", err);
+                    None
                 }
             },
-            None => None,
+            false => None,
         };
 
-        Ok(Self {
+        self.media_items.push(MediaItem {
             video_file,
-            subtitle_file,
-            server_addr,
-        })
+            subtitle_files,
+            tags,
+            probe,
+            transcoder: None,
+            burned_subtitles: None,
+        });
+        Ok(())
+    }
+
+    /// Returns the media items being served, in the order they were added
+    pub fn media_items(&self) -> &[MediaItem] {
+        &self.media_items
+    }
+
+    /// Returns the media items being served, to be changed before running the server (e.g. to transcode them)
+    pub fn media_items_mut(&mut self) -> &mut [MediaItem] {
+        &mut self.media_items
     }
 
     #[doc(hidden)]
+    #[deprecated(note = "use the `video_uri` of the media items instead")]
     pub fn video_uri(&self) -> String {
-        format!("{}/{}", self.video_file.host_uri, self.video_file.file_uri)
+        self.media_items[0].video_uri()
     }
 
     #[doc(hidden)]
+    #[deprecated(note = "use the `video_path` of the media items instead")]
     pub fn video_type(&self) -> String {
-        self.video_file
-            .file_path
-            .as_path()
+        self.media_items[0]
+            .video_path()
             .extension()
             .unwrap_or_default()
             .to_str()
@@ -110,51 +460,206 @@ impl MediaStreamingServer {
     }
 
     #[doc(hidden)]
+    #[deprecated(note = "use the `subtitle_uri` of the media items instead")]
     pub fn subtitle_uri(&self) -> Option<String> {
-        self.subtitle_file
-            .clone()
-            .map(|subtitle_file| format!("{}/{}", subtitle_file.host_uri, subtitle_file.file_uri))
+        self.media_items[0].subtitle_uri()
     }
 
     #[doc(hidden)]
+    #[deprecated(note = "use the `subtitle_type` of the media items instead")]
     pub fn subtitle_type(&self) -> Option<String> {
-        self.subtitle_file.clone().map(|subtitle_file| {
-            subtitle_file
-                .file_path
-                .as_path()
-                .extension()
-                .unwrap_or_default()
-                .to_str()
-                .unwrap_or_default()
-                .to_string()
-        })
+        self.media_items[0].subtitle_type()
+    }
+
+    /// Extracts the subtitle tracks embedded in a video (or only the selected one), the most relevant
+    /// first, to be served as subtitle files
+    fn embedded_subtitle_files(&self, video_file: &MediaFile) -> Vec<MediaFile> {
+        let subtitle_options = &self.subtitle_options;
+        let tracks = match list_subtitle_tracks(&video_file.file_path) {
+            Ok(tracks) => tracks,
+            Err(err) => {
+                warn!("{}", err);
+                return Vec::new();
+            }
+        };
+        let tracks = match &subtitle_options.track {
+            Some(selector) => {
+                match SubtitleTrackSelector::select(
+                    Some(selector),
+                    &tracks,
+                    &subtitle_options.languages,
+                ) {
+                    Some(track) => vec![track.clone()],
+                    None => {
+                        warn!(
+                            "No subtitle track '{}' in '{}'",
+                            selector,
+                            video_file.file_path.display()
+                        );
+                        return Vec::new();
+                    }
+                }
+            }
+            None => rank_subtitle_tracks(&tracks, &subtitle_options.languages)
+                .into_iter()
+                .cloned()
+                .collect(),
+        };
+        if tracks.is_empty() {
+            debug!(
+                "No embedded subtitle track in '{}'",
+                video_file.file_path.display()
+            );
+            return Vec::new();
+        }
+        let tracks_subtitles = match extract_subtitle_tracks(&video_file.file_path, &tracks) {
+            Ok(tracks_subtitles) => tracks_subtitles,
+            Err(err) => {
+                warn!("{}", err);
+                return Vec::new();
+            }
+        };
+
+        let mut subtitle_files = Vec::new();
+        for (track, subtitles) in tracks.iter().zip(tracks_subtitles) {
+            if subtitles.cues.is_empty() {
+                warn!("The subtitle track {} has no subtitles", track);
+                continue;
+            }
+            let mut subtitle_file = video_file.clone();
+            subtitle_file.file_uri = format!("{}.track{}.srt", video_file.file_uri, track.index);
+            subtitle_file.language = track.language.as_deref().map(language_tag);
+            subtitle_file.serve_subtitles(subtitles, subtitle_options);
+            subtitle_files.push(subtitle_file);
+        }
+        subtitle_files
+    }
+
+    /// Changes the URI of a media file so that it does not collide with the ones already served, nor
+    /// with the files being added with it
+    fn make_unique(&self, media_file: &mut MediaFile, added_files: &[&MediaFile]) {
+        let is_served = |file_uri: &String| {
+            added_files
+                .iter()
+                .any(|added_file| added_file.file_uri == *file_uri)
+                || self.media_items.iter().any(|media_item| {
+                    media_item.video_file.file_uri == *file_uri
+                        || media_item
+                            .subtitle_files
+                            .iter()
+                            .any(|subtitle_file| subtitle_file.file_uri == *file_uri)
+                })
+        };
+        let file_uri = media_file.file_uri.clone();
+        let mut prefix = self.media_items.len();
+        while is_served(&media_file.file_uri) {
+            media_file.file_uri = format!("{}.{}", prefix, file_uri);
+            prefix += 1;
+        }
+    }
+
+    /// Enables the endpoint where renders notify their events, to be used with [`RenderEvents`](crate::RenderEvents).
+    ///
+    /// It must be called before running the server.
+    pub fn event_callback(&mut self) -> EventCallback {
+        let (sender, receiver) = mpsc::unbounded();
+        self.event_sender = Some(sender);
+        EventCallback {
+            uri: format!("http://{}/{}", self.server_addr, EVENTS_PATH),
+            receiver,
+        }
+    }
+
+    /// Sets the quirks of the render the media are served to, which change the headers of the responses.
+    ///
+    /// It must be called before running the server.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
     }
 
-    #[allow(clippy::unnecessary_to_owned)]
     fn get_routes(
         &self,
     ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
-        let video_route = warp::path(self.video_file.file_uri.to_string())
-            .and(warp::fs::file(self.video_file.file_path.clone()));
-
-        info!("Video file: {}", self.video_file.file_path.display());
-        debug!("Serving video file: {}", self.video_file);
+        let mut media_routes = Vec::new();
+        for media_item in &self.media_items {
+            info!("Media file: {}", media_item.video_file.file_path.display());
+            match &media_item.transcoder {
+                Some(transcoder) => {
+                    debug!(
+                        "Serving video file transcoded to {}: {}",
+                        transcoder.profile(),
+                        media_item.video_file
+                    );
+                    media_routes.push(transcode_route(
+                        media_item,
+                        transcoder.clone(),
+                        &self.quirks,
+                    ));
+                }
+                None => {
+                    debug!("Serving video file: {}", media_item.video_file);
+                    media_routes.push(file_route(
+                        &media_item.video_file,
+                        media_item.subtitle_uri(),
+                        &self.quirks,
+                    ));
+                }
+            }
 
-        let subtitle_route = match &self.subtitle_file {
-            Some(subtitle_file) => {
-                info!("Subtitle file: {}", subtitle_file.file_path.display());
+            if media_item.subtitle_files.is_empty() {
+                info!("No subtitle file");
+            }
+            for subtitle_file in &media_item.subtitle_files {
+                match &subtitle_file.language {
+                    Some(language) => info!(
+                        "Subtitle file: {} ({})",
+                        subtitle_file.file_path.display(),
+                        language
+                    ),
+                    None => info!("Subtitle file: {}", subtitle_file.file_path.display()),
+                }
                 debug!("Serving subtitle file: {}", subtitle_file);
-                warp::path(subtitle_file.file_uri.to_string())
-                    .and(warp::fs::file(subtitle_file.file_path.clone()))
+                media_routes.push(file_route(subtitle_file, None, &self.quirks));
             }
-            None => {
-                info!("No subtitle file");
-                warp::path("dummy.srt".to_string())
-                    .and(warp::fs::file(self.video_file.file_path.clone()))
+
+            if let Some(cover_art_route) = cover_art_route(media_item, &self.quirks) {
+                media_routes.push(cover_art_route);
             }
-        };
+        }
+        let media_routes = media_routes
+            .into_iter()
+            .reduce(|routes, route| routes.or(route).unify().boxed())
+            .expect("the streaming server has at least one media item");
+
+        let event_sender = self.event_sender.clone();
+        let events_route = warp::method()
+            .and(warp::path(EVENTS_PATH))
+            .and(warp::header::optional::<String>("sid"))
+            .and(warp::body::bytes())
+            .and_then(move |method: Method, sid: Option<String>, body: Bytes| {
+                let event_sender = event_sender.clone();
+                async move {
+                    match (method.as_str(), event_sender) {
+                        ("NOTIFY", Some(event_sender)) => {
+                            debug!("Received event notification from {:?}", sid);
+                            event_sender
+                                .unbounded_send(EventNotification {
+                                    sid: sid.unwrap_or_default(),
+                                    body: String::from_utf8_lossy(&body).to_string(),
+                                })
+                                .ok();
+                            Ok(StatusCode::OK)
+                        }
+                        _ => Err(warp::reject::not_found()),
+                    }
+                }
+            });
 
-        warp::get().and(video_route.or(subtitle_route))
+        warp::get()
+            .or(warp::head())
+            .unify()
+            .and(media_routes)
+            .or(events_route)
     }
 
     /// Start the media streaming server.
@@ -162,6 +667,193 @@ impl MediaStreamingServer {
         let streaming_routes = self.get_routes();
         warp::serve(streaming_routes).run(self.server_addr).await;
     }
+
+    /// Start the media streaming server, gracefully shutting it down once `signal` completes.
+    pub async fn run_until(&self, signal: impl Future<Output = ()> + Send + 'static) {
+        let streaming_routes = self.get_routes();
+        let (_, server) =
+            warp::serve(streaming_routes).bind_with_graceful_shutdown(self.server_addr, signal);
+        server.await;
+    }
+}
+
+/// The DLNA headers requested by a render for a media file
+#[derive(Debug, Clone)]
+struct DlnaRequest {
+    /// Whether `contentFeatures.dlna.org` is requested
+    content_features: bool,
+    /// Whether `CaptionInfo.sec` is requested
+    caption_info: bool,
+    /// The requested transfer mode
+    transfer_mode: Option<String>,
+}
+
+/// Extracts the DLNA headers of a request
+fn dlna_request() -> impl Filter<Extract = (DlnaRequest,), Error = warp::Rejection> + Clone {
+    let requested = |value: Option<String>| value.is_some_and(|value| value.trim() == "1");
+    warp::header::optional::<String>("getcontentFeatures.dlna.org")
+        .and(warp::header::optional::<String>("getCaptionInfo.sec"))
+        .and(warp::header::optional::<String>("transferMode.dlna.org"))
+        .map(
+            move |content_features, caption_info, transfer_mode| DlnaRequest {
+                content_features: requested(content_features),
+                caption_info: requested(caption_info),
+                transfer_mode,
+            },
+        )
+}
+
+/// What the DLNA headers of the responses of a media file are made of
+#[derive(Debug, Clone)]
+struct DlnaHeaders {
+    profile: MediaProfile,
+    /// The URI of the subtitles of a video, announced through `CaptionInfo.sec`
+    caption_uri: Option<String>,
+    quirks: Quirks,
+}
+
+#[allow(clippy::unnecessary_to_owned)]
+fn file_route(
+    media_file: &MediaFile,
+    caption_uri: Option<String>,
+    quirks: &Quirks,
+) -> BoxedFilter<(Response,)> {
+    let headers = DlnaHeaders {
+        profile: media_file.profile,
+        caption_uri,
+        quirks: quirks.clone(),
+    };
+    if let Some(content) = &media_file.content {
+        return content_route(media_file.file_uri.to_string(), content.clone(), headers);
+    }
+    warp::path(media_file.file_uri.to_string())
+        .and(dlna_request())
+        .and(warp::fs::file(media_file.file_path.clone()))
+        .map(move |request: DlnaRequest, file: File| {
+            dlna_response(file.into_response(), &headers, request)
+        })
+        .boxed()
+}
+
+/// Serves a media transcoded on the fly by ffmpeg, started for each request, as a chunked response
+fn transcode_route(
+    media_item: &MediaItem,
+    transcoder: Transcoder,
+    quirks: &Quirks,
+) -> BoxedFilter<(Response,)> {
+    let headers = DlnaHeaders {
+        profile: media_item.video_file.profile,
+        caption_uri: media_item.subtitle_uri(),
+        quirks: quirks.clone(),
+    };
+    let file_uri = media_item.video_file.file_uri.clone();
+    let file_path = media_item.video_file.file_path.clone();
+    let burned_subtitles = media_item.burned_subtitles.clone();
+    warp::path(file_uri)
+        .and(warp::path::end())
+        .and(warp::method())
+        .and(dlna_request())
+        .map(move |method: Method, request: DlnaRequest| {
+            let response = match method {
+                Method::HEAD => Response::new(Body::empty()),
+                _ => match transcoder.spawn(&file_path, burned_subtitles.as_deref()) {
+                    Ok(output) => Response::new(Body::wrap_stream(output)),
+                    Err(err) => {
+                        warn!("{}", err);
+                        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+                    }
+                },
+            };
+            dlna_response(response, &headers, request)
+        })
+        .boxed()
+}
+
+/// Serves the cover art embedded in an audio file
+fn cover_art_route(media_item: &MediaItem, quirks: &Quirks) -> Option<BoxedFilter<(Response,)>> {
+    let file_uri = media_item.cover_art_file_uri()?;
+    let cover_art = media_item.tags.as_ref()?.cover_art.clone()?;
+    let headers = DlnaHeaders {
+        profile: MediaProfile::from_extension(cover_art.extension()),
+        caption_uri: None,
+        quirks: quirks.clone(),
+    };
+    debug!("Serving cover art: {}", file_uri);
+    Some(content_route(
+        file_uri,
+        Bytes::from(cover_art.data),
+        headers,
+    ))
+}
+
+/// Serves content held in memory, such as cover art or converted subtitles
+fn content_route(
+    file_uri: String,
+    content: Bytes,
+    headers: DlnaHeaders,
+) -> BoxedFilter<(Response,)> {
+    warp::path(file_uri)
+        .and(warp::path::end())
+        .and(dlna_request())
+        .map(move |request: DlnaRequest| {
+            dlna_response(Response::new(content.clone().into()), &headers, request)
+        })
+        .boxed()
+}
+
+/// Adds the DLNA headers to the response of a media file, honouring the ones of the request and the
+/// quirks of the render
+fn dlna_response(mut response: Response, headers: &DlnaHeaders, request: DlnaRequest) -> Response {
+    let profile = &headers.profile;
+    let quirks = &headers.quirks;
+    let kind = profile.kind;
+    let transfer_mode = match request.transfer_mode {
+        Some(requested) => match TransferMode::from_header(&requested) {
+            Some(transfer_mode) if transfer_mode.supports(kind) => transfer_mode,
+            _ => {
+                warn!("Rejecting unsupported transfer mode '{}'", requested);
+                return StatusCode::NOT_ACCEPTABLE.into_response();
+            }
+        },
+        None => TransferMode::default_for(kind),
+    };
+
+    let content_type = match &quirks.subtitle_mime {
+        Some(subtitle_mime) if kind == MediaKind::Subtitle => {
+            HeaderValue::from_str(subtitle_mime).ok()
+        }
+        _ => None,
+    };
+    let response_headers = response.headers_mut();
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        content_type.unwrap_or_else(|| HeaderValue::from_static(profile.mime)),
+    );
+    response_headers.insert(
+        "transferMode.dlna.org",
+        HeaderValue::from_static(transfer_mode.as_str()),
+    );
+    if request.content_features || quirks.content_features_header {
+        if let Ok(content_features) = HeaderValue::from_str(&profile.content_features()) {
+            response_headers.insert("contentFeatures.dlna.org", content_features);
+        }
+    }
+    if request.caption_info || quirks.caption_info_header {
+        if let Some(caption_uri) = headers
+            .caption_uri
+            .as_deref()
+            .and_then(|caption_uri| HeaderValue::from_str(caption_uri).ok())
+        {
+            response_headers.insert("CaptionInfo.sec", caption_uri);
+        }
+    }
+    if kind.is_av() {
+        response_headers.insert(
+            "realTimeInfo.dlna.org",
+            HeaderValue::from_static("DLNA.ORG_TLAG=*"),
+        );
+    }
+    response
 }
 
 /// Identifies the local serve IP address.
@@ -173,25 +865,125 @@ pub async fn get_local_ip() -> Result<String> {
 }
 
 /// Infer the subtitle file path from the video file path.
+///
+/// It is the most relevant of the [`find_subtitle_candidates`](crate::find_subtitle_candidates), without language preference.
 pub fn infer_subtitle_from_video(video_path: &std::path::Path) -> Option<std::path::PathBuf> {
+    infer_subtitle_from_video_with_languages(video_path, &[])
+}
+
+/// Infer the subtitle file path from the video file path, preferring the subtitles in some `languages` (e.g. `en`, `pt-BR`).
+pub fn infer_subtitle_from_video_with_languages(
+    video_path: &std::path::Path,
+    languages: &[String],
+) -> Option<std::path::PathBuf> {
     debug!(
         "Inferring subtitle file from video file: {}",
         video_path.display()
     );
-    let infered_subtitle_path = video_path.with_extension("srt");
-    debug!(
-        "Inferred subtitle file: {}",
-        infered_subtitle_path.display()
-    );
-    match infered_subtitle_path.exists() {
-        true => Some(infered_subtitle_path),
-        false => {
+    let candidates = find_subtitle_candidates(video_path, languages);
+    for candidate in &candidates {
+        debug!("Subtitle file candidate: {:?}", candidate);
+    }
+    match candidates.into_iter().next() {
+        Some(candidate) => {
+            debug!("Inferred subtitle file: {}", candidate.path.display());
+            Some(candidate.path)
+        }
+        None => {
             warn!(
-                "Tried inferring subtitle file from video file '{}', but it does not exist: '{}'",
-                video_path.display(),
-                infered_subtitle_path.display()
+                "Tried inferring subtitle file from video file '{}', but none was found",
+                video_path.display()
             );
             None
         }
     }
 }
+
+/// Infer the subtitle files of a video from its path, keeping the most relevant one of each language
+/// (and of its forced subtitles), with the ones in the preferred `languages` first.
+pub fn infer_subtitles_from_video(
+    video_path: &std::path::Path,
+    languages: &[String],
+) -> Vec<SubtitleCandidate> {
+    debug!(
+        "Inferring subtitle files from video file: {}",
+        video_path.display()
+    );
+    let mut subtitles: Vec<SubtitleCandidate> = Vec::new();
+    for candidate in find_subtitle_candidates(video_path, languages) {
+        debug!("Subtitle file candidate: {:?}", candidate);
+        let language = candidate.language.as_deref().map(language_tag);
+        let is_duplicate = subtitles.iter().any(|subtitle| {
+            subtitle.language.as_deref().map(language_tag) == language
+                && subtitle.forced == candidate.forced
+        });
+        if !is_duplicate {
+            debug!("Inferred subtitle file: {}", candidate.path.display());
+            subtitles.push(candidate);
+        }
+    }
+    if subtitles.is_empty() {
+        debug!(
+            "No subtitle file found for video file '{}'",
+            video_path.display()
+        );
+    }
+    subtitles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::with_temp_dir;
+
+    const SRT: &[u8] = b"1\n00:00:01,000 --> 00:00:02,000\nHello\n";
+    const ASS: &[u8] = b"[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.00,Default,,0,0,0,,Hello\n";
+
+    fn file_uris(media_streaming_server: &MediaStreamingServer) -> Vec<String> {
+        media_streaming_server
+            .media_items()
+            .iter()
+            .flat_map(|media_item| {
+                std::iter::once(&media_item.video_file).chain(&media_item.subtitle_files)
+            })
+            .map(|media_file| media_file.file_uri.clone())
+            .collect()
+    }
+
+    #[test]
+    fn unique_file_uris() {
+        let files: [(&str, &[u8]); 3] =
+            [("movie.mkv", b""), ("movie.srt", SRT), ("movie.ass", ASS)];
+        with_temp_dir(&files, |dir| {
+            let video_path = dir.join("movie.mkv");
+            let subtitles = [
+                SubtitleCandidate::from_file(&dir.join("movie.srt"), &video_path),
+                SubtitleCandidate::from_file(&dir.join("movie.ass"), &video_path),
+            ];
+            let mut media_streaming_server = MediaStreamingServer::new_with_options(
+                &video_path,
+                &subtitles,
+                &"127.0.0.1".to_string(),
+                &9000,
+                SubtitleOptions::default(),
+            )
+            .unwrap();
+            media_streaming_server
+                .add_media_with_subtitles(&video_path, &subtitles)
+                .unwrap();
+
+            let file_uris = file_uris(&media_streaming_server);
+            let srt_uri = slugify!(
+                dir.join("movie.srt").display().to_string().as_str(),
+                separator = "."
+            );
+            assert_eq!(file_uris[1], srt_uri);
+            // the ASS file is converted to SRT, as `movie.srt`
+            assert_eq!(file_uris[2], format!("0.{}", srt_uri));
+            let mut unique_uris = file_uris.clone();
+            unique_uris.sort();
+            unique_uris.dedup();
+            assert_eq!(unique_uris.len(), 6);
+        });
+    }
+}
@@ -0,0 +1,1184 @@
+use crate::{
+    embedded::SubtitleTrackSelector,
+    error::{Error, Result},
+};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use log::debug;
+use std::{
+    fmt,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// Frame rate assumed for MicroDVD subtitles which do not declare theirs
+const MICRODVD_FPS_DEFAULT: f64 = 23.976;
+
+/// Frame rates accepted from the first line of MicroDVD subtitles
+const MICRODVD_FPS_RANGE: RangeInclusive<f64> = 1.0..=1000.0;
+
+/// Duration of the last synchronization point of SAMI files, which no other point ends
+const SMI_LAST_CUE_DURATION: Duration = Duration::from_secs(5);
+
+/// Extensions of the subtitle files looked for next to videos, from the most to the least preferred
+const SUBTITLE_EXTENSIONS: [&str; 6] = ["srt", "ass", "ssa", "vtt", "sub", "smi"];
+
+/// Names of the directories where subtitles are looked for, in lowercase
+const SUBTITLE_DIRECTORIES: [&str; 2] = ["subs", "subtitles"];
+
+/// Tags of subtitle file names which look like language codes but are not
+const NON_LANGUAGE_TAGS: [&str; 3] = ["sdh", "cc", "hi"];
+
+/// Common languages, as their ISO 639-1 code, their ISO 639-2 codes and their English name
+const LANGUAGES: [(&str, &str, &str); 32] = [
+    ("ar", "ara", "arabic"),
+    ("bg", "bul", "bulgarian"),
+    ("ca", "cat", "catalan"),
+    ("cs", "ces/cze", "czech"),
+    ("da", "dan", "danish"),
+    ("de", "deu/ger", "german"),
+    ("el", "ell/gre", "greek"),
+    ("en", "eng", "english"),
+    ("es", "spa", "spanish"),
+    ("fa", "fas/per", "persian"),
+    ("fi", "fin", "finnish"),
+    ("fr", "fra/fre", "french"),
+    ("he", "heb", "hebrew"),
+    ("hi", "hin", "hindi"),
+    ("hr", "hrv", "croatian"),
+    ("hu", "hun", "hungarian"),
+    ("id", "ind", "indonesian"),
+    ("it", "ita", "italian"),
+    ("ja", "jpn", "japanese"),
+    ("ko", "kor", "korean"),
+    ("nl", "nld/dut", "dutch"),
+    ("no", "nor", "norwegian"),
+    ("pl", "pol", "polish"),
+    ("pt", "por", "portuguese"),
+    ("ro", "ron/rum", "romanian"),
+    ("ru", "rus", "russian"),
+    ("sk", "slk/slo", "slovak"),
+    ("sr", "srp", "serbian"),
+    ("sv", "swe", "swedish"),
+    ("tr", "tur", "turkish"),
+    ("uk", "ukr", "ukrainian"),
+    ("zh", "zho/chi", "chinese"),
+];
+
+/// Fields of the events of ASS/SSA subtitles, when their `[Events]` section has no `Format` line
+const ASS_FORMAT_DEFAULT: &str =
+    "Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text";
+
+/// A format of subtitles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    /// SubRip (`.srt`)
+    Srt,
+    /// WebVTT (`.vtt`)
+    WebVtt,
+    /// Advanced SubStation Alpha and SubStation Alpha (`.ass`, `.ssa`)
+    Ass,
+    /// MicroDVD (`.sub`)
+    MicroDvd,
+    /// SAMI (`.smi`), as understood by Samsung renders
+    Smi,
+}
+
+impl SubtitleFormat {
+    /// Detects the format of subtitles from the extension of their file, falling back to their content
+    pub fn detect(path: &Path, content: &str) -> Option<Self> {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::WebVtt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "smi" | "sami" => Some(Self::Smi),
+            _ => Self::from_content(content),
+        }
+    }
+
+    /// Detects the format of subtitles from their first lines
+    pub fn from_content(content: &str) -> Option<Self> {
+        let mut lines = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        let first_line = lines.next()?;
+        if first_line.starts_with("WEBVTT") {
+            Some(Self::WebVtt)
+        } else if first_line.eq_ignore_ascii_case("[script info]") {
+            Some(Self::Ass)
+        } else if first_line.to_uppercase().starts_with("<SAMI") {
+            Some(Self::Smi)
+        } else if parse_microdvd_line(first_line).is_some() {
+            Some(Self::MicroDvd)
+        } else if first_line.contains("-->") || lines.next()?.contains("-->") {
+            Some(Self::Srt)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the usual file extension of the format, also used as its type in DIDL-Lite
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Srt => "srt",
+            Self::WebVtt => "vtt",
+            Self::Ass => "ass",
+            Self::MicroDvd => "sub",
+            Self::Smi => "smi",
+        }
+    }
+}
+
+impl FromStr for SubtitleFormat {
+    type Err = Error;
+
+    /// Parses a format subtitles can be served in: `srt`, `vtt` or `smi`
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "srt" => Ok(Self::Srt),
+            "vtt" | "webvtt" => Ok(Self::WebVtt),
+            "smi" | "sami" => Ok(Self::Smi),
+            _ => Err(Error::SubtitlesFormatParseError(s.to_owned())),
+        }
+    }
+}
+
+impl fmt::Display for SubtitleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// A character encoding of subtitles, such as `utf-8`, `windows-1252` or `iso-8859-2`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubtitleEncoding(&'static Encoding);
+
+impl SubtitleEncoding {
+    /// The UTF-8 encoding, in which subtitles are served by default
+    pub const UTF_8: Self = Self(UTF_8);
+
+    /// Detects the encoding of the content of a subtitle file, from its byte order mark or else
+    /// from the frequency of its characters, and decodes it
+    pub fn decode(content: &[u8]) -> (Self, String) {
+        let (encoding, content) = match Encoding::for_bom(content) {
+            Some((encoding, bom_length)) => (encoding, &content[bom_length..]),
+            None => (detect_encoding(content), content),
+        };
+        let (text, _) = encoding.decode_without_bom_handling(content);
+        (Self(encoding), text.into_owned())
+    }
+
+    /// Encodes a text, with a byte order mark for UTF-16.
+    ///
+    /// Characters which do not exist in the encoding are written as numeric character references.
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        let utf16_units = || {
+            std::iter::once('\u{feff}')
+                .chain(text.chars())
+                .collect::<String>()
+        };
+        match self.0 {
+            encoding if encoding == UTF_16LE => utf16_units()
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes)
+                .collect(),
+            encoding if encoding == UTF_16BE => utf16_units()
+                .encode_utf16()
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            encoding => encoding.encode(text).0.into_owned(),
+        }
+    }
+
+    /// Returns the name of the encoding, as used in the `charset` of MIME types
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+}
+
+impl Default for SubtitleEncoding {
+    fn default() -> Self {
+        Self::UTF_8
+    }
+}
+
+impl FromStr for SubtitleEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Encoding::for_label(s.trim().as_bytes())
+            .map(Self)
+            .ok_or_else(|| Error::SubtitlesEncodingParseError(s.to_owned()))
+    }
+}
+
+impl fmt::Display for SubtitleEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name().to_lowercase())
+    }
+}
+
+/// How subtitles are converted before being served
+#[derive(Debug, Clone)]
+pub struct SubtitleOptions {
+    /// The format the subtitles are served in (`srt`, `vtt` or `smi`)
+    pub format: SubtitleFormat,
+    /// The character encoding the subtitles are served in
+    pub encoding: SubtitleEncoding,
+    /// The delay added to the subtitles, in milliseconds (negative to show them earlier)
+    pub delay: i64,
+    /// The frame rates of the video the subtitles were made for, and of the one being played
+    pub framerate: Option<(f64, f64)>,
+    /// Extract the subtitles embedded in videos which have no subtitle file
+    pub embedded: bool,
+    /// The embedded subtitle track to extract (if not provided, the most relevant one)
+    pub track: Option<SubtitleTrackSelector>,
+    /// The preferred languages of the embedded subtitle tracks
+    pub languages: Vec<String>,
+}
+
+impl Default for SubtitleOptions {
+    fn default() -> Self {
+        Self {
+            format: SubtitleFormat::Srt,
+            encoding: SubtitleEncoding::UTF_8,
+            delay: 0,
+            framerate: None,
+            embedded: false,
+            track: None,
+            languages: Vec::new(),
+        }
+    }
+}
+
+/// A subtitle shown between two instants of a media
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCue {
+    /// When the subtitle is shown
+    pub start: Duration,
+    /// When the subtitle is hidden
+    pub end: Duration,
+    /// The text of the subtitle, with lines separated by `\n` and styled with `<i>`, `<b>` and `<u>` tags
+    pub text: String,
+}
+
+/// The subtitles of a media, as a list of cues independent of their format
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Subtitles {
+    /// The cues of the subtitles, in order
+    pub cues: Vec<SubtitleCue>,
+}
+
+impl Subtitles {
+    /// Reads a subtitle file in SRT, WebVTT, ASS/SSA, MicroDVD or SAMI format, in any encoding.
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::from_text(path, &read_subtitle_text(path)?)
+    }
+
+    /// Parses the decoded text of a subtitle file, whose format is detected from its path and content.
+    pub fn from_text(path: &Path, content: &str) -> Result<Self> {
+        let unsupported = || Error::SubtitlesUnsupportedError(path.display().to_string());
+
+        let format = SubtitleFormat::detect(path, content).ok_or_else(unsupported)?;
+        let subtitles = Self::parse(content, format).ok_or_else(unsupported)?;
+        debug!(
+            "Read {} {} subtitle cues from '{}'",
+            subtitles.cues.len(),
+            format,
+            path.display()
+        );
+        Ok(subtitles)
+    }
+
+    /// Parses subtitles in a given format, returning `None` if the format can not be parsed or has no cues
+    pub fn parse(content: &str, format: SubtitleFormat) -> Option<Self> {
+        let mut cues = match format {
+            SubtitleFormat::Srt | SubtitleFormat::WebVtt => parse_timed_blocks(content, format),
+            SubtitleFormat::Ass => parse_ass(content),
+            SubtitleFormat::MicroDvd => parse_microdvd(content),
+            SubtitleFormat::Smi => parse_smi(content),
+        };
+        cues.retain(|cue| !cue.text.trim().is_empty() && cue.end >= cue.start);
+        cues.sort_by_key(|cue| cue.start);
+        (!cues.is_empty()).then_some(Self { cues })
+    }
+
+    /// Shifts the subtitles by a delay in milliseconds, after scaling them from a frame rate to another.
+    ///
+    /// Cues which end up before the beginning of the media are removed.
+    pub fn retime(&mut self, delay: i64, framerate: Option<(f64, f64)>) {
+        let scale = match framerate {
+            Some((fps_from, fps_to)) if fps_from > 0.0 && fps_to > 0.0 => fps_from / fps_to,
+            _ => 1.0,
+        };
+        let retime = |timestamp: Duration| {
+            let millis =
+                ((timestamp.as_millis() as f64 * scale).round() as i64).saturating_add(delay);
+            Duration::from_millis(millis.max(0) as u64)
+        };
+        for cue in &mut self.cues {
+            cue.start = retime(cue.start);
+            cue.end = retime(cue.end);
+        }
+        self.cues.retain(|cue| !cue.end.is_zero());
+    }
+
+    /// Serializes the subtitles in a format they can be served in (SRT, WebVTT or SAMI).
+    pub fn to_format(&self, format: SubtitleFormat) -> String {
+        match format {
+            SubtitleFormat::WebVtt => self.to_vtt(),
+            SubtitleFormat::Smi => self.to_smi(),
+            SubtitleFormat::Srt | SubtitleFormat::Ass | SubtitleFormat::MicroDvd => self.to_srt(),
+        }
+    }
+
+    /// Serializes the subtitles in SubRip format.
+    pub fn to_srt(&self) -> String {
+        self.cues
+            .iter()
+            .enumerate()
+            .map(|(index, cue)| {
+                format!(
+                    "{}\r\n{} --> {}\r\n{}\r\n\r\n",
+                    index + 1,
+                    format_timestamp(cue.start, ','),
+                    format_timestamp(cue.end, ','),
+                    cue.text.replace('\n', "\r\n")
+                )
+            })
+            .collect()
+    }
+
+    /// Serializes the subtitles in WebVTT format.
+    pub fn to_vtt(&self) -> String {
+        let cues = self
+            .cues
+            .iter()
+            .map(|cue| {
+                format!(
+                    "{} --> {}\n{}\n\n",
+                    format_timestamp(cue.start, '.'),
+                    format_timestamp(cue.end, '.'),
+                    cue.text
+                )
+            })
+            .collect::<String>();
+        format!("WEBVTT\n\n{}", cues)
+    }
+
+    /// Serializes the subtitles in SAMI format.
+    pub fn to_smi(&self) -> String {
+        let mut smi = String::from(
+            "<SAMI>\r\n<HEAD>\r\n<STYLE TYPE=\"text/css\">\r\n<!--\r\n\
+             P { margin-left: 8pt; margin-right: 8pt; text-align: center; font-family: sans-serif; color: white; }\r\n\
+             .SUBTTL { Name: Subtitles; SAMIType: CC; }\r\n\
+             -->\r\n</STYLE>\r\n</HEAD>\r\n<BODY>\r\n",
+        );
+        for (index, cue) in self.cues.iter().enumerate() {
+            smi.push_str(&format!(
+                "<SYNC Start={}><P Class=SUBTTL>{}\r\n",
+                cue.start.as_millis(),
+                cue.text.replace('\n', "<br>")
+            ));
+            let next_start = self.cues.get(index + 1).map(|next| next.start);
+            if next_start.is_none_or(|next_start| next_start > cue.end) {
+                smi.push_str(&format!(
+                    "<SYNC Start={}><P Class=SUBTTL>&nbsp;\r\n",
+                    cue.end.as_millis()
+                ));
+            }
+        }
+        smi.push_str("</BODY>\r\n</SAMI>\r\n");
+        smi
+    }
+}
+
+/// A subtitle file found for a video, by [`find_subtitle_candidates`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtitleCandidate {
+    /// The path of the subtitle file
+    pub path: PathBuf,
+    /// The language of the subtitles, as written in the file name (e.g. `en`, `pt-BR` or `English`)
+    pub language: Option<String>,
+    /// Whether the subtitles are forced, i.e. only translate foreign dialogues and signs
+    pub forced: bool,
+}
+
+impl SubtitleCandidate {
+    /// Create a candidate from a subtitle file of a video, whose language and forced flag are read
+    /// from the end of its name when it starts with the one of the video (`movie.en.forced.srt`).
+    pub fn from_file(subtitle_path: &Path, video_path: &Path) -> Self {
+        let video_stem = video_path.file_stem().and_then(|stem| stem.to_str());
+        subtitle_candidate(subtitle_path.to_path_buf(), video_stem).unwrap_or_else(|| Self {
+            path: subtitle_path.to_path_buf(),
+            language: None,
+            forced: false,
+        })
+    }
+}
+
+/// Finds the subtitle files of a video, ranked by relevance, with the preferred languages first.
+///
+/// Subtitles are looked for next to the video, named after it (`movie.srt`, `movie.en.srt`,
+/// `movie.pt-BR.forced.ass`, ...), and in its `Subs/` and `Subtitles/` directories, either
+/// named after it or in a directory named after it (`Subs/movie/English.srt`).
+///
+/// Subtitles in the preferred languages come first, in the order of `languages`, followed by the
+/// ones without language, and then by the ones in other languages. Forced subtitles come after the
+/// full ones, and SRT files before the formats that have to be converted.
+pub fn find_subtitle_candidates(video_path: &Path, languages: &[String]) -> Vec<SubtitleCandidate> {
+    let directory = video_path.parent().unwrap_or_else(|| Path::new("."));
+    let directory = match directory.as_os_str().is_empty() {
+        true => Path::new("."),
+        false => directory,
+    };
+    let video_stem = match video_path.file_stem().and_then(|stem| stem.to_str()) {
+        Some(video_stem) => video_stem,
+        None => return Vec::new(),
+    };
+
+    let mut candidates = Vec::new();
+    for path in subtitle_files(directory) {
+        candidates.extend(subtitle_candidate(path, Some(video_stem)));
+    }
+    let subtitle_directories = list_directory(directory)
+        .into_iter()
+        .filter(|path| path.is_dir())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            SUBTITLE_DIRECTORIES.contains(&name.to_lowercase().as_str())
+        });
+    for subtitle_directory in subtitle_directories {
+        for path in subtitle_files(&subtitle_directory) {
+            candidates.extend(subtitle_candidate(path, Some(video_stem)));
+        }
+        for path in subtitle_files(&subtitle_directory.join(video_stem)) {
+            candidates.extend(subtitle_candidate(path, None));
+        }
+    }
+
+    let format_rank = |candidate: &SubtitleCandidate| {
+        let extension = candidate
+            .path
+            .extension()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_lowercase();
+        SUBTITLE_EXTENSIONS
+            .iter()
+            .position(|subtitle_extension| *subtitle_extension == extension)
+    };
+    candidates.sort_by_cached_key(|candidate| {
+        (
+            language_rank(candidate.language.as_deref(), languages),
+            candidate.forced,
+            format_rank(candidate),
+            candidate.path.components().count(),
+            candidate.path.clone(),
+        )
+    });
+    candidates
+}
+
+/// Ranks a language of subtitles by the preferred `languages`: the preferred ones come first, in order,
+/// followed by an unknown language, and then by the other languages
+pub(crate) fn language_rank(language: Option<&str>, languages: &[String]) -> usize {
+    let preferred = languages.iter().enumerate().find_map(|(index, preferred)| {
+        let exact = match_language(language?, preferred)?;
+        Some(2 * index + !exact as usize)
+    });
+    match (preferred, language) {
+        (Some(rank), _) => rank,
+        (None, None) => 2 * languages.len(),
+        (None, Some(_)) => 2 * languages.len() + 1,
+    }
+}
+
+/// Whether a language matches a wanted one, returning whether it matches exactly (`pt-BR` for
+/// `pt-BR`) or only by its primary language (`pt-BR` for `pt`); codes and names are equivalent (`eng`, `English`, `en`)
+pub(crate) fn match_language(language: &str, wanted: &str) -> Option<bool> {
+    let (primary, region) = split_language(language);
+    let (wanted_primary, wanted_region) = split_language(wanted);
+    (primary == wanted_primary).then(|| region == wanted_region)
+}
+
+/// Normalizes a language as a tag with an ISO 639-1 code when known (`en` for `eng` or `English`,
+/// `pt-BR` for `por-br`), as announced to renders
+pub(crate) fn language_tag(language: &str) -> String {
+    match split_language(language) {
+        (primary, Some(region)) if region.len() == 2 => {
+            format!("{}-{}", primary, region.to_uppercase())
+        }
+        (primary, Some(region)) => format!("{}-{}", primary, region),
+        (primary, None) => primary,
+    }
+}
+
+/// Builds a candidate from a subtitle file, whose name must start with the one of the video when given
+fn subtitle_candidate(path: PathBuf, video_stem: Option<&str>) -> Option<SubtitleCandidate> {
+    let stem = path.file_stem()?.to_str()?;
+    let tags = match video_stem {
+        Some(video_stem) if stem == video_stem => "",
+        Some(video_stem) => stem.strip_prefix(video_stem)?.strip_prefix('.')?,
+        None => stem,
+    };
+    let tags = tags
+        .split(['.', '_', ' ', '[', ']', '(', ')'])
+        .filter(|tag| !tag.is_empty())
+        .collect::<Vec<_>>();
+    let forced = tags.iter().any(|tag| tag.eq_ignore_ascii_case("forced"));
+    let language = tags
+        .iter()
+        .find(|tag| is_language(tag))
+        .map(|tag| tag.to_string());
+    Some(SubtitleCandidate {
+        path,
+        language,
+        forced,
+    })
+}
+
+/// Lists the subtitle files of a directory, which may not exist
+fn subtitle_files(directory: &Path) -> Vec<PathBuf> {
+    list_directory(directory)
+        .into_iter()
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            let extension = path.extension().unwrap_or_default().to_string_lossy();
+            SUBTITLE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        })
+        .collect()
+}
+
+fn list_directory(directory: &Path) -> Vec<PathBuf> {
+    match std::fs::read_dir(directory) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Whether a tag of a file name is a language: a code like `en`, `eng` or `pt-BR`, or a name like `English`
+fn is_language(tag: &str) -> bool {
+    let tag = tag.to_lowercase();
+    if LANGUAGES.iter().any(|(_, _, name)| *name == tag) {
+        return true;
+    }
+    let (primary, region) = match tag.split_once('-') {
+        Some((primary, region)) => (primary, Some(region)),
+        None => (tag.as_str(), None),
+    };
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && region.is_none_or(|region| {
+            (2..=4).contains(&region.len()) && region.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        && !NON_LANGUAGE_TAGS.contains(&tag.as_str())
+}
+
+/// Splits a language tag into its primary language, as an ISO 639-1 code when known, and its region
+fn split_language(language: &str) -> (String, Option<String>) {
+    let language = language.to_lowercase();
+    let (primary, region) = match language.split_once(['-', '_']) {
+        Some((primary, region)) => (primary, Some(region.to_string())),
+        None => (language.as_str(), None),
+    };
+    let primary = LANGUAGES
+        .iter()
+        .find(|(_, code, name)| code.split('/').any(|code| code == primary) || *name == primary)
+        .map(|(code, _, _)| code.to_string())
+        .unwrap_or_else(|| primary.to_string());
+    (primary, region)
+}
+
+/// Reads the text of a subtitle file, detecting its encoding.
+pub fn read_subtitle_text(path: &Path) -> Result<String> {
+    let content = std::fs::read(path)
+        .map_err(|err| Error::SubtitlesReadError(path.display().to_string(), err))?;
+    let (encoding, text) = SubtitleEncoding::decode(&content);
+    debug!(
+        "Encoding of subtitle file '{}': {}",
+        path.display(),
+        encoding
+    );
+    Ok(text)
+}
+
+/// Detects the encoding of a text without byte order mark
+fn detect_encoding(content: &[u8]) -> &'static Encoding {
+    if std::str::from_utf8(content).is_ok() {
+        return UTF_8;
+    }
+    // UTF-16 without byte order mark, detected from the zero high bytes of ASCII characters
+    let zeros_at = |parity: usize| {
+        content
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let half_length = content.len() / 2;
+    if half_length > 0 && zeros_at(1) > half_length / 2 && zeros_at(0) == 0 {
+        return UTF_16LE;
+    }
+    if half_length > 0 && zeros_at(0) > half_length / 2 && zeros_at(1) == 0 {
+        return UTF_16BE;
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(content, true);
+    detector.guess(None, true)
+}
+
+/// Parses a timestamp as `[HH:]MM:SS[.,]fff`, also accepting the centiseconds of ASS/SSA
+fn parse_timestamp(timestamp: &str) -> Option<Duration> {
+    let (clock, fraction) = match timestamp.trim().split_once(['.', ',']) {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (timestamp.trim(), ""),
+    };
+    let mut secs = 0u64;
+    for part in clock.split(':') {
+        secs = secs * 60 + part.trim().parse::<u64>().ok()?;
+    }
+    let millis = match fraction {
+        "" => 0,
+        _ => {
+            let digits = fraction.chars().take(3).collect::<String>();
+            let millis = digits.parse::<u64>().ok()?;
+            millis * 10u64.pow(3 - digits.len() as u32)
+        }
+    };
+    Some(Duration::from_secs(secs) + Duration::from_millis(millis))
+}
+
+/// Formats a timestamp as `HH:MM:SS,fff` (or with a `.` before the milliseconds)
+fn format_timestamp(timestamp: Duration, separator: char) -> String {
+    let millis = timestamp.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+/// Parses the cues of SRT and WebVTT files, made of blocks with a timing line followed by text
+fn parse_timed_blocks(content: &str, format: SubtitleFormat) -> Vec<SubtitleCue> {
+    let mut cues = Vec::new();
+    let mut lines = content.lines().map(|line| line.trim_end());
+    while let Some(line) = lines.next() {
+        let (start, end) = match line.split_once("-->") {
+            Some((start, end)) => (start, end),
+            None => continue,
+        };
+        // WebVTT cue settings and SRT coordinates follow the end timestamp
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (start, end) = match (parse_timestamp(start), parse_timestamp(end)) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        let text_lines = lines
+            .by_ref()
+            .take_while(|line| !line.trim().is_empty())
+            .map(|line| match format {
+                SubtitleFormat::WebVtt => vtt_text(line),
+                _ => line.to_string(),
+            })
+            .collect::<Vec<_>>();
+        cues.push(SubtitleCue {
+            start,
+            end,
+            text: text_lines.join("\n"),
+        });
+    }
+    cues
+}
+
+/// Converts the text of a WebVTT cue, keeping only the style tags SRT also has
+pub(crate) fn vtt_text(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(tag_start) = rest.find('<') {
+        let tag_end = match rest[tag_start..].find('>') {
+            Some(tag_end) => tag_start + tag_end,
+            None => break,
+        };
+        text.push_str(&rest[..tag_start]);
+        let tag = &rest[tag_start + 1..tag_end];
+        let name = tag.trim_start_matches('/').split(['.', ' ']).next();
+        if let Some(name @ ("i" | "b" | "u")) = name {
+            let closing = if tag.starts_with('/') { "/" } else { "" };
+            text.push_str(&format!("<{}{}>", closing, name));
+        }
+        rest = &rest[tag_end + 1..];
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Parses the dialogue events of ASS/SSA files
+fn parse_ass(content: &str) -> Vec<SubtitleCue> {
+    let mut cues = Vec::new();
+    let mut in_events = false;
+    let mut fields = ASS_FORMAT_DEFAULT
+        .split(',')
+        .map(|field| field.trim().to_lowercase())
+        .collect::<Vec<_>>();
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_events = line.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim_start()),
+            None => continue,
+        };
+        match key {
+            "Format" => {
+                fields = value
+                    .split(',')
+                    .map(|field| field.trim().to_lowercase())
+                    .collect()
+            }
+            "Dialogue" => {
+                let values = value.splitn(fields.len(), ',').collect::<Vec<_>>();
+                let field = |name: &str| {
+                    let index = fields.iter().position(|field| field == name)?;
+                    values.get(index).copied()
+                };
+                let cue = (|| {
+                    Some(SubtitleCue {
+                        start: parse_timestamp(field("start")?)?,
+                        end: parse_timestamp(field("end")?)?,
+                        text: ass_text(field("text")?)?,
+                    })
+                })();
+                cues.extend(cue);
+            }
+            _ => {}
+        }
+    }
+    cues
+}
+
+/// Converts the text of an ASS/SSA event, mapping its override tags to SRT style tags.
+///
+/// Returns `None` for drawings, which have no text to show.
+pub(crate) fn ass_text(text: &str) -> Option<String> {
+    let mut converted = String::new();
+    let mut open_tags: Vec<&str> = Vec::new();
+    let mut rest = text;
+    while let Some(block_start) = rest.find('{') {
+        let block_end = match rest[block_start..].find('}') {
+            Some(block_end) => block_start + block_end,
+            None => break,
+        };
+        converted.push_str(&rest[..block_start]);
+        for tag in rest[block_start + 1..block_end].split('\\') {
+            let (name, value) = match tag.char_indices().find(|(_, c)| !c.is_alphabetic()) {
+                Some((index, _)) => tag.split_at(index),
+                None => (tag, ""),
+            };
+            let enabled = value.trim().parse::<u32>().ok().map(|value| value > 0);
+            match (name, enabled) {
+                ("p", Some(true)) => return None,
+                ("i" | "b" | "u", Some(enabled)) => {
+                    let tag_name = name;
+                    let is_open = open_tags.contains(&tag_name);
+                    if enabled && !is_open {
+                        converted.push_str(&format!("<{}>", tag_name));
+                        open_tags.push(tag_name);
+                    } else if !enabled && is_open {
+                        converted.push_str(&format!("</{}>", tag_name));
+                        open_tags.retain(|open_tag| *open_tag != tag_name);
+                    }
+                }
+                _ => {}
+            }
+        }
+        rest = &rest[block_end + 1..];
+    }
+    converted.push_str(rest);
+    for tag_name in open_tags.iter().rev() {
+        converted.push_str(&format!("</{}>", tag_name));
+    }
+    Some(
+        converted
+            .replace("\\N", "\n")
+            .replace("\\n", "\n")
+            .replace("\\h", " ")
+            .lines()
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+/// Parses the synchronization points of SAMI files, keeping the paragraphs of their first class
+/// (usually the first language) and ending each one at the next point
+fn parse_smi(content: &str) -> Vec<SubtitleCue> {
+    // ASCII lowercasing keeps the byte offsets of the content
+    let lowercase = content.to_ascii_lowercase();
+    let body_end = lowercase.find("</body").unwrap_or(content.len());
+    let mut sync_points = Vec::new();
+    let mut first_class = None;
+    let mut search_start = 0;
+    while let Some(sync_start) = lowercase[search_start..body_end].find("<sync") {
+        let sync_start = search_start + sync_start;
+        let tag_end = match lowercase[sync_start..body_end].find('>') {
+            Some(tag_end) => sync_start + tag_end,
+            None => break,
+        };
+        let block_end = lowercase[tag_end..body_end]
+            .find("<sync")
+            .map_or(body_end, |block_end| tag_end + block_end);
+        search_start = block_end;
+
+        let start = match smi_attribute(&lowercase[sync_start..tag_end], "start")
+            .and_then(|start| start.parse::<u64>().ok())
+        {
+            Some(start) => Duration::from_millis(start),
+            None => continue,
+        };
+        let block = &content[tag_end + 1..block_end];
+        let class = lowercase[tag_end + 1..block_end]
+            .find("<p")
+            .and_then(|paragraph| {
+                let paragraph = &lowercase[tag_end + 1 + paragraph..block_end];
+                smi_attribute(&paragraph[..paragraph.find('>')?], "class")
+            });
+        if first_class.is_none() {
+            first_class = class;
+        } else if class.is_some() && class != first_class {
+            continue;
+        }
+        let mut text = block.replace(['\r', '\n'], " ");
+        for line_break in ["<br>", "<br/>", "<br />", "<BR>", "<BR/>", "<BR />"] {
+            text = text.replace(line_break, "\n");
+        }
+        sync_points.push((start, vtt_text(&text)));
+    }
+
+    sync_points
+        .iter()
+        .enumerate()
+        .map(|(index, (start, text))| SubtitleCue {
+            start: *start,
+            end: sync_points
+                .get(index + 1)
+                .map_or(*start + SMI_LAST_CUE_DURATION, |(next_start, _)| {
+                    *next_start
+                }),
+            text: text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .collect()
+}
+
+/// Reads the value of an attribute from a lowercase SAMI tag, unquoted
+fn smi_attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let value = tag.split(name).nth(1)?.trim_start().strip_prefix('=')?;
+    let value = value.trim_start().trim_start_matches(['"', '\'']);
+    value
+        .split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '>'))
+        .next()
+        .filter(|value| !value.is_empty())
+}
+
+/// Splits a MicroDVD line into its start frame, end frame and text
+fn parse_microdvd_line(line: &str) -> Option<(u64, Option<u64>, &str)> {
+    let rest = line.strip_prefix('{')?;
+    let (start, rest) = rest.split_once('}')?;
+    let rest = rest.strip_prefix('{')?;
+    let (end, text) = rest.split_once('}')?;
+    Some((start.parse().ok()?, end.parse().ok(), text))
+}
+
+/// Parses the frames of MicroDVD files, whose frame rate can be declared in their first line
+fn parse_microdvd(content: &str) -> Vec<SubtitleCue> {
+    let lines = content
+        .lines()
+        .filter_map(|line| parse_microdvd_line(line.trim()))
+        .collect::<Vec<_>>();
+    let declared_fps = lines
+        .first()
+        .and_then(|(_, _, text)| text.trim().parse::<f64>().ok())
+        .filter(|fps| MICRODVD_FPS_RANGE.contains(fps));
+    let fps = declared_fps.unwrap_or(MICRODVD_FPS_DEFAULT);
+    let frame_time = |frame: u64| Duration::try_from_secs_f64(frame as f64 / fps).ok();
+
+    lines
+        .iter()
+        .enumerate()
+        .skip(declared_fps.is_some() as usize)
+        .filter_map(|(index, (start, end, text))| {
+            // a missing end frame lasts until the next line
+            let end = end
+                .or_else(|| lines.get(index + 1).map(|(next_start, _, _)| *next_start))
+                .unwrap_or(*start);
+            Some(SubtitleCue {
+                start: frame_time(*start)?,
+                end: frame_time(end)?,
+                text: microdvd_text(text),
+            })
+        })
+        .collect()
+}
+
+/// Converts the text of a MicroDVD line, whose lines are separated by `|`
+fn microdvd_text(text: &str) -> String {
+    let (all_lines_styles, text) = microdvd_styles(text, 'Y');
+    text.split('|')
+        .map(|line| {
+            let (line_styles, line) = microdvd_styles(line, 'y');
+            let styles = [all_lines_styles.as_slice(), line_styles.as_slice()].concat();
+            let opening = styles.iter().map(|style| format!("<{}>", style));
+            let closing = styles.iter().rev().map(|style| format!("</{}>", style));
+            opening
+                .chain(std::iter::once(line.trim().to_string()))
+                .chain(closing)
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Strips the control codes at the beginning of a MicroDVD text, returning the style tags set by
+/// its `{y:...}` (one line) or `{Y:...}` (all lines) codes
+fn microdvd_styles(text: &str, code: char) -> (Vec<char>, &str) {
+    let mut styles = Vec::new();
+    let mut rest = text;
+    while let Some((control_code, after)) = rest
+        .strip_prefix('{')
+        .and_then(|inner| inner.split_once('}'))
+    {
+        if let Some(values) = control_code
+            .strip_prefix(code)
+            .and_then(|c| c.strip_prefix(':'))
+        {
+            styles.extend(
+                values
+                    .chars()
+                    .filter(|style| ['i', 'b', 'u'].contains(style)),
+            );
+        } else if control_code.starts_with(code.to_ascii_lowercase())
+            || control_code.starts_with(code.to_ascii_uppercase())
+        {
+            // the other scope of style codes, handled at its own level
+            break;
+        }
+        rest = after;
+    }
+    (styles, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cue(start_millis: u64, end_millis: u64, text: &str) -> SubtitleCue {
+        SubtitleCue {
+            start: Duration::from_millis(start_millis),
+            end: Duration::from_millis(end_millis),
+            text: text.to_string(),
+        }
+    }
+
+    fn parse(content: &str, format: SubtitleFormat) -> Vec<SubtitleCue> {
+        Subtitles::parse(content, format)
+            .map(|subtitles| subtitles.cues)
+            .unwrap_or_default()
+    }
+
+    const SRT: &str = "1\r\n00:00:01,000 --> 00:00:02,500\r\nFirst <i>line</i>\r\nSecond line\r\n\r\n2\r\n00:00:03,000 --> 00:00:04,000 X1:0\r\nLast\r\n";
+
+    const VTT: &str = "WEBVTT\n\nNOTE a comment\n\n00:01.000 --> 00:02.000 align:start\n<v Bob><c.yellow>Hi</c> <i>there</i> &amp; a < b\n";
+
+    const ASS: &str = "[Script Info]\nTitle: Test\n\n[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\nDialogue: 0,0:00:01.00,0:00:02.50,Default,,0,0,0,,{\\i1}Hi{\\i0}, you\\Nthere\nDialogue: 0,0:00:03.00,0:00:04.00,Default,,0,0,0,,{\\p1}m 0 0 l 100 0\n";
+
+    #[test]
+    fn detect_formats() {
+        let detect = |name: &str, content: &str| SubtitleFormat::detect(Path::new(name), content);
+        assert_eq!(detect("movie.srt", ""), Some(SubtitleFormat::Srt));
+        assert_eq!(detect("movie.txt", VTT), Some(SubtitleFormat::WebVtt));
+        assert_eq!(detect("movie.txt", ASS), Some(SubtitleFormat::Ass));
+        assert_eq!(
+            detect("movie.sub", "{1}{2}Hi"),
+            Some(SubtitleFormat::MicroDvd)
+        );
+        assert_eq!(detect("movie.txt", SRT), Some(SubtitleFormat::Srt));
+        assert_eq!(detect("movie.txt", "<SAMI>"), Some(SubtitleFormat::Smi));
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let subtitles = Subtitles::parse(SRT, SubtitleFormat::Srt).unwrap();
+        assert_eq!(
+            subtitles.cues,
+            [
+                cue(1000, 2500, "First <i>line</i>\nSecond line"),
+                cue(3000, 4000, "Last")
+            ]
+        );
+        let srt = subtitles.to_srt();
+        assert_eq!(
+            Subtitles::parse(&srt, SubtitleFormat::Srt).unwrap(),
+            subtitles
+        );
+    }
+
+    #[test]
+    fn parse_vtt() {
+        assert_eq!(
+            parse(VTT, SubtitleFormat::WebVtt),
+            [cue(1000, 2000, "Hi <i>there</i> & a < b")]
+        );
+    }
+
+    #[test]
+    fn vtt_text_with_unclosed_tag() {
+        assert_eq!(vtt_text("a < b"), "a < b");
+        assert_eq!(vtt_text("<b>a</b> <"), "<b>a</b> <");
+    }
+
+    #[test]
+    fn parse_ass_events() {
+        assert_eq!(
+            parse(ASS, SubtitleFormat::Ass),
+            [cue(1000, 2500, "<i>Hi</i>, you\nthere")]
+        );
+    }
+
+    #[test]
+    fn ass_text_with_unclosed_block() {
+        assert_eq!(ass_text("a { b").as_deref(), Some("a { b"));
+        assert_eq!(ass_text("{\\b1}a{\\b0} {").as_deref(), Some("<b>a</b> {"));
+        assert_eq!(ass_text("{\\p1}m 0 0 l 1 1"), None);
+    }
+
+    #[test]
+    fn parse_microdvd_with_declared_frame_rate() {
+        let content = "{1}{1}25.000\n{25}{}First|{y:i}Second\n{50}{75}Third\n";
+        assert_eq!(
+            parse(content, SubtitleFormat::MicroDvd),
+            [
+                cue(1000, 2000, "First\n<i>Second</i>"),
+                cue(2000, 3000, "Third")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_microdvd_with_default_frame_rate() {
+        let cues = parse("{0}{}A\n{2397}{}B\n", SubtitleFormat::MicroDvd);
+        assert_eq!(cues[0].end.as_millis(), 99_974);
+    }
+
+    #[test]
+    fn parse_microdvd_with_invalid_frame_rate() {
+        // the first line is not taken as a frame rate, but as a cue at the default frame rate
+        let content = "{1}{1}0.0000000000000000000001\n{2397}{4795}A\n";
+        assert_eq!(
+            parse(content, SubtitleFormat::MicroDvd)
+                .iter()
+                .map(|cue| (cue.start.as_secs(), cue.text.as_str()))
+                .collect::<Vec<_>>(),
+            [(0, "0.0000000000000000000001"), (99, "A")]
+        );
+        // frames too large for a duration are dropped
+        let content = "{1}{1}1\n{1}{18446744073709551615}A\n{2}{3}B\n";
+        assert_eq!(
+            parse(content, SubtitleFormat::MicroDvd),
+            [cue(2000, 3000, "B")]
+        );
+    }
+
+    #[test]
+    fn parse_sami() {
+        let content = "<SAMI><HEAD><STYLE><!-- .ENCC { lang: en-US; } --></STYLE></HEAD><BODY>\r\n\
+            <SYNC Start=1000><P Class=ENCC>Hello<br>world &amp; all\r\n\
+            <SYNC Start=1000><P Class=FRCC>Bonjour\r\n\
+            <SYNC Start=\"2500\"><P Class=ENCC>&nbsp;\r\n\
+            <SYNC Start=3000><P Class=ENCC><font color=\"red\">Last</font>\r\n\
+            </BODY></SAMI>";
+        assert_eq!(
+            parse(content, SubtitleFormat::Smi),
+            [
+                cue(1000, 2500, "Hello\nworld & all"),
+                cue(3000, 8000, "Last")
+            ]
+        );
+    }
+
+    #[test]
+    fn sami_round_trip() {
+        let subtitles = Subtitles::parse(SRT, SubtitleFormat::Srt).unwrap();
+        let smi = subtitles.to_smi();
+        assert_eq!(
+            Subtitles::parse(&smi, SubtitleFormat::Smi).unwrap().cues[..1],
+            subtitles.cues[..1]
+        );
+    }
+
+    #[test]
+    fn retime_cues() {
+        let mut subtitles = Subtitles {
+            cues: vec![cue(500, 900, "Dropped"), cue(25_000, 26_000, "Kept")],
+        };
+        subtitles.retime(-1000, Some((25.0, 23.976)));
+        assert_eq!(subtitles.cues, [cue(25_068, 26_110, "Kept")]);
+        subtitles.retime(i64::MAX, None);
+        assert_eq!(subtitles.cues[0].start.as_millis(), i64::MAX as u128);
+        subtitles.retime(i64::MIN, None);
+        assert!(subtitles.cues.is_empty());
+    }
+
+    #[test]
+    fn decode_encodings() {
+        let (encoding, text) = SubtitleEncoding::decode(&[0xff, 0xfe, b'H', 0, b'i', 0]);
+        assert_eq!((encoding.name(), text.as_str()), ("UTF-16LE", "Hi"));
+        let (_, text) = SubtitleEncoding::decode(b"caf\xe9 cr\xe8me br\xfbl\xe9e, d\xe9j\xe0 vu");
+        assert_eq!(text, "café crème brûlée, déjà vu");
+    }
+
+    #[test]
+    fn parse_truncated_and_invalid_content() {
+        let invalid = "99999999999999999999:00:00,000 --> 99:99:99999999999999999999,000\nA\n";
+        assert!(Subtitles::parse(invalid, SubtitleFormat::Srt).is_none());
+        for (content, format) in [
+            (SRT, SubtitleFormat::Srt),
+            (VTT, SubtitleFormat::WebVtt),
+            (ASS, SubtitleFormat::Ass),
+            ("{1}{1}25\n{25}{50}{y:i}A|B\n", SubtitleFormat::MicroDvd),
+            (
+                "<SAMI><BODY><SYNC Start=1><P Class=A>a<br>b",
+                SubtitleFormat::Smi,
+            ),
+        ] {
+            for (length, _) in content.char_indices() {
+                Subtitles::parse(&content[..length], format);
+            }
+        }
+    }
+}
@@ -0,0 +1,846 @@
+use crate::{
+    control::RelTime,
+    error::{Error, Result},
+};
+use base64::Engine;
+use log::debug;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    time::Duration,
+};
+
+/// Maximum size of the metadata read from the beginning of a file (cover art included)
+const METADATA_MAX_SIZE: u64 = 32 * 1024 * 1024;
+
+/// Length of the tail of an Ogg file read to find its last granule position
+const OGG_TAIL_LENGTH: u64 = 64 * 1024;
+
+/// Picture type of the front cover, in ID3v2 and FLAC pictures
+const PICTURE_TYPE_FRONT_COVER: u32 = 3;
+
+/// The cover art embedded in an audio file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverArt {
+    /// The MIME type of the image
+    pub mime: String,
+    /// The content of the image
+    pub data: Vec<u8>,
+}
+
+impl CoverArt {
+    /// Returns the file extension matching the MIME type of the image
+    pub fn extension(&self) -> &'static str {
+        match self.mime.as_str() {
+            "image/png" => "png",
+            _ => "jpg",
+        }
+    }
+}
+
+/// The tags of an audio file: ID3v2 (MP3), FLAC and Vorbis comments (FLAC, Ogg) and MP4 atoms.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioTags {
+    /// The title of the track
+    pub title: Option<String>,
+    /// The artist of the track
+    pub artist: Option<String>,
+    /// The album of the track
+    pub album: Option<String>,
+    /// The number of the track in its album
+    pub track_number: Option<u32>,
+    /// The duration of the track
+    pub duration: Option<RelTime>,
+    /// The front cover (or first picture) embedded in the file
+    pub cover_art: Option<CoverArt>,
+}
+
+impl AudioTags {
+    /// Reads the tags of an audio file, detecting the tag format from its content (other formats have no tags).
+    pub fn read(path: &Path) -> Result<Self> {
+        let read_error = |err| Error::TagsReadError(path.display().to_string(), err);
+        let mut file = File::open(path).map_err(read_error)?;
+        let mut magic = [0u8; 8];
+        let magic_length = file.read(&mut magic).map_err(read_error)?;
+        file.rewind().map_err(read_error)?;
+
+        let tags = match &magic[..magic_length] {
+            [b'f', b'L', b'a', b'C', ..] => read_flac(&mut file),
+            [b'O', b'g', b'g', b'S', ..] => read_ogg(&mut file),
+            [_, _, _, _, b'f', b't', b'y', b'p'] => read_mp4(&mut file),
+            [b'I', b'D', b'3', ..] => read_mp3(&mut file),
+            [0xff, sync, ..] if is_mp3_sync(*sync) => read_mp3(&mut file),
+            _ => Ok(Self::default()),
+        }
+        .map_err(read_error)?;
+        debug!(
+            "Tags of '{}': {:?} / {:?} / {:?} #{:?} ({:?}, cover art: {})",
+            path.display(),
+            tags.title,
+            tags.artist,
+            tags.album,
+            tags.track_number,
+            tags.duration,
+            tags.cover_art.is_some()
+        );
+        Ok(tags)
+    }
+
+    /// Sets the cover art, preferring front covers over other pictures
+    fn set_picture(&mut self, picture_type: u32, mime: String, data: Vec<u8>) {
+        if data.is_empty() || (self.cover_art.is_some() && picture_type != PICTURE_TYPE_FRONT_COVER)
+        {
+            return;
+        }
+        self.cover_art = Some(CoverArt { mime, data });
+    }
+
+    /// Sets a tag from a Vorbis comment (`KEY=value`)
+    fn set_vorbis_comment(&mut self, comment: &str) {
+        let (key, value) = match comment.split_once('=') {
+            Some((key, value)) => (key.to_uppercase(), value.trim().to_owned()),
+            None => return,
+        };
+        match key.as_str() {
+            "TITLE" => self.title = self.title.take().or(Some(value)),
+            "ARTIST" => self.artist = self.artist.take().or(Some(value)),
+            "ALBUM" => self.album = self.album.take().or(Some(value)),
+            "TRACKNUMBER" => self.track_number = parse_track_number(&value),
+            "METADATA_BLOCK_PICTURE" => {
+                if let Ok(block) = base64::engine::general_purpose::STANDARD.decode(value) {
+                    self.set_flac_picture(&block);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn set_vorbis_comments(&mut self, data: &[u8]) {
+        let mut reader = ByteReader::new(data);
+        let comments = (|| {
+            let vendor_length = reader.u32_le()? as usize;
+            reader.skip(vendor_length)?;
+            let count = reader.u32_le()?;
+            let mut comments = Vec::new();
+            for _ in 0..count {
+                let length = reader.u32_le()? as usize;
+                comments.push(String::from_utf8_lossy(reader.bytes(length)?).to_string());
+            }
+            Some(comments)
+        })();
+        for comment in comments.unwrap_or_default() {
+            self.set_vorbis_comment(&comment);
+        }
+    }
+
+    /// Sets the cover art from a FLAC picture block
+    fn set_flac_picture(&mut self, data: &[u8]) {
+        let mut reader = ByteReader::new(data);
+        let picture = (|| {
+            let picture_type = reader.u32_be()?;
+            let mime_length = reader.u32_be()? as usize;
+            let mime = String::from_utf8_lossy(reader.bytes(mime_length)?).to_string();
+            let description_length = reader.u32_be()? as usize;
+            // description, then width, height, depth and number of colors
+            reader.skip(description_length + 16)?;
+            let data_length = reader.u32_be()? as usize;
+            Some((picture_type, mime, reader.bytes(data_length)?.to_vec()))
+        })();
+        if let Some((picture_type, mime, data)) = picture {
+            self.set_picture(picture_type, mime, data);
+        }
+    }
+}
+
+/// A cursor over a slice of bytes, whose reads fail (returning `None`) past its end
+pub(crate) struct ByteReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub(crate) fn bytes(&mut self, length: usize) -> Option<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position.checked_add(length)?)?;
+        self.position += length;
+        Some(bytes)
+    }
+
+    pub(crate) fn skip(&mut self, length: usize) -> Option<()> {
+        self.bytes(length).map(|_| ())
+    }
+
+    pub(crate) fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub(crate) fn u16_be(&mut self) -> Option<u16> {
+        self.bytes(2)
+            .map(|bytes| u16::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32_be(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64_be(&mut self) -> Option<u64> {
+        self.bytes(8)
+            .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn u32_le(&mut self) -> Option<u32> {
+        self.bytes(4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.data.len().saturating_sub(self.position)
+    }
+
+    pub(crate) fn rest(&mut self) -> &'a [u8] {
+        let rest = &self.data[self.position.min(self.data.len())..];
+        self.position = self.data.len();
+        rest
+    }
+}
+
+fn parse_track_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+pub(crate) fn duration_from_samples(samples: u64, sample_rate: u32) -> Option<RelTime> {
+    let sample_rate = sample_rate as u64;
+    (sample_rate > 0).then(|| {
+        // in integers, so that any number of samples fits in a duration
+        let nanos = (samples % sample_rate) * 1_000_000_000 / sample_rate;
+        RelTime::from(Duration::new(samples / sample_rate, nanos as u32))
+    })
+}
+
+fn read_bytes(file: &mut File, length: u64) -> std::io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.by_ref()
+        .take(length.min(METADATA_MAX_SIZE))
+        .read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// ID3v2 and MP3
+
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0, |value, byte| (value << 7) | (*byte & 0x7f) as u32)
+}
+
+/// Removes the unsynchronisation scheme of ID3v2 (`FF 00` → `FF`)
+fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    for (index, byte) in data.iter().enumerate() {
+        if !(*byte == 0x00 && index > 0 && data[index - 1] == 0xff) {
+            result.push(*byte);
+        }
+    }
+    result
+}
+
+/// Decodes a text of an ID3v2 frame, according to its encoding byte
+fn decode_id3_text(encoding: u8, data: &[u8]) -> String {
+    let text = match encoding {
+        0 => data.iter().map(|byte| *byte as char).collect(),
+        1 | 2 => {
+            let (big_endian, data) = match data {
+                [0xfe, 0xff, rest @ ..] => (true, rest),
+                [0xff, 0xfe, rest @ ..] => (false, rest),
+                _ => (encoding == 2, data),
+            };
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|unit| match big_endian {
+                    true => u16::from_be_bytes([unit[0], unit[1]]),
+                    false => u16::from_le_bytes([unit[0], unit[1]]),
+                })
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(data).to_string(),
+    };
+    // ID3v2.4 separates multiple values with NULs, of which we keep the first one
+    text.split('\0')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_owned()
+}
+
+/// Splits a NUL terminated string of an ID3v2 frame from the data following it
+fn split_id3_terminated(encoding: u8, data: &[u8]) -> (&[u8], &[u8]) {
+    let position = match encoding {
+        1 | 2 => data
+            .chunks_exact(2)
+            .position(|unit| unit == [0, 0])
+            .map(|position| (position * 2, 2)),
+        _ => data
+            .iter()
+            .position(|byte| *byte == 0)
+            .map(|position| (position, 1)),
+    };
+    match position {
+        Some((position, terminator_length)) => {
+            (&data[..position], &data[position + terminator_length..])
+        }
+        None => (data, &[]),
+    }
+}
+
+/// Reads an ID3v2 tag, returning it with the offset of the audio frames following it
+fn read_id3v2(file: &mut File) -> std::io::Result<(AudioTags, u64)> {
+    let mut tags = AudioTags::default();
+    let mut header = [0u8; 10];
+    if file.read_exact(&mut header).is_err() || &header[..3] != b"ID3" {
+        return Ok((tags, 0));
+    }
+    let version = header[3];
+    let flags = header[5];
+    let size = syncsafe(&header[6..10]) as u64;
+    let audio_offset = 10 + size + if flags & 0x10 != 0 { 10 } else { 0 };
+
+    let mut tag = read_bytes(file, size)?;
+    if flags & 0x80 != 0 && version < 4 {
+        tag = remove_unsynchronisation(&tag);
+    }
+    // skip the extended header, whose size includes itself only in ID3v2.4
+    let mut position = match (flags & 0x40 != 0, tag.get(..4)) {
+        (true, Some(size)) if version == 3 => {
+            4 + u32::from_be_bytes(size.try_into().unwrap()) as usize
+        }
+        (true, Some(size)) => syncsafe(size) as usize,
+        _ => 0,
+    };
+
+    let (id_length, header_length) = match version {
+        2 => (3, 6),
+        _ => (4, 10),
+    };
+    while position + header_length <= tag.len() {
+        let frame_header = &tag[position..position + header_length];
+        if frame_header[0] == 0 {
+            break;
+        }
+        let id = String::from_utf8_lossy(&frame_header[..id_length]).to_string();
+        let frame_size = match version {
+            2 => u32::from_be_bytes([0, frame_header[3], frame_header[4], frame_header[5]]),
+            3 => u32::from_be_bytes(frame_header[4..8].try_into().unwrap()),
+            _ => syncsafe(&frame_header[4..8]),
+        } as usize;
+        let start = position + header_length;
+        let end = (start + frame_size).min(tag.len());
+        position = start + frame_size;
+
+        let mut frame = tag[start..end].to_vec();
+        if version == 4 {
+            let frame_flags = frame_header[9];
+            if frame_flags & 0x02 != 0 {
+                frame = remove_unsynchronisation(&frame);
+            }
+            if frame_flags & 0x01 != 0 && frame.len() >= 4 {
+                frame.drain(..4);
+            }
+        }
+        read_id3v2_frame(&mut tags, &id, &frame);
+    }
+
+    Ok((tags, audio_offset))
+}
+
+fn read_id3v2_frame(tags: &mut AudioTags, id: &str, frame: &[u8]) {
+    let (encoding, data) = match frame.split_first() {
+        Some((encoding, data)) => (*encoding, data),
+        None => return,
+    };
+    let text = || decode_id3_text(encoding, data);
+    match id {
+        "TIT2" | "TT2" => tags.title = Some(text()),
+        "TPE1" | "TP1" => tags.artist = Some(text()),
+        "TALB" | "TAL" => tags.album = Some(text()),
+        "TRCK" | "TRK" => tags.track_number = parse_track_number(&text()),
+        "TLEN" | "TLE" => {
+            tags.duration = text()
+                .parse()
+                .ok()
+                .map(|millis| RelTime::from(Duration::from_millis(millis)))
+        }
+        "APIC" => {
+            let (mime, rest) = split_id3_terminated(0, data);
+            let mime = decode_id3_text(0, mime);
+            let (picture_type, rest) = match rest.split_first() {
+                Some((picture_type, rest)) => (*picture_type as u32, rest),
+                None => return,
+            };
+            let (_, picture) = split_id3_terminated(encoding, rest);
+            let mime = match mime.as_str() {
+                "" | "image/jpg" => "image/jpeg".to_owned(),
+                _ if !mime.contains('/') => format!("image/{}", mime.to_lowercase()),
+                _ => mime,
+            };
+            tags.set_picture(picture_type, mime, picture.to_vec());
+        }
+        "PIC" if data.len() > 4 => {
+            let mime = match &data[..3] {
+                b"PNG" => "image/png",
+                _ => "image/jpeg",
+            };
+            let (_, picture) = split_id3_terminated(encoding, &data[4..]);
+            tags.set_picture(data[3] as u32, mime.to_owned(), picture.to_vec());
+        }
+        _ => {}
+    }
+}
+
+fn read_mp3(file: &mut File) -> std::io::Result<AudioTags> {
+    let (mut tags, audio_offset) = read_id3v2(file)?;
+    if tags.duration.is_none() {
+        let file_length = file.metadata()?.len();
+        file.seek(SeekFrom::Start(audio_offset))?;
+        let frames = read_bytes(file, 8192)?;
+        tags.duration = mp3_duration(&frames, file_length.saturating_sub(audio_offset));
+    }
+    Ok(tags)
+}
+
+/// Whether the second byte of a frame header follows a 0xff byte with the rest of the frame sync
+/// of MPEG audio layer III
+fn is_mp3_sync(byte: u8) -> bool {
+    byte & 0xe6 == 0xe2
+}
+
+/// Estimates the duration of MPEG audio layer III frames, from their Xing/VBRI header or bitrate
+fn mp3_duration(frames: &[u8], audio_length: u64) -> Option<RelTime> {
+    const BITRATES_V1: [u32; 15] = [
+        0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+    ];
+    const BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+    const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+    let start = frames
+        .windows(2)
+        .position(|sync| sync[0] == 0xff && is_mp3_sync(sync[1]))?;
+    let header = frames.get(start..start + 4)?;
+    let version = (header[1] >> 3) & 0x03;
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0x03) as usize;
+    let mono = header[3] >> 6 == 0x03;
+    if bitrate_index == 0x0f || sample_rate_index == 0x03 || version == 0x01 {
+        return None;
+    }
+
+    let is_v1 = version == 0x03;
+    let sample_rate = SAMPLE_RATES[sample_rate_index]
+        >> match version {
+            0x03 => 0,
+            0x02 => 1,
+            _ => 2,
+        };
+    let samples_per_frame = if is_v1 { 1152 } else { 576 };
+    let side_info_length = match (is_v1, mono) {
+        (true, false) => 32,
+        (true, true) | (false, false) => 17,
+        (false, true) => 9,
+    };
+
+    let xing = start + 4 + side_info_length;
+    if let Some(b"Xing") | Some(b"Info") = frames.get(xing..xing + 4) {
+        let mut reader = ByteReader::new(&frames[xing + 4..]);
+        if reader.u32_be()? & 0x01 != 0 {
+            let frame_count = reader.u32_be()? as u64;
+            return duration_from_samples(frame_count * samples_per_frame, sample_rate);
+        }
+    }
+    let vbri = start + 4 + 32;
+    if let Some(b"VBRI") = frames.get(vbri..vbri + 4) {
+        let mut reader = ByteReader::new(frames.get(vbri + 14..)?);
+        let frame_count = reader.u32_be()? as u64;
+        return duration_from_samples(frame_count * samples_per_frame, sample_rate);
+    }
+
+    let bitrate = match is_v1 {
+        true => BITRATES_V1[bitrate_index],
+        false => BITRATES_V2[bitrate_index],
+    };
+    (bitrate > 0).then(|| {
+        RelTime::from(Duration::from_secs_f64(
+            audio_length as f64 * 8.0 / (bitrate as f64 * 1000.0),
+        ))
+    })
+}
+
+// FLAC
+
+fn read_flac(file: &mut File) -> std::io::Result<AudioTags> {
+    let mut tags = AudioTags::default();
+    file.seek(SeekFrom::Start(4))?;
+    loop {
+        let mut header = [0u8; 4];
+        if file.read_exact(&mut header).is_err() {
+            break;
+        }
+        let is_last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7f;
+        let length = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        match block_type {
+            0 | 4 | 6 => {
+                let block = read_bytes(file, length)?;
+                match block_type {
+                    0 => tags.duration = flac_duration(&block),
+                    4 => tags.set_vorbis_comments(&block),
+                    _ => tags.set_flac_picture(&block),
+                }
+            }
+            _ => {
+                file.seek(SeekFrom::Current(length as i64))?;
+            }
+        }
+        if is_last {
+            break;
+        }
+    }
+    Ok(tags)
+}
+
+fn flac_duration(stream_info: &[u8]) -> Option<RelTime> {
+    let bytes = stream_info.get(10..18)?;
+    let sample_rate = ((bytes[0] as u32) << 12) | ((bytes[1] as u32) << 4) | (bytes[2] as u32 >> 4);
+    let total_samples = (((bytes[3] & 0x0f) as u64) << 32)
+        | u32::from_be_bytes(bytes[4..8].try_into().unwrap()) as u64;
+    match total_samples {
+        0 => None,
+        _ => duration_from_samples(total_samples, sample_rate),
+    }
+}
+
+// Ogg (Vorbis and Opus)
+
+/// Reads the first packets of the first logical stream of an Ogg file
+fn read_ogg_packets(file: &mut File, count: usize) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut packets = Vec::new();
+    let mut packet = Vec::new();
+    let mut read_length = 0;
+    while packets.len() < count && read_length < METADATA_MAX_SIZE {
+        let mut header = [0u8; 27];
+        if file.read_exact(&mut header).is_err() || &header[..4] != b"OggS" {
+            break;
+        }
+        let mut segments = vec![0u8; header[26] as usize];
+        file.read_exact(&mut segments)?;
+        for segment in segments {
+            let mut data = vec![0u8; segment as usize];
+            file.read_exact(&mut data)?;
+            read_length += segment as u64;
+            packet.extend(data);
+            if segment < 255 {
+                packets.push(std::mem::take(&mut packet));
+            }
+        }
+    }
+    Ok(packets)
+}
+
+/// Returns the granule position of the last page of an Ogg file
+fn ogg_last_granule(file: &mut File) -> std::io::Result<Option<u64>> {
+    let file_length = file.metadata()?.len();
+    file.seek(SeekFrom::Start(file_length.saturating_sub(OGG_TAIL_LENGTH)))?;
+    let tail = read_bytes(file, OGG_TAIL_LENGTH)?;
+    let last_page = tail.windows(4).rposition(|magic| magic == b"OggS");
+    Ok(last_page
+        .and_then(|position| tail.get(position + 6..position + 14))
+        .map(|granule| u64::from_le_bytes(granule.try_into().unwrap())))
+}
+
+fn read_ogg(file: &mut File) -> std::io::Result<AudioTags> {
+    let mut tags = AudioTags::default();
+    let packets = read_ogg_packets(file, 2)?;
+    let (identification, comment) = match packets.as_slice() {
+        [identification, comment, ..] => (identification, comment),
+        _ => return Ok(tags),
+    };
+
+    let (sample_rate, pre_skip) = if identification.starts_with(b"\x01vorbis") {
+        tags.set_vorbis_comments(comment.get(7..).unwrap_or_default());
+        let mut reader = ByteReader::new(identification);
+        reader.skip(12);
+        (reader.u32_le().unwrap_or_default(), 0)
+    } else if identification.starts_with(b"OpusHead") {
+        tags.set_vorbis_comments(comment.get(8..).unwrap_or_default());
+        let pre_skip = identification
+            .get(10..12)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+            .unwrap_or_default();
+        // Opus granule positions are always expressed at 48 kHz
+        (48000, pre_skip as u64)
+    } else {
+        return Ok(tags);
+    };
+
+    if let Some(granule) = ogg_last_granule(file)?.filter(|granule| *granule != u64::MAX) {
+        tags.duration = duration_from_samples(granule.saturating_sub(pre_skip), sample_rate);
+    }
+    Ok(tags)
+}
+
+// MP4
+
+/// Iterates over the boxes contained in a slice of bytes, as their type and content
+pub(crate) fn mp4_boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut reader = ByteReader::new(data);
+    std::iter::from_fn(move || {
+        let size = reader.u32_be()? as usize;
+        let box_type = reader.bytes(4)?;
+        let content = match size {
+            0 => reader.rest(),
+            1 => {
+                let large_size = u64::from_be_bytes(reader.bytes(8)?.try_into().unwrap());
+                reader.bytes((large_size as usize).checked_sub(16)?)?
+            }
+            _ => reader.bytes(size.checked_sub(8)?)?,
+        };
+        Some((box_type, content))
+    })
+}
+
+pub(crate) fn mp4_child<'a>(data: &'a [u8], box_type: &[u8]) -> Option<&'a [u8]> {
+    mp4_boxes(data)
+        .find(|(child_type, _)| *child_type == box_type)
+        .map(|(_, content)| content)
+}
+
+/// Reads the content of the `moov` box of an MP4 file, which may be after the media data
+pub(crate) fn read_mp4_moov(file: &mut File) -> std::io::Result<Option<Vec<u8>>> {
+    let file_length = file.metadata()?.len();
+    let mut position = 0;
+    loop {
+        if position + 8 > file_length {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(position))?;
+        let mut header = [0u8; 16];
+        file.read_exact(&mut header[..8])?;
+        let (size, header_length) = match u32::from_be_bytes(header[..4].try_into().unwrap()) {
+            0 => (file_length - position, 8),
+            1 => {
+                file.read_exact(&mut header[8..])?;
+                (u64::from_be_bytes(header[8..].try_into().unwrap()), 16)
+            }
+            size => (size as u64, 8),
+        };
+        if size < header_length {
+            return Ok(None);
+        }
+        if &header[4..8] == b"moov" {
+            return read_bytes(file, size - header_length).map(Some);
+        }
+        position = match position.checked_add(size) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+    }
+}
+
+/// Returns the duration of an MP4 movie, from the `mvhd` box of its `moov` box
+pub(crate) fn mp4_movie_duration(moov: &[u8]) -> Option<RelTime> {
+    let mut reader = ByteReader::new(mp4_child(moov, b"mvhd")?);
+    match reader.u8()? {
+        1 => {
+            reader.skip(3 + 16)?;
+            let timescale = reader.u32_be()?;
+            duration_from_samples(reader.u64_be()?, timescale)
+        }
+        _ => {
+            reader.skip(3 + 8)?;
+            let timescale = reader.u32_be()?;
+            duration_from_samples(reader.u32_be()? as u64, timescale)
+        }
+    }
+}
+
+fn read_mp4(file: &mut File) -> std::io::Result<AudioTags> {
+    let mut tags = AudioTags::default();
+    let moov = match read_mp4_moov(file)? {
+        Some(moov) => moov,
+        None => return Ok(tags),
+    };
+    tags.duration = mp4_movie_duration(&moov);
+
+    let ilst = mp4_child(&moov, b"udta")
+        .and_then(|udta| mp4_child(udta, b"meta"))
+        .and_then(|meta| {
+            // `meta` is a full box in ISO files, but not in QuickTime ones
+            match meta.get(4..8) {
+                Some(b"hdlr") => mp4_child(meta, b"ilst"),
+                _ => mp4_child(meta.get(4..)?, b"ilst"),
+            }
+        });
+    for (item_type, item) in ilst.map(mp4_boxes).into_iter().flatten() {
+        let data = match mp4_child(item, b"data").filter(|data| data.len() >= 8) {
+            Some(data) => data,
+            None => continue,
+        };
+        let data_type = u32::from_be_bytes(data[..4].try_into().unwrap()) & 0x00ff_ffff;
+        let value = &data[8..];
+        let text = || String::from_utf8_lossy(value).trim().to_owned();
+        match item_type {
+            b"\xa9nam" => tags.title = Some(text()),
+            b"\xa9ART" => tags.artist = Some(text()),
+            b"aART" if tags.artist.is_none() => tags.artist = Some(text()),
+            b"\xa9alb" => tags.album = Some(text()),
+            b"trkn" => {
+                tags.track_number = value
+                    .get(2..4)
+                    .map(|track| u16::from_be_bytes([track[0], track[1]]) as u32)
+                    .filter(|track| *track > 0)
+            }
+            b"covr" => {
+                let mime = match data_type {
+                    14 => "image/png",
+                    _ => "image/jpeg",
+                };
+                tags.set_picture(PICTURE_TYPE_FRONT_COVER, mime.to_owned(), value.to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{mp4_box, with_temp_file};
+
+    /// MPEG-1 layer III frame header, 128 kbps at 44.1 kHz, joint stereo
+    const MP3_FRAME_HEADER: [u8; 4] = [0xff, 0xfb, 0x90, 0x64];
+
+    fn mp3_frame(tag_offset: usize, tag: &[u8]) -> Vec<u8> {
+        let mut frame = MP3_FRAME_HEADER.to_vec();
+        frame.resize(4 + tag_offset, 0);
+        frame.extend_from_slice(tag);
+        frame
+    }
+
+    fn read_tags(name: &str, data: &[u8]) -> Result<AudioTags> {
+        with_temp_file(name, data, AudioTags::read)
+    }
+
+    fn millis(duration: Option<RelTime>) -> Option<u128> {
+        duration.map(|duration| duration.as_duration().as_millis())
+    }
+
+    #[test]
+    fn mp3_duration_from_xing_header() {
+        let frames = mp3_frame(
+            32,
+            &[b"Xing".as_slice(), &[0, 0, 0, 1], &[0, 0, 0, 100]].concat(),
+        );
+        // 100 frames of 1152 samples at 44.1 kHz
+        assert_eq!(millis(mp3_duration(&frames, 1_000_000)), Some(2612));
+    }
+
+    #[test]
+    fn mp3_duration_from_vbri_header() {
+        let mut vbri = b"VBRI".to_vec();
+        vbri.extend_from_slice(&[0; 10]);
+        vbri.extend_from_slice(&200u32.to_be_bytes());
+        let frames = mp3_frame(32, &vbri);
+        assert_eq!(millis(mp3_duration(&frames, 1_000_000)), Some(5224));
+    }
+
+    #[test]
+    fn mp3_duration_from_truncated_vbri_header() {
+        let frames = mp3_frame(32, b"VBRI\0\0");
+        assert_eq!(mp3_duration(&frames, 1_000_000), None);
+    }
+
+    #[test]
+    fn mp3_duration_from_bitrate() {
+        let frames = mp3_frame(0, &[]);
+        assert_eq!(millis(mp3_duration(&frames, 16_000)), Some(1000));
+    }
+
+    #[test]
+    fn mp4_duration_from_movie_header() {
+        // version 1, times, timescale and duration
+        let mvhd = |timescale: u32, duration: u64| {
+            let content = [
+                &[1, 0, 0, 0],
+                [0; 16].as_slice(),
+                &timescale.to_be_bytes(),
+                &duration.to_be_bytes(),
+            ]
+            .concat();
+            mp4_box(b"mvhd", &content)
+        };
+        assert_eq!(millis(mp4_movie_duration(&mvhd(600, 1500))), Some(2500));
+        assert_eq!(
+            mp4_movie_duration(&mvhd(1, u64::MAX)).map(|duration| duration.as_secs()),
+            Some(u64::MAX)
+        );
+        assert_eq!(mp4_movie_duration(&mvhd(0, 1500)), None);
+    }
+
+    #[test]
+    fn read_id3v2_text_frames() {
+        let mut frames = Vec::new();
+        for (id, text) in [("TIT2", "Title"), ("TPE1", "Artist"), ("TRCK", "3/12")] {
+            frames.extend_from_slice(id.as_bytes());
+            frames.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+            frames.extend_from_slice(&[0, 0, 0]);
+            frames.extend_from_slice(text.as_bytes());
+        }
+        let mut data = b"ID3\x03\x00\x00".to_vec();
+        data.extend_from_slice(&[0, 0, 0, frames.len() as u8]);
+        data.extend(frames);
+        let tags = read_tags("id3.mp3", &data).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Title"));
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.track_number, Some(3));
+    }
+
+    #[test]
+    fn read_non_mp3_file_without_tags() {
+        // an MPEG frame sync in the audio data of a WAV file is not taken as an MP3 frame
+        let mut data = b"RIFF\0\0\0\0WAVE".to_vec();
+        data.extend(mp3_frame(0, &[0; 64]));
+        assert_eq!(read_tags("audio.wav", &data).unwrap(), AudioTags::default());
+    }
+
+    #[test]
+    fn read_truncated_files() {
+        let truncated: [(&str, &[u8]); 5] = [
+            ("id3.mp3", b"ID3\x04\x00\x40\x00\x00\x7f\x7fTIT2\x00\x00"),
+            ("frame.mp3", &[0xff, 0xfb, 0x90]),
+            ("flac.flac", b"fLaC\x00\x00\x00\x22\x00\x10"),
+            ("ogg.ogg", b"OggS\x00\x02\x00\x00"),
+            ("mp4.m4a", b"\x00\x00\x00\x18ftypM4A \x00\x00\x00\x00moov"),
+        ];
+        for (name, data) in truncated {
+            let _ = read_tags(name, data);
+        }
+    }
+
+    #[test]
+    fn vorbis_comments() {
+        let mut tags = AudioTags::default();
+        tags.set_vorbis_comment("title=Song");
+        tags.set_vorbis_comment("TRACKNUMBER=7/10");
+        tags.set_vorbis_comment("invalid");
+        assert_eq!(tags.title.as_deref(), Some("Song"));
+        assert_eq!(tags.track_number, Some(7));
+    }
+}
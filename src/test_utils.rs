@@ -0,0 +1,49 @@
+use std::{
+    path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+static TEMP_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Writes `data` to a temporary file, unique to the call, and passes its path to `f` before removing it
+pub(crate) fn with_temp_file<T>(name: &str, data: &[u8], f: impl FnOnce(&Path) -> T) -> T {
+    let path = std::env::temp_dir().join(format!(
+        "crab-dlna-test-{}-{}-{}",
+        std::process::id(),
+        TEMP_FILE_COUNT.fetch_add(1, Ordering::Relaxed),
+        name
+    ));
+    std::fs::write(&path, data).unwrap();
+    let result = f(&path);
+    std::fs::remove_file(&path).unwrap();
+    result
+}
+
+/// Writes files to a temporary directory, unique to the call, and passes its path to `f` before removing it
+pub(crate) fn with_temp_dir<T>(files: &[(&str, &[u8])], f: impl FnOnce(&Path) -> T) -> T {
+    let dir = std::env::temp_dir().join(format!(
+        "crab-dlna-test-{}-{}",
+        std::process::id(),
+        TEMP_FILE_COUNT.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir(&dir).unwrap();
+    for (name, data) in files {
+        std::fs::write(dir.join(name), data).unwrap();
+    }
+    let result = f(&dir);
+    std::fs::remove_dir_all(&dir).unwrap();
+    result
+}
+
+/// Builds an MP4 box from its type and content
+pub(crate) fn mp4_box(box_type: &[u8], content: &[u8]) -> Vec<u8> {
+    [&(content.len() as u32 + 8).to_be_bytes(), box_type, content].concat()
+}
+
+/// Builds an EBML element from its ID and content, with a 2 bytes size
+pub(crate) fn ebml(id: u32, content: &[u8]) -> Vec<u8> {
+    let id = id.to_be_bytes();
+    let id = &id[id.iter().position(|byte| *byte != 0).unwrap()..];
+    let size = 0x4000 | content.len() as u16;
+    [id, &size.to_be_bytes(), content].concat()
+}
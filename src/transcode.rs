@@ -0,0 +1,706 @@
+use crate::{
+    devices::Render,
+    error::{Error, Result},
+    media::{MediaKind, MediaProfile},
+    probe::MediaProbe,
+    protocol::ProtocolInfo,
+    streaming::MediaItem,
+};
+use futures::Stream;
+use log::{debug, info, warn};
+use std::{
+    ffi::OsString,
+    fmt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, BufReader},
+    process::{Child, ChildStdout, Command},
+};
+use warp::hyper::body::Bytes;
+
+/// Default command used to run ffmpeg
+pub const FFMPEG_DEFAULT: &str = "ffmpeg";
+
+/// Size of the chunks of the output of ffmpeg sent to the render
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Video codecs which can be remuxed into an MPEG transport stream for renders which do not play their container
+const REMUX_VIDEO_CODECS: [&str; 2] = ["h264", "mpeg2"];
+
+/// Audio codecs which can be remuxed into an MPEG transport stream for renders which do not play their container
+const REMUX_AUDIO_CODECS: [&str; 4] = ["aac", "mp3", "mp2", "ac3"];
+
+/// Video codecs renders are assumed to play in the containers they declare
+const PLAYABLE_VIDEO_CODECS: [&str; 3] = ["h264", "mpeg2", "mpeg4"];
+
+/// Audio codecs renders are assumed to play in the containers they declare
+const PLAYABLE_AUDIO_CODECS: [&str; 6] = ["aac", "mp3", "mp2", "ac3", "eac3", "flac"];
+
+/// Prefixes of the DLNA profiles through which renders declare they play other codecs
+const CODEC_DLNA_PROFILES: [(&str, &str); 5] = [
+    ("hevc", "HEVC_"),
+    ("vc1", "VC1_"),
+    ("wmv3", "WMV"),
+    ("dts", "DTS"),
+    ("wmav2", "WMA"),
+];
+
+/// Counter of the subtitle files written to be burned into videos, to give them unique names
+static BURNED_SUBTITLES_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A built-in transcoding profile: the codecs and the container media are converted to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodeProfile {
+    /// H.264 video and AAC audio in an MPEG transport stream
+    H264AacTs,
+    /// H.264 video and AAC audio in a fragmented MP4
+    H264AacMp4,
+    /// MP3 audio, dropping the video
+    Mp3,
+    /// H.264 video, with the subtitles burned in, and AAC audio in an MPEG transport stream
+    H264AacTsSubtitles,
+    /// The video and audio as they are, remuxed into an MPEG transport stream
+    CopyTs,
+}
+
+impl TranscodeProfile {
+    /// Returns the type of the transcoded media
+    pub fn media_profile(&self) -> MediaProfile {
+        match self {
+            Self::H264AacTs | Self::H264AacTsSubtitles | Self::CopyTs => {
+                MediaProfile::transcoded("video/mpeg", None, MediaKind::Video)
+            }
+            Self::H264AacMp4 => MediaProfile::transcoded("video/mp4", None, MediaKind::Video),
+            Self::Mp3 => MediaProfile::transcoded("audio/mpeg", Some("MP3"), MediaKind::Audio),
+        }
+    }
+
+    /// Returns the extension of the files of the transcoded media
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::H264AacTs | Self::H264AacTsSubtitles | Self::CopyTs => "ts",
+            Self::H264AacMp4 => "mp4",
+            Self::Mp3 => "mp3",
+        }
+    }
+
+    /// Whether the subtitles are burned into the video, instead of being served aside
+    pub fn burns_subtitles(&self) -> bool {
+        matches!(self, Self::H264AacTsSubtitles)
+    }
+
+    /// Whether a kind of media can be transcoded with the profile
+    pub fn supports(&self, kind: MediaKind) -> bool {
+        match self {
+            Self::Mp3 => kind.is_av(),
+            _ => kind == MediaKind::Video,
+        }
+    }
+
+    /// Returns the ffmpeg arguments selecting the streams and setting their codecs
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            Self::H264AacTs | Self::H264AacMp4 | Self::H264AacTsSubtitles => &[
+                "-map",
+                "0:v:0",
+                "-map",
+                "0:a:0?",
+                "-c:v",
+                "libx264",
+                "-preset",
+                "veryfast",
+                "-profile:v",
+                "high",
+                "-level:v",
+                "4.1",
+                "-pix_fmt",
+                "yuv420p",
+                "-c:a",
+                "aac",
+                "-b:a",
+                "192k",
+                "-ac",
+                "2",
+            ],
+            Self::Mp3 => &[
+                "-map",
+                "0:a:0",
+                "-vn",
+                "-c:a",
+                "libmp3lame",
+                "-b:a",
+                "320k",
+                "-ar",
+                "44100",
+            ],
+            Self::CopyTs => &["-map", "0:v:0", "-map", "0:a:0?", "-c", "copy"],
+        }
+    }
+
+    /// Returns the ffmpeg arguments setting the container of the output
+    fn format_args(&self) -> &'static [&'static str] {
+        match self {
+            Self::H264AacTs | Self::H264AacTsSubtitles | Self::CopyTs => &["-f", "mpegts"],
+            Self::H264AacMp4 => &[
+                "-movflags",
+                "frag_keyframe+empty_moov+default_base_moof",
+                "-f",
+                "mp4",
+            ],
+            Self::Mp3 => &["-f", "mp3"],
+        }
+    }
+}
+
+impl FromStr for TranscodeProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "h264-aac-ts" => Ok(Self::H264AacTs),
+            "h264-aac-mp4" => Ok(Self::H264AacMp4),
+            "mp3" => Ok(Self::Mp3),
+            "h264-aac-ts-subs" => Ok(Self::H264AacTsSubtitles),
+            "copy-ts" => Ok(Self::CopyTs),
+            _ => Err(Error::TranscodeProfileParseError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TranscodeProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let profile = match self {
+            Self::H264AacTs => "h264-aac-ts",
+            Self::H264AacMp4 => "h264-aac-mp4",
+            Self::Mp3 => "mp3",
+            Self::H264AacTsSubtitles => "h264-aac-ts-subs",
+            Self::CopyTs => "copy-ts",
+        };
+        write!(f, "{}", profile)
+    }
+}
+
+/// When media is transcoded while being streamed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscodeMode {
+    /// Only the media the render declares it can not play, from their container and their codecs:
+    /// remuxed when only their container is not supported, transcoded otherwise
+    Auto,
+    /// Never, streaming the files as they are
+    Never,
+    /// Always, even the media the render can play
+    Always,
+}
+
+impl TranscodeMode {
+    /// Chooses the profile a media is transcoded with for a render, or `None` to stream its file directly.
+    ///
+    /// `profile` is used instead of the one matching what the render plays, when it supports the kind
+    /// of the media.
+    pub fn decide(
+        &self,
+        media_item: &MediaItem,
+        render: &Render,
+        profile: Option<TranscodeProfile>,
+    ) -> Option<TranscodeProfile> {
+        self.decide_for_sink(
+            media_item.video_profile(),
+            media_item.probe(),
+            &render.sink_protocol_info,
+            profile,
+        )
+    }
+
+    /// Chooses the profile a media is transcoded with, for a render declaring `sink_protocol_info`
+    fn decide_for_sink(
+        &self,
+        media_profile: &MediaProfile,
+        probe: Option<&MediaProbe>,
+        sink_protocol_info: &[ProtocolInfo],
+        profile: Option<TranscodeProfile>,
+    ) -> Option<TranscodeProfile> {
+        let kind = media_profile.kind;
+        if !kind.is_av() {
+            return None;
+        }
+        let profile = profile.filter(|profile| profile.supports(kind));
+        match self {
+            Self::Never => None,
+            Self::Auto
+                if ProtocolInfo::list_matches(sink_protocol_info, media_profile)
+                    && codecs_playable(media_profile, probe, sink_protocol_info) =>
+            {
+                None
+            }
+            Self::Auto
+                if profile.is_none() && can_remux(media_profile, probe, sink_protocol_info) =>
+            {
+                Some(TranscodeProfile::CopyTs)
+            }
+            Self::Auto | Self::Always => {
+                Some(profile.unwrap_or_else(|| default_profile(kind, sink_protocol_info)))
+            }
+        }
+    }
+}
+
+impl FromStr for TranscodeMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "never" => Ok(Self::Never),
+            "always" => Ok(Self::Always),
+            _ => Err(Error::TranscodeModeParseError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for TranscodeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            Self::Auto => "auto",
+            Self::Never => "never",
+            Self::Always => "always",
+        };
+        write!(f, "{}", mode)
+    }
+}
+
+/// Whether a render plays the codecs probed in a media, either because they are common ones or because it
+/// declares a DLNA profile of theirs for the type of the media.
+///
+/// Codecs which could not be probed, or of renders which do not declare what they play, are assumed to be played.
+fn codecs_playable(
+    media_profile: &MediaProfile,
+    probe: Option<&MediaProbe>,
+    sink_protocol_info: &[ProtocolInfo],
+) -> bool {
+    sink_protocol_info.is_empty()
+        || probe.is_none_or(|probe| {
+            let video_playable = media_profile.kind != MediaKind::Video
+                || probe.video_codec.as_deref().is_none_or(|codec| {
+                    PLAYABLE_VIDEO_CODECS.contains(&codec)
+                        || declares_codec(media_profile, codec, sink_protocol_info)
+                });
+            let audio_playable = probe.audio_codec.as_deref().is_none_or(|codec| {
+                PLAYABLE_AUDIO_CODECS.contains(&codec)
+                    || declares_codec(media_profile, codec, sink_protocol_info)
+            });
+            video_playable && audio_playable
+        })
+}
+
+/// Whether a render declares a DLNA profile of a codec, for the MIME type of a media
+fn declares_codec(
+    media_profile: &MediaProfile,
+    codec: &str,
+    sink_protocol_info: &[ProtocolInfo],
+) -> bool {
+    let container_profile = MediaProfile {
+        dlna_profile: None,
+        ..*media_profile
+    };
+    CODEC_DLNA_PROFILES
+        .iter()
+        .filter(|(profile_codec, _)| *profile_codec == codec)
+        .any(|(_, dlna_profile_prefix)| {
+            sink_protocol_info.iter().any(|protocol_info| {
+                protocol_info.matches(&container_profile)
+                    && protocol_info
+                        .dlna_profile
+                        .as_deref()
+                        .is_some_and(|dlna_profile| dlna_profile.starts_with(dlna_profile_prefix))
+            })
+        })
+}
+
+/// Whether a video can be played by a render by only remuxing its codecs into an MPEG transport stream
+fn can_remux(
+    media_profile: &MediaProfile,
+    probe: Option<&MediaProbe>,
+    sink_protocol_info: &[ProtocolInfo],
+) -> bool {
+    let remuxed_profile = TranscodeProfile::CopyTs.media_profile();
+    let codecs_remuxable = probe.is_some_and(|probe| {
+        probe
+            .video_codec
+            .as_deref()
+            .is_some_and(|codec| REMUX_VIDEO_CODECS.contains(&codec))
+            && probe
+                .audio_codec
+                .as_deref()
+                .is_none_or(|codec| REMUX_AUDIO_CODECS.contains(&codec))
+    });
+    media_profile.kind == MediaKind::Video
+        && media_profile.mime != remuxed_profile.mime
+        && ProtocolInfo::list_matches(sink_protocol_info, &remuxed_profile)
+        && codecs_remuxable
+}
+
+/// Returns the profile media of a kind are transcoded with by default, according to what a render plays
+fn default_profile(kind: MediaKind, sink_protocol_info: &[ProtocolInfo]) -> TranscodeProfile {
+    let supports = |profile: TranscodeProfile| {
+        ProtocolInfo::list_matches(sink_protocol_info, &profile.media_profile())
+    };
+    let mp4_only = !supports(TranscodeProfile::H264AacTs) && supports(TranscodeProfile::H264AacMp4);
+    match kind {
+        MediaKind::Audio => TranscodeProfile::Mp3,
+        _ if mp4_only => TranscodeProfile::H264AacMp4,
+        _ => TranscodeProfile::H264AacTs,
+    }
+}
+
+/// Transcodes media on the fly with an external ffmpeg process, whose output is streamed to the render.
+///
+/// ```
+/// use crab_dlna::{TranscodeProfile, Transcoder};
+///
+/// let transcoder = Transcoder::new(TranscodeProfile::H264AacTs)
+///     .args(vec!["-crf".to_string(), "20".to_string()]);
+/// let args = transcoder.command_args(std::path::Path::new("movie.mkv"), None);
+/// assert!(args.iter().any(|arg| arg == "-crf"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcoder {
+    profile: TranscodeProfile,
+    ffmpeg: PathBuf,
+    args: Vec<String>,
+}
+
+impl Transcoder {
+    /// Create a new transcoder, with a built-in profile, running `ffmpeg` from the `PATH`.
+    pub fn new(profile: TranscodeProfile) -> Self {
+        Self {
+            profile,
+            ffmpeg: PathBuf::from(FFMPEG_DEFAULT),
+            args: Vec::new(),
+        }
+    }
+
+    /// Sets the command used to run ffmpeg.
+    pub fn ffmpeg(mut self, ffmpeg: impl Into<PathBuf>) -> Self {
+        self.ffmpeg = ffmpeg.into();
+        self
+    }
+
+    /// Sets custom ffmpeg arguments, added after the ones of the profile (so they override them) and
+    /// before the output.
+    pub fn args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Returns the profile of the transcoder
+    pub fn profile(&self) -> TranscodeProfile {
+        self.profile
+    }
+
+    /// Whether the ffmpeg command can be found, as a path or in the `PATH`
+    pub fn is_available(&self) -> bool {
+        if self.ffmpeg.components().count() > 1 {
+            return self.ffmpeg.is_file();
+        }
+        std::env::var_os("PATH").is_some_and(|paths| {
+            std::env::split_paths(&paths).any(|path| path.join(&self.ffmpeg).is_file())
+        })
+    }
+
+    /// Returns the arguments ffmpeg is run with to transcode a file, burning the subtitles of a file if any.
+    pub fn command_args(&self, input: &Path, subtitle_path: Option<&Path>) -> Vec<OsString> {
+        let mut args: Vec<OsString> = ["-hide_banner", "-loglevel", "error", "-nostdin", "-i"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        args.push(input.as_os_str().to_os_string());
+        args.extend(self.profile.codec_args().iter().map(OsString::from));
+        if let Some(subtitle_path) = subtitle_path {
+            args.push("-vf".into());
+            args.push(
+                format!(
+                    "subtitles=filename={}",
+                    escape_filter_value(&subtitle_path.to_string_lossy())
+                )
+                .into(),
+            );
+        }
+        args.extend(self.args.iter().map(OsString::from));
+        args.extend(self.profile.format_args().iter().map(OsString::from));
+        args.push("pipe:1".into());
+        args
+    }
+
+    /// Starts transcoding a file, burning `subtitles` (in SRT) into the video when given, and returns
+    /// the output of ffmpeg as a stream of chunks.
+    ///
+    /// ffmpeg is killed when the stream is dropped, e.g. when the render closes the connection.
+    pub fn spawn(
+        &self,
+        input: &Path,
+        subtitles: Option<&str>,
+    ) -> Result<impl Stream<Item = std::io::Result<Bytes>> + Send + 'static> {
+        let spawn_error = |err| Error::TranscodeSpawnError(self.ffmpeg.display().to_string(), err);
+
+        let subtitle_path = match subtitles {
+            Some(subtitles) => {
+                let subtitle_path = std::env::temp_dir().join(format!(
+                    "crab-dlna-{}-{}.srt",
+                    std::process::id(),
+                    BURNED_SUBTITLES_COUNT.fetch_add(1, Ordering::Relaxed)
+                ));
+                std::fs::write(&subtitle_path, subtitles).map_err(spawn_error)?;
+                Some(subtitle_path)
+            }
+            None => None,
+        };
+        let args = self.command_args(input, subtitle_path.as_deref());
+        info!(
+            "Transcoding '{}' to {} with {}",
+            input.display(),
+            self.profile,
+            self.ffmpeg.display()
+        );
+        debug!("ffmpeg arguments: {:?}", args);
+
+        let transcoding = Command::new(&self.ffmpeg)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(spawn_error);
+        let mut child = match transcoding {
+            Ok(child) => child,
+            Err(err) => {
+                if let Some(subtitle_path) = &subtitle_path {
+                    let _ = std::fs::remove_file(subtitle_path);
+                }
+                return Err(err);
+            }
+        };
+        if let Some(stderr) = child.stderr.take() {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    warn!("ffmpeg: {}", line);
+                }
+            });
+        }
+        let stdout = child.stdout.take().expect("the output of ffmpeg is piped");
+
+        let transcoding = Transcoding {
+            child,
+            stdout,
+            subtitle_path,
+        };
+        Ok(futures::stream::unfold(
+            transcoding,
+            |mut transcoding| async move {
+                let mut chunk = vec![0u8; CHUNK_SIZE];
+                match transcoding.stdout.read(&mut chunk).await {
+                    Ok(0) => {
+                        match transcoding.child.wait().await {
+                            Ok(status) if !status.success() => {
+                                warn!("ffmpeg exited with {}", status)
+                            }
+                            Ok(_) => debug!("Transcoding finished"),
+                            Err(err) => warn!("Failed to wait for ffmpeg: {}", err),
+                        }
+                        None
+                    }
+                    Ok(length) => {
+                        chunk.truncate(length);
+                        Some((Ok(Bytes::from(chunk)), transcoding))
+                    }
+                    Err(err) => Some((Err(err), transcoding)),
+                }
+            },
+        ))
+    }
+}
+
+/// A running ffmpeg process, with the subtitle file it burns, removed once it is done
+struct Transcoding {
+    child: Child,
+    stdout: ChildStdout,
+    subtitle_path: Option<PathBuf>,
+}
+
+impl Drop for Transcoding {
+    fn drop(&mut self) {
+        if let Some(subtitle_path) = &self.subtitle_path {
+            let _ = std::fs::remove_file(subtitle_path);
+        }
+    }
+}
+
+/// Escapes a value of a filter option, then the filter in the filter graph (ffmpeg-filters, 4.1)
+fn escape_filter_value(value: &str) -> String {
+    let escape = |value: &str, special: &[char]| {
+        value.chars().fold(String::new(), |mut escaped, c| {
+            if special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+            escaped
+        })
+    };
+    let value = escape(value, &['\\', '\'', ':']);
+    escape(&value, &['\\', '\'', '[', ']', ',', ';'])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decide(
+        mode: TranscodeMode,
+        extension: &str,
+        codecs: Option<(&str, &str)>,
+        sink: &str,
+        profile: Option<TranscodeProfile>,
+    ) -> Option<TranscodeProfile> {
+        let probe = codecs.map(|(video_codec, audio_codec)| MediaProbe {
+            video_codec: Some(video_codec.to_owned()).filter(|codec| !codec.is_empty()),
+            audio_codec: Some(audio_codec.to_owned()).filter(|codec| !codec.is_empty()),
+            ..MediaProbe::default()
+        });
+        mode.decide_for_sink(
+            &MediaProfile::from_extension(extension),
+            probe.as_ref(),
+            &ProtocolInfo::parse_list(sink),
+            profile,
+        )
+    }
+
+    const MKV_TS_SINK: &str = "http-get:*:video/x-matroska:*,http-get:*:video/mpeg:*";
+    const TS_SINK: &str = "http-get:*:video/mpeg:*,http-get:*:audio/mpeg:*";
+
+    #[test]
+    fn decide_playable_media() {
+        use TranscodeMode::*;
+        let h264 = Some(("h264", "aac"));
+        assert_eq!(decide(Auto, "mkv", h264, MKV_TS_SINK, None), None);
+        // renders which do not declare what they play are assumed to play anything
+        assert_eq!(decide(Auto, "mkv", Some(("hevc", "dts")), "", None), None);
+        // codecs which could not be probed are assumed to be played
+        assert_eq!(decide(Auto, "mkv", None, MKV_TS_SINK, None), None);
+        assert_eq!(decide(Auto, "mkv", Some(("", "")), MKV_TS_SINK, None), None);
+        assert_eq!(decide(Never, "avi", h264, TS_SINK, None), None);
+        // neither images nor unknown files are transcoded
+        assert_eq!(decide(Always, "jpg", None, TS_SINK, None), None);
+        assert_eq!(decide(Always, "xyz", None, TS_SINK, None), None);
+    }
+
+    #[test]
+    fn decide_declared_codecs() {
+        use TranscodeMode::*;
+        let hevc = Some(("hevc", "aac"));
+        assert_eq!(
+            decide(Auto, "mkv", hevc, MKV_TS_SINK, None),
+            Some(TranscodeProfile::H264AacTs)
+        );
+        let sink = "http-get:*:video/x-matroska:DLNA.ORG_PN=HEVC_MKV_MAIN";
+        assert_eq!(decide(Auto, "mkv", hevc, sink, None), None);
+        // the profile must be declared for the container of the media
+        let sink = "http-get:*:video/mp4:DLNA.ORG_PN=HEVC_MP4_MAIN,http-get:*:video/x-matroska:*";
+        assert_eq!(
+            decide(Auto, "mkv", hevc, sink, None),
+            Some(TranscodeProfile::H264AacMp4)
+        );
+    }
+
+    #[test]
+    fn decide_remuxing() {
+        use TranscodeMode::*;
+        assert_eq!(
+            decide(Auto, "mkv", Some(("h264", "ac3")), TS_SINK, None),
+            Some(TranscodeProfile::CopyTs)
+        );
+        assert_eq!(
+            decide(Auto, "mkv", Some(("h264", "")), TS_SINK, None),
+            Some(TranscodeProfile::CopyTs)
+        );
+        // codecs which can not be remuxed, or which are unknown, are transcoded
+        assert_eq!(
+            decide(Auto, "mkv", Some(("hevc", "aac")), TS_SINK, None),
+            Some(TranscodeProfile::H264AacTs)
+        );
+        assert_eq!(
+            decide(Auto, "mkv", Some(("h264", "dts")), TS_SINK, None),
+            Some(TranscodeProfile::H264AacTs)
+        );
+        assert_eq!(
+            decide(Auto, "mkv", None, TS_SINK, None),
+            Some(TranscodeProfile::H264AacTs)
+        );
+        // a profile given explicitly is preferred to remuxing
+        assert_eq!(
+            decide(
+                Auto,
+                "mkv",
+                Some(("h264", "aac")),
+                TS_SINK,
+                Some(TranscodeProfile::Mp3)
+            ),
+            Some(TranscodeProfile::Mp3)
+        );
+    }
+
+    #[test]
+    fn decide_profiles() {
+        use TranscodeMode::*;
+        let h264 = Some(("h264", "aac"));
+        assert_eq!(
+            decide(Always, "mkv", h264, MKV_TS_SINK, None),
+            Some(TranscodeProfile::H264AacTs)
+        );
+        assert_eq!(
+            decide(Always, "mkv", h264, "http-get:*:video/mp4:*", None),
+            Some(TranscodeProfile::H264AacMp4)
+        );
+        assert_eq!(
+            decide(Always, "flac", None, TS_SINK, None),
+            Some(TranscodeProfile::Mp3)
+        );
+        assert_eq!(
+            decide(Auto, "flac", None, "http-get:*:audio/mpeg:*", None),
+            Some(TranscodeProfile::Mp3)
+        );
+        // profiles which do not support the kind of the media are ignored
+        let profile = Some(TranscodeProfile::H264AacMp4);
+        assert_eq!(
+            decide(Always, "flac", None, TS_SINK, profile),
+            Some(TranscodeProfile::Mp3)
+        );
+        assert_eq!(
+            decide(Always, "mkv", h264, TS_SINK, profile),
+            Some(TranscodeProfile::H264AacMp4)
+        );
+    }
+
+    #[test]
+    fn parse_transcode_modes() {
+        assert_eq!(
+            " Auto ".parse::<TranscodeMode>().unwrap(),
+            TranscodeMode::Auto
+        );
+        for mode in [
+            TranscodeMode::Auto,
+            TranscodeMode::Never,
+            TranscodeMode::Always,
+        ] {
+            assert_eq!(mode.to_string().parse::<TranscodeMode>().unwrap(), mode);
+        }
+        assert!(matches!(
+            "sometimes".parse::<TranscodeMode>(),
+            Err(Error::TranscodeModeParseError(_))
+        ));
+    }
+}